@@ -0,0 +1,87 @@
+//! Compares drawing a dense grid of labels mesh by mesh with drawing it through the batching of
+//! `Painter::paint_primitives`, which merges consecutive meshes sharing a texture and clip rect.
+//!
+//! Every label is tessellated into a mesh of its own, like the output of a ui that splits its
+//! shapes into many small meshes.
+//!
+//! Run with `cargo run --release --example batching_bench`.
+
+use std::time::{Duration, Instant};
+
+use egui::ClippedPrimitive;
+use egui_skia::Painter;
+use skia_safe::{Color, Surface};
+
+const FRAMES: usize = 200;
+const COLUMNS: usize = 20;
+const ROWS: usize = 50;
+const SIZE: (i32, i32) = (1024, 768);
+
+/// A painter with the font texture uploaded, and the frame of the label grid with a mesh per
+/// label.
+fn setup() -> (Painter, Vec<ClippedPrimitive>) {
+    let ctx = egui::Context::default();
+    let input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(SIZE.0 as f32, SIZE.1 as f32),
+        )),
+        ..Default::default()
+    };
+    let output = ctx.run(input, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::Grid::new("labels").show(ui, |ui| {
+                for row in 0..ROWS {
+                    for column in 0..COLUMNS {
+                        ui.label(format!("{}:{}", row, column));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    });
+    let primitives = output
+        .shapes
+        .into_iter()
+        .flat_map(|shape| ctx.tessellate(vec![shape]))
+        .collect();
+    let mut painter = Painter::new();
+    painter.update_textures(&output.textures_delta);
+    (painter, primitives)
+}
+
+/// Paints the frame `FRAMES` times with `paint` and returns the time it took.
+fn run(mut paint: impl FnMut(&mut Surface)) -> Duration {
+    let mut surface = Surface::new_raster_n32_premul(SIZE).unwrap();
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        surface.canvas().clear(Color::WHITE);
+        paint(&mut surface);
+    }
+    start.elapsed()
+}
+
+pub fn main() {
+    let (painter, primitives) = setup();
+    let one_by_one = run(|surface| {
+        for primitive in &primitives {
+            painter.draw_primitive(surface.canvas(), 1.0, primitive);
+        }
+    });
+    let batched = run(|surface| painter.paint_primitives(surface.canvas(), 1.0, &primitives));
+    let stats = painter.frame_stats();
+
+    let per_frame = |total: Duration| total / FRAMES as u32;
+    println!("meshes:     {}", primitives.len());
+    println!(
+        "draw calls: {} one by one, {} batched",
+        primitives.len(),
+        stats.draw_calls
+    );
+    println!("one by one: {:?} per frame", per_frame(one_by_one));
+    println!("batched:    {:?} per frame", per_frame(batched));
+    println!(
+        "speedup:    {:.2}x",
+        one_by_one.as_secs_f64() / batched.as_secs_f64()
+    );
+}
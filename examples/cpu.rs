@@ -37,12 +37,6 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
         *control_flow = ControlFlow::Wait;
 
         match ev {
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => {
-                *control_flow = ControlFlow::Exit;
-            }
             Event::WindowEvent {
                 event: WindowEvent::Resized(size),
                 ..
@@ -60,6 +54,10 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
                 canvas.clear(skia_safe::Color::TRANSPARENT);
 
                 let repaint_after = egui_skia.run(gc.window(), &mut ui);
+                if egui_skia.should_close() {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
 
                 *control_flow = if repaint_after.is_zero() {
                     gc.window().request_redraw();
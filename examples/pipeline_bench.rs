@@ -0,0 +1,80 @@
+//! Compares running and painting the egui demo on one thread with running it while the
+//! previous frame is painted by a `PipelinedRenderer`.
+//!
+//! Run with `cargo run --release --example pipeline_bench`.
+
+use std::time::{Duration, Instant};
+
+use egui_skia::{FrameData, Painter, PipelinedRenderer};
+use skia_safe::{Color, Surface};
+
+const FRAMES: usize = 200;
+const SIZE: (i32, i32) = (1024, 756);
+
+fn input(time: f64) -> egui::RawInput {
+    egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(SIZE.0 as f32, SIZE.1 as f32),
+        )),
+        time: Some(time),
+        ..Default::default()
+    }
+}
+
+/// Runs the demo for one frame and tessellates it.
+fn run_frame(
+    ctx: &egui::Context,
+    demo: &mut egui_demo_lib::DemoWindows,
+    frame: usize,
+) -> FrameData {
+    let output = ctx.run(input(frame as f64 / 60.0), |ctx| demo.ui(ctx));
+    FrameData {
+        size: SIZE,
+        dpi: ctx.pixels_per_point(),
+        clear_color: Color::WHITE,
+        primitives: ctx.tessellate(output.shapes),
+        textures_delta: output.textures_delta,
+    }
+}
+
+fn serial() -> Duration {
+    let ctx = egui::Context::default();
+    let mut demo = egui_demo_lib::DemoWindows::default();
+    let mut painter = Painter::new();
+    let mut surface = Surface::new_raster_n32_premul(SIZE).unwrap();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        let data = run_frame(&ctx, &mut demo, frame);
+        let canvas = surface.canvas();
+        canvas.clear(data.clear_color);
+        painter.paint_and_update_textures(canvas, data.dpi, data.primitives, data.textures_delta);
+    }
+    start.elapsed()
+}
+
+fn pipelined() -> Duration {
+    let ctx = egui::Context::default();
+    let mut demo = egui_demo_lib::DemoWindows::default();
+    let mut renderer = PipelinedRenderer::new();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        renderer.submit(run_frame(&ctx, &mut demo, frame));
+        renderer.take_finished_image();
+    }
+    // Dropping the renderer waits for the last frame.
+    drop(renderer);
+    start.elapsed()
+}
+
+pub fn main() {
+    let serial = serial();
+    let pipelined = pipelined();
+    let per_frame = |total: Duration| total / FRAMES as u32;
+    println!("serial:    {:?} per frame", per_frame(serial));
+    println!("pipelined: {:?} per frame", per_frame(pipelined));
+    println!(
+        "speedup:   {:.2}x",
+        serial.as_secs_f64() / pipelined.as_secs_f64()
+    );
+}
@@ -0,0 +1,87 @@
+//! Compares painting the egui demo onto the window and a `PictureRecorder` with two full
+//! paints against painting both at once with `Painter::paint_tee`.
+//!
+//! Run with `cargo run --release --example tee_bench`.
+
+use std::time::{Duration, Instant};
+
+use egui_skia::Painter;
+use skia_safe::{Color, PictureRecorder, Rect, Surface};
+
+const FRAMES: usize = 200;
+const SIZE: (i32, i32) = (1024, 756);
+
+fn input(time: f64) -> egui::RawInput {
+    egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(SIZE.0 as f32, SIZE.1 as f32),
+        )),
+        time: Some(time),
+        ..Default::default()
+    }
+}
+
+/// Runs the demo for `FRAMES` frames and calls `paint` with the window surface, the stream
+/// recorder and the tessellated output of every frame. Returns the time spent in `paint`.
+fn run(
+    mut paint: impl FnMut(&mut Surface, &mut PictureRecorder, egui::FullOutput, &egui::Context),
+) -> Duration {
+    let ctx = egui::Context::default();
+    let mut demo = egui_demo_lib::DemoWindows::default();
+    let mut surface = Surface::new_raster_n32_premul(SIZE).unwrap();
+    let mut elapsed = Duration::ZERO;
+    for frame in 0..FRAMES {
+        let output = ctx.run(input(frame as f64 / 60.0), |ctx| demo.ui(ctx));
+        let mut recorder = PictureRecorder::new();
+        let start = Instant::now();
+        paint(&mut surface, &mut recorder, output, &ctx);
+        recorder.finish_recording_as_picture(None);
+        elapsed += start.elapsed();
+    }
+    elapsed
+}
+
+fn bounds() -> Rect {
+    Rect::from_wh(SIZE.0 as f32, SIZE.1 as f32)
+}
+
+fn twice() -> Duration {
+    let mut painter = Painter::new();
+    run(|surface, recorder, output, ctx| {
+        let primitives = ctx.tessellate(output.shapes);
+        let window = surface.canvas();
+        window.clear(Color::WHITE);
+        painter.update_textures(&output.textures_delta);
+        painter.paint_primitives(window, ctx.pixels_per_point(), &primitives);
+        let stream = recorder.begin_recording(bounds(), None);
+        stream.clear(Color::WHITE);
+        painter.paint_primitives(stream, ctx.pixels_per_point(), &primitives);
+        painter.free_textures(&output.textures_delta);
+    })
+}
+
+fn tee() -> Duration {
+    let mut painter = Painter::new();
+    run(|surface, recorder, output, ctx| {
+        let primitives = ctx.tessellate(output.shapes);
+        let window = surface.canvas();
+        window.clear(Color::WHITE);
+        let stream = recorder.begin_recording(bounds(), None);
+        stream.clear(Color::WHITE);
+        let dpi = ctx.pixels_per_point();
+        painter.paint_tee(window, stream, dpi, primitives, output.textures_delta);
+    })
+}
+
+pub fn main() {
+    let twice = twice();
+    let tee = tee();
+    let per_frame = |total: Duration| total / FRAMES as u32;
+    println!("two paints: {:?} per frame", per_frame(twice));
+    println!("paint_tee:  {:?} per frame", per_frame(tee));
+    println!(
+        "speedup:    {:.2}x",
+        twice.as_secs_f64() / tee.as_secs_f64()
+    );
+}
@@ -0,0 +1,68 @@
+//! Compares painting textured quads through the built-in runtime effect with painting them
+//! through plain image shaders, see `TextureStore::with_raw_image_shaders`.
+//!
+//! Run with `cargo run --release --example texture_shader_bench`.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use egui::epaint::{Mesh, Primitive};
+use egui::{ClippedPrimitive, Color32, ColorImage, ImageData, TextureId, TextureOptions};
+use egui_skia::{Painter, TextureStore};
+use skia_safe::{Color, Surface};
+
+const FRAMES: usize = 200;
+const TEXTURES: u64 = 64;
+const SIZE: (i32, i32) = (1024, 768);
+
+/// A painter with `TEXTURES` textures of 64x64 pixels and a frame drawing each of them 16 times.
+fn setup(raw: bool) -> (Painter, Vec<ClippedPrimitive>) {
+    let store = TextureStore::new().with_raw_image_shaders(raw);
+    let mut painter = Painter::with_texture_store(Arc::new(RwLock::new(store)));
+    let mut primitives = Vec::new();
+    for i in 0..TEXTURES {
+        let id = TextureId::User(i);
+        let color = Color32::from_rgb(i as u8 * 4, 255 - i as u8 * 4, 128);
+        let image = ImageData::Color(ColorImage::new([64, 64], color));
+        painter
+            .upload_texture(id, &image, TextureOptions::LINEAR)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        for copy in 0..16 {
+            // Overlapping copies, stacked downwards in a column per texture.
+            let min = egui::pos2((i % 16 * 64) as f32, (i / 16 * 192 + copy * 12) as f32);
+            let rect = egui::Rect::from_min_size(min, egui::vec2(60.0, 48.0));
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        }
+        primitives.push(ClippedPrimitive {
+            clip_rect: egui::Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        });
+    }
+    (painter, primitives)
+}
+
+fn run(raw: bool) -> Duration {
+    let (painter, primitives) = setup(raw);
+    let mut surface = Surface::new_raster_n32_premul(SIZE).unwrap();
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        let canvas = surface.canvas();
+        canvas.clear(Color::WHITE);
+        painter.paint_primitives(canvas, 1.0, &primitives);
+    }
+    start.elapsed()
+}
+
+pub fn main() {
+    let effect = run(false);
+    let raw = run(true);
+    let per_frame = |total: Duration| total / FRAMES as u32;
+    println!("runtime effect: {:?} per frame", per_frame(effect));
+    println!("image shader:   {:?} per frame", per_frame(raw));
+    println!(
+        "speedup:        {:.2}x",
+        effect.as_secs_f64() / raw.as_secs_f64()
+    );
+}
@@ -0,0 +1,83 @@
+//! A transparent window showing only egui's widgets over the desktop.
+
+#[cfg(feature = "winit")]
+fn main() {
+    use egui_skia::{argb_pixels, EguiSkiaWinit, Painter, Platform};
+    use egui_winit::winit::dpi::LogicalSize;
+    use egui_winit::winit::event::{Event, WindowEvent};
+    use egui_winit::winit::event_loop::{ControlFlow, EventLoop};
+    use egui_winit::winit::window::WindowBuilder;
+    use skia_safe::Surface;
+    use softbuffer::GraphicsContext;
+
+    let ev_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Transparent")
+        .with_transparent(true)
+        .with_decorations(false)
+        .with_inner_size(LogicalSize::new(800.0, 600.0))
+        .build(&ev_loop)
+        .unwrap();
+
+    let mut gc = unsafe { GraphicsContext::new(window) }.unwrap();
+    let mut egui_skia = EguiSkiaWinit::new(&ev_loop);
+    // Clears every frame to transparent instead of painting over the last one.
+    egui_skia.egui_skia.painter = Painter::for_platform(Platform::TransparentWindow);
+    egui_skia
+        .egui_winit
+        .set_pixels_per_point(gc.window().scale_factor() as f32);
+
+    let size = gc.window().inner_size();
+    let mut surface =
+        Surface::new_raster_n32_premul((size.width as i32, size.height as i32)).unwrap();
+
+    ev_loop.run(move |ev, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+
+        match ev {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                surface = Surface::new_raster_n32_premul((size.width as i32, size.height as i32))
+                    .unwrap();
+                gc.window().request_redraw();
+            }
+            Event::WindowEvent { event, .. } => {
+                egui_skia.on_event(&event);
+                gc.window().request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let mut quit = false;
+                egui_skia.run(gc.window(), |ctx| {
+                    egui::Window::new("Overlay").show(ctx, |ui| {
+                        ui.label("The desktop shows through around this window.");
+                        quit = ui.button("Quit").clicked();
+                    });
+                });
+                if quit {
+                    *control_flow = ControlFlow::Exit;
+                }
+
+                egui_skia.paint(surface.canvas());
+
+                // Keeps the alpha channel, so the compositor shows the desktop where egui drew
+                // nothing.
+                let pixels = argb_pixels(&surface.image_snapshot()).unwrap();
+                gc.set_buffer(&pixels, surface.width() as u16, surface.height() as u16);
+            }
+            _ => {}
+        }
+    })
+}
+
+#[cfg(not(feature = "winit"))]
+pub fn main() {
+    println!("This example requires the winit feature to be enabled");
+}
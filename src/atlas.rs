@@ -0,0 +1,119 @@
+use egui::epaint::ahash::AHashMap;
+use egui::TextureId;
+use skia_safe::{BlendMode, ClipOp, Data, IPoint, IRect, Image, ImageInfo, Paint, Point, Surface};
+
+/// Width and height of the atlas in pixels.
+const ATLAS_SIZE: i32 = 1024;
+/// Textures larger than this on either side are not packed.
+const MAX_ENTRY_SIZE: i32 = 64;
+
+/// Small textures packed into one image, see
+/// [`TextureStore::with_texture_atlas`](crate::TextureStore::with_texture_atlas).
+///
+/// Textures are packed into rows from the top left. The region of a freed texture isn't reused
+/// unless a texture with the same id and size is uploaded again, textures that don't fit any
+/// more stay standalone images.
+pub(crate) struct TextureAtlas {
+    /// The premultiplied N32 pixels the textures are drawn into.
+    pixels: Vec<u8>,
+    /// A snapshot of `pixels` as of the last [`Self::snapshot`].
+    pub(crate) image: Image,
+    pub(crate) regions: AHashMap<TextureId, IRect>,
+    /// The next free position in the current row.
+    cursor: IPoint,
+    row_height: i32,
+    /// Whether `pixels` changed since the last snapshot.
+    pub(crate) dirty: bool,
+}
+
+impl TextureAtlas {
+    pub(crate) fn new() -> Option<Self> {
+        let pixels = vec![0; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize];
+        let image = Image::from_raster_data(
+            &Self::image_info(),
+            Data::new_copy(&pixels),
+            ATLAS_SIZE as usize * 4,
+        )?;
+        Some(Self {
+            pixels,
+            image,
+            regions: AHashMap::default(),
+            cursor: IPoint::new(0, 0),
+            row_height: 0,
+            dirty: false,
+        })
+    }
+
+    fn image_info() -> ImageInfo {
+        ImageInfo::new_n32_premul((ATLAS_SIZE, ATLAS_SIZE), None)
+    }
+
+    /// Copies `image` into the region of texture `id` and returns the region, or `None` if the
+    /// image is too large or the atlas is full.
+    ///
+    /// The region of `id` is reused if it has the size of `image`.
+    pub(crate) fn insert(&mut self, id: TextureId, image: &Image) -> Option<IRect> {
+        let (width, height) = (image.width(), image.height());
+        if width > MAX_ENTRY_SIZE || height > MAX_ENTRY_SIZE {
+            return None;
+        }
+        let region = match self.regions.get(&id) {
+            Some(region) if region.width() == width && region.height() == height => *region,
+            _ => {
+                // One pixel of padding on each side for the repeated edges.
+                let (padded_width, padded_height) = (width + 2, height + 2);
+                if self.cursor.x + padded_width > ATLAS_SIZE {
+                    self.cursor = IPoint::new(0, self.cursor.y + self.row_height);
+                    self.row_height = 0;
+                }
+                if self.cursor.y + padded_height > ATLAS_SIZE {
+                    return None;
+                }
+                let region = IRect::from_xywh(self.cursor.x + 1, self.cursor.y + 1, width, height);
+                self.cursor.x += padded_width;
+                self.row_height = self.row_height.max(padded_height);
+                self.regions.insert(id, region);
+                region
+            }
+        };
+
+        let info = Self::image_info();
+        let row_bytes = info.min_row_bytes();
+        let mut surface = Surface::new_raster_direct(&info, &mut self.pixels, row_bytes, None)?;
+        let canvas = surface.canvas();
+        canvas.clip_irect(region.with_outset((1, 1)), ClipOp::Intersect);
+        let mut copy = Paint::default();
+        copy.set_blend_mode(BlendMode::Src);
+        // Drawing the image shifted by a pixel in every direction before drawing it in place
+        // repeats its edges into the padding, so linear filtering at the edges of the texture
+        // samples the texture itself instead of its neighbors, like the clamp tile mode of
+        // standalone textures.
+        for (dx, dy) in [
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+            (0, -1),
+            (0, 1),
+            (-1, 0),
+            (1, 0),
+            (0, 0),
+        ] {
+            let pos = Point::new((region.left + dx) as f32, (region.top + dy) as f32);
+            canvas.draw_image(image, pos, Some(&copy));
+        }
+        self.dirty = true;
+        Some(region)
+    }
+
+    /// Updates [`Self::image`] to the current pixels.
+    pub(crate) fn snapshot(&mut self) -> Option<()> {
+        self.image = Image::from_raster_data(
+            &Self::image_info(),
+            Data::new_copy(&self.pixels),
+            ATLAS_SIZE as usize * 4,
+        )?;
+        self.dirty = false;
+        Some(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::borrow::Cow;
+
+use egui::epaint::{Mesh, Primitive};
+use egui::{ClippedPrimitive, Rect};
+
+/// Merges consecutive meshes with the same texture and clip rect, so they are drawn with one
+/// draw call. egui emits a mesh per shape run, so text heavy uis produce long runs of small
+/// meshes on the font texture.
+///
+/// Meshes are never reordered, and a paint callback, a change of clip rect or texture, or a
+/// mesh `keep_apart` returns `true` for ends the run. Unmerged primitives are borrowed.
+pub(crate) fn batch_primitives<'a>(
+    primitives: &'a [ClippedPrimitive],
+    keep_apart: impl Fn(&Mesh) -> bool,
+) -> Vec<Cow<'a, ClippedPrimitive>> {
+    let mut batched: Vec<Cow<'a, ClippedPrimitive>> = Vec::with_capacity(primitives.len());
+    // Whether the last primitive may be merged with the next one.
+    let mut open = false;
+    for primitive in primitives {
+        let mesh = match &primitive.primitive {
+            Primitive::Mesh(mesh) if !keep_apart(mesh) => mesh,
+            _ => {
+                batched.push(Cow::Borrowed(primitive));
+                open = false;
+                continue;
+            }
+        };
+        if let Some(last) = batched.last_mut().filter(|_| open) {
+            let same_texture = matches!(
+                &last.primitive,
+                Primitive::Mesh(last_mesh) if last_mesh.texture_id == mesh.texture_id
+            );
+            if same_texture && last.clip_rect == primitive.clip_rect {
+                if let Primitive::Mesh(last_mesh) = &mut last.to_mut().primitive {
+                    last_mesh.append(mesh.clone());
+                }
+                continue;
+            }
+        }
+        batched.push(Cow::Borrowed(primitive));
+        open = true;
+    }
+    batched
+}
+
+/// Merges mesh primitives so that at most `max_draw_calls` meshes remain, where possible.
+///
+/// Consecutive meshes with the same texture and clip rect are always merged. If that isn't
+/// enough, neighbouring meshes with the same texture but different clip rects are merged, using
+/// the union of their clip rects. The pairs whose union adds the least area are merged first,
+/// trading a bit of overdraw outside the original clip rects for fewer draw calls. Meshes are
+/// never reordered and never merged across a paint callback.
+pub(crate) fn merge_primitives(
+    primitives: &[ClippedPrimitive],
+    max_draw_calls: usize,
+) -> Vec<ClippedPrimitive> {
+    let mut merged = batch_primitives(primitives, |_| false)
+        .into_iter()
+        .map(Cow::into_owned)
+        .collect::<Vec<_>>();
+
+    let mut mesh_count = merged
+        .iter()
+        .filter(|p| matches!(p.primitive, Primitive::Mesh(_)))
+        .count();
+
+    while mesh_count > max_draw_calls {
+        let candidate = merged
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| match (&pair[0].primitive, &pair[1].primitive) {
+                (Primitive::Mesh(a), Primitive::Mesh(b)) if a.texture_id == b.texture_id => {
+                    let union = pair[0].clip_rect.union(pair[1].clip_rect);
+                    let cost = area(union) - area(pair[0].clip_rect) - area(pair[1].clip_rect);
+                    Some((i, cost))
+                }
+                _ => None,
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let Some((i, _)) = candidate else {
+            break;
+        };
+
+        let next = merged.remove(i + 1);
+        let current = &mut merged[i];
+        current.clip_rect = current.clip_rect.union(next.clip_rect);
+        if let (Primitive::Mesh(mesh), Primitive::Mesh(next_mesh)) =
+            (&mut current.primitive, next.primitive)
+        {
+            mesh.append(next_mesh);
+        }
+        mesh_count -= 1;
+    }
+
+    merged
+}
+
+fn area(rect: Rect) -> f32 {
+    rect.width().max(0.0) * rect.height().max(0.0)
+}
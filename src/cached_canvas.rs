@@ -0,0 +1,207 @@
+use egui::epaint::ahash::AHashMap;
+use egui::{
+    pos2, Color32, ColorImage, Context, Id, Rect, Response, Sense, TextureHandle, TextureOptions,
+    Ui, Vec2,
+};
+use skia_safe::{AlphaType, Canvas, ColorType, ImageInfo, Surface};
+
+/// Entries not shown for this many frames are dropped, freeing their textures.
+const EVICT_AFTER_FRAMES: u64 = 60;
+
+#[derive(Clone)]
+struct Entry {
+    texture: TextureHandle,
+    revision: u64,
+    size: [usize; 2],
+    pixels_per_point: f32,
+    last_frame: u64,
+}
+
+/// The cached images of all [`CachedCanvas`]es, kept in egui's temporary data.
+#[derive(Clone, Default)]
+struct CacheStore {
+    entries: AHashMap<Id, Entry>,
+    frame: u64,
+    /// The egui time of `frame`, a new time starts a new frame.
+    time: f64,
+}
+
+fn store_id() -> Id {
+    Id::new("egui_skia_cached_canvas")
+}
+
+/// Draws with skia into an image that is only redrawn when its content changes, for content
+/// that is expensive to draw but rarely changes, like a waveform.
+///
+/// The image is redrawn when the revision passed to [`Self::show`] changes, when the size of
+/// the widget or the pixels per point change, and after [`Self::invalidate`]. Otherwise the
+/// cached image is drawn as a regular egui texture. Images of widgets that aren't shown for a
+/// while are dropped.
+pub struct CachedCanvas {
+    id: Id,
+}
+
+impl CachedCanvas {
+    pub fn new(id: impl Into<Id>) -> Self {
+        Self { id: id.into() }
+    }
+
+    /// Makes the next [`Self::show`] redraw the image.
+    pub fn invalidate(&self, ctx: &Context) {
+        let mut data = ctx.data();
+        let store = data.get_temp_mut_or_default::<CacheStore>(store_id());
+        store.entries.remove(&self.id);
+    }
+
+    /// Allocates `size` points and shows the image, redrawing it with `draw` if needed.
+    ///
+    /// `draw` gets a canvas scaled to points, with the origin at the top left corner of the
+    /// widget.
+    pub fn show(
+        &self,
+        ui: &mut Ui,
+        size: Vec2,
+        revision: u64,
+        draw: impl FnOnce(&mut Canvas),
+    ) -> Response {
+        let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+        let ctx = ui.ctx().clone();
+        let pixels_per_point = ctx.pixels_per_point();
+        let pixels = (size * pixels_per_point).round();
+        let pixel_size = [pixels.x.max(1.0) as usize, pixels.y.max(1.0) as usize];
+        let time = ctx.input().time;
+
+        // The entry is taken out of the store while it is drawn, egui's data can't stay locked
+        // while loading textures.
+        let (frame, entry) = {
+            let mut data = ctx.data();
+            let store = data.get_temp_mut_or_default::<CacheStore>(store_id());
+            if store.time != time {
+                store.time = time;
+                store.frame += 1;
+                let frame = store.frame;
+                store
+                    .entries
+                    .retain(|_, entry| entry.last_frame + EVICT_AFTER_FRAMES >= frame);
+            }
+            (store.frame, store.entries.remove(&self.id))
+        };
+
+        let entry = match entry {
+            Some(mut entry)
+                if entry.revision == revision
+                    && entry.size == pixel_size
+                    && entry.pixels_per_point == pixels_per_point =>
+            {
+                entry.last_frame = frame;
+                entry
+            }
+            entry => {
+                let image = match render(pixel_size, pixels_per_point, draw) {
+                    Some(image) => image,
+                    None => return response,
+                };
+                let texture = match entry {
+                    Some(mut entry) => {
+                        entry.texture.set(image, TextureOptions::LINEAR);
+                        entry.texture
+                    }
+                    None => ctx.load_texture(
+                        format!("cached_canvas_{:?}", self.id),
+                        image,
+                        TextureOptions::LINEAR,
+                    ),
+                };
+                Entry {
+                    texture,
+                    revision,
+                    size: pixel_size,
+                    pixels_per_point,
+                    last_frame: frame,
+                }
+            }
+        };
+        let texture = entry.texture.id();
+        ctx.data()
+            .get_temp_mut_or_default::<CacheStore>(store_id())
+            .entries
+            .insert(self.id, entry);
+
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        ui.painter().image(texture, rect, uv, Color32::WHITE);
+        response
+    }
+}
+
+/// Draws `draw` into a raster image of `size` pixels.
+fn render(
+    size: [usize; 2],
+    pixels_per_point: f32,
+    draw: impl FnOnce(&mut Canvas),
+) -> Option<ColorImage> {
+    let dimensions = (size[0] as i32, size[1] as i32);
+    let mut surface = Surface::new_raster_n32_premul(dimensions)?;
+    let canvas = surface.canvas();
+    canvas.scale((pixels_per_point, pixels_per_point));
+    draw(canvas);
+
+    // egui takes unmultiplied colors, skia converts while reading.
+    let info = ImageInfo::new(dimensions, ColorType::RGBA8888, AlphaType::Unpremul, None);
+    let mut pixels = vec![0; size[0] * size[1] * 4];
+    if !surface.read_pixels(&info, &mut pixels, size[0] * 4, (0, 0)) {
+        return None;
+    }
+    Some(ColorImage::from_rgba_unmultiplied(size, &pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::EguiSkia;
+
+    #[test]
+    fn drawings_are_redrawn_only_when_revision_size_or_dpi_change() {
+        let mut egui_skia = EguiSkia::new();
+        let (canvas, other) = (CachedCanvas::new("waveform"), CachedCanvas::new("other"));
+        let draws = Cell::new(0);
+        let mut time = 0.0;
+        // Shows `canvas` if `shown` and `other` otherwise, returning how often `canvas` drew.
+        let mut frame = |egui_skia: &mut EguiSkia, shown, revision: u64, size: Vec2, ppp: f32| {
+            time += 1.0;
+            let input = egui::RawInput {
+                time: Some(time),
+                pixels_per_point: Some(ppp),
+                ..Default::default()
+            };
+            egui_skia.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if shown {
+                        canvas.show(ui, size, revision, |_| draws.set(draws.get() + 1));
+                    } else {
+                        other.show(ui, size, 0, |_| {});
+                    }
+                });
+            });
+            draws.get()
+        };
+        let (small, wide) = (Vec2::new(20.0, 10.0), Vec2::new(30.0, 10.0));
+
+        assert_eq!(frame(&mut egui_skia, true, 0, small, 1.0), 1);
+        assert_eq!(frame(&mut egui_skia, true, 0, small, 1.0), 1);
+        assert_eq!(frame(&mut egui_skia, true, 1, small, 1.0), 2);
+        assert_eq!(frame(&mut egui_skia, true, 1, wide, 1.0), 3);
+        assert_eq!(frame(&mut egui_skia, true, 1, wide, 2.0), 4);
+        assert_eq!(frame(&mut egui_skia, true, 1, wide, 2.0), 4);
+
+        canvas.invalidate(&egui_skia.egui_ctx);
+        assert_eq!(frame(&mut egui_skia, true, 1, wide, 2.0), 5);
+
+        // Hidden for long enough, the image is dropped and drawn again when shown.
+        for _ in 0..=EVICT_AFTER_FRAMES {
+            frame(&mut egui_skia, false, 0, wide, 2.0);
+        }
+        assert_eq!(frame(&mut egui_skia, true, 1, wide, 2.0), 6);
+    }
+}
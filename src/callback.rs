@@ -0,0 +1,698 @@
+use std::cell::Cell;
+#[cfg(feature = "gpu")]
+use std::cell::RefCell;
+#[cfg(feature = "gpu")]
+use std::collections::HashMap;
+#[cfg(feature = "gpu")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "gpu")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use skia_safe::{
+    Canvas, ClipOp, ConditionallySend, Drawable, FilterMode, Image, Matrix, MipmapMode, Paint,
+    Path, PathEffect, Picture, PictureRecorder, RRect, Rect, SamplingOptions, Sendable, Vector,
+};
+
+/// Controls how [`EguiSkiaPaintCallback::image`] maps an image onto the callback rect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fit {
+    /// Stretch the image to the rect, ignoring its aspect ratio.
+    Fill,
+    /// Scale the image to fit inside the rect while keeping its aspect ratio, leaving the rest
+    /// of the rect empty (letterboxing).
+    Contain,
+    /// Scale the image to cover the whole rect while keeping its aspect ratio, cropping the parts
+    /// that don't fit.
+    Cover,
+}
+
+impl Fit {
+    /// Returns the destination rect of content of `size` drawn into `bounds`.
+    pub(crate) fn dst_rect(self, size: (f32, f32), bounds: Rect) -> Rect {
+        let (width, height) = size;
+        if width <= 0.0 || height <= 0.0 {
+            return bounds;
+        }
+        let scale_x = bounds.width() / width;
+        let scale_y = bounds.height() / height;
+        let scale = match self {
+            Fit::Fill => return bounds,
+            Fit::Contain => scale_x.min(scale_y),
+            Fit::Cover => scale_x.max(scale_y),
+        };
+        Rect::from_xywh(
+            bounds.center_x() - width * scale / 2.0,
+            bounds.center_y() - height * scale / 2.0,
+            width * scale,
+            height * scale,
+        )
+    }
+}
+
+/// Controls how [`EguiSkiaPaintCallback::from_picture`] maps the cull rect of a picture onto the
+/// callback rect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PictureFit {
+    /// Stretch the picture to the rect, ignoring its aspect ratio.
+    Stretch,
+    /// Scale the picture to fit inside the rect while keeping its aspect ratio.
+    Contain,
+    /// Scale the picture to cover the whole rect while keeping its aspect ratio, cropping the
+    /// parts that don't fit.
+    Cover,
+    /// Draw the picture unscaled, centered in the rect.
+    None,
+}
+
+impl PictureFit {
+    /// Returns the destination rect of a picture with the cull rect `cull` drawn into `bounds`.
+    fn dst_rect(self, cull: Rect, bounds: Rect) -> Rect {
+        let size = (cull.width(), cull.height());
+        match self {
+            PictureFit::Stretch => Fit::Fill.dst_rect(size, bounds),
+            PictureFit::Contain => Fit::Contain.dst_rect(size, bounds),
+            PictureFit::Cover => Fit::Cover.dst_rect(size, bounds),
+            PictureFit::None => Rect::from_xywh(
+                bounds.center_x() - size.0 / 2.0,
+                bounds.center_y() - size.1 / 2.0,
+                size.0,
+                size.1,
+            ),
+        }
+    }
+}
+
+/// Information about the frame a callback is drawn in, passed to the closure of
+/// [`EguiSkiaPaintCallback::animated`].
+#[derive(Clone, Copy, Debug)]
+pub struct CallbackInfo {
+    /// The bounds of the callback rect in points, with the origin at the top left corner of the
+    /// rect.
+    pub bounds: Rect,
+    /// The animation time of the frame in seconds, as set by
+    /// [`Painter::set_animation_time`](crate::Painter::set_animation_time).
+    pub time: f64,
+    /// The matrix mapping the callback content to the pixels of the target canvas: the matrix
+    /// the canvas had when painting started, the dpi scale and the position of the callback.
+    /// With [`Painter::paint_tee`](crate::Painter::paint_tee), the matrix of the primary canvas.
+    pub transform: Matrix,
+}
+
+/// Lets a paint callback schedule another frame, see [`EguiSkiaPaintCallback::animated`].
+#[derive(Debug, Default)]
+pub struct CallbackResponse {
+    repaint_after: Cell<Option<Duration>>,
+}
+
+impl CallbackResponse {
+    /// Requests a repaint after `duration`, like [`egui::Context::request_repaint_after`]. The
+    /// shortest duration requested by the callbacks of a frame wins.
+    pub fn request_repaint_after(&self, duration: Duration) {
+        let repaint_after = match self.repaint_after.get() {
+            Some(requested) => requested.min(duration),
+            None => duration,
+        };
+        self.repaint_after.set(Some(repaint_after));
+    }
+
+    /// Requests a repaint as soon as possible.
+    pub fn request_repaint(&self) {
+        self.request_repaint_after(Duration::ZERO);
+    }
+
+    /// The shortest duration requested so far, if any.
+    pub(crate) fn repaint_after(&self) -> Option<Duration> {
+        self.repaint_after.get()
+    }
+}
+
+type RecordCallback =
+    dyn Fn(&CallbackInfo, &CallbackResponse) -> SyncSendableDrawable + Send + Sync;
+
+enum Source {
+    Record(Box<RecordCallback>),
+    Picture(Picture, PictureFit),
+}
+
+pub struct EguiSkiaPaintCallback {
+    source: Source,
+}
+
+/// What a paint callback draws in a frame, see [`EguiSkiaPaintCallback::content`].
+pub(crate) enum CallbackContent {
+    Drawable(Drawable),
+    Picture(Picture, PictureFit),
+}
+
+impl CallbackContent {
+    /// Draws the content into `bounds`, the callback rect with the origin at its top left
+    /// corner.
+    pub(crate) fn draw(&mut self, canvas: &mut Canvas, bounds: Rect) {
+        match self {
+            CallbackContent::Drawable(drawable) => drawable.draw(canvas, None),
+            CallbackContent::Picture(picture, fit) => {
+                let cull = picture.cull_rect();
+                if cull.is_empty() {
+                    return;
+                }
+                let dst = fit.dst_rect(cull, bounds);
+                canvas.save();
+                canvas.clip_rect(bounds, ClipOp::default(), true);
+                canvas.translate((dst.left, dst.top));
+                canvas.scale((dst.width() / cull.width(), dst.height() / cull.height()));
+                canvas.translate((-cull.left, -cull.top));
+                canvas.draw_picture(picture, None, None);
+                canvas.restore();
+            }
+        }
+    }
+}
+
+impl EguiSkiaPaintCallback {
+    pub fn new<F: Fn(&mut Canvas) + Send + Sync + 'static>(callback: F) -> EguiSkiaPaintCallback {
+        Self::with_bounds(move |canvas, _| callback(canvas))
+    }
+
+    /// Like [`Self::new`], but the callback also receives the bounds of the callback rect in
+    /// points, with the origin at the top left corner of the rect.
+    pub fn with_bounds<F: Fn(&mut Canvas, Rect) + Send + Sync + 'static>(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        Self::animated(move |canvas, info, _| callback(canvas, info.bounds))
+    }
+
+    /// Like [`Self::with_bounds`], but the callback gets the animation time of the frame and can
+    /// request another frame through the [`CallbackResponse`], e.g. to keep animated content
+    /// running when the app only repaints on demand.
+    ///
+    /// [`EguiSkia::paint`](crate::EguiSkia::paint) returns the requested repaint delay.
+    pub fn animated<F: Fn(&mut Canvas, &CallbackInfo, &CallbackResponse) + Send + Sync + 'static>(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        EguiSkiaPaintCallback {
+            source: Source::Record(Box::new(move |info, response| {
+                let mut pr = PictureRecorder::new();
+                let mut canvas = pr.begin_recording(info.bounds, None);
+                callback(&mut canvas, info, response);
+                SyncSendableDrawable(
+                    pr.finish_recording_as_drawable()
+                        .unwrap()
+                        .wrap_send()
+                        .unwrap(),
+                )
+            })),
+        }
+    }
+
+    /// Draws a prebuilt `picture`, e.g. a logo loaded from an `.skp`, into the callback rect
+    /// with its cull rect mapped according to `fit`.
+    ///
+    /// The picture is drawn directly instead of being recorded again every frame. Pictures are
+    /// reference counted, so one picture can be cloned into the callbacks of many widgets and
+    /// frames cheaply.
+    pub fn from_picture(picture: Picture, fit: PictureFit) -> EguiSkiaPaintCallback {
+        EguiSkiaPaintCallback {
+            source: Source::Picture(picture, fit),
+        }
+    }
+
+    /// Records or returns the content of the callback for a frame.
+    pub(crate) fn content(
+        &self,
+        info: &CallbackInfo,
+        response: &CallbackResponse,
+    ) -> CallbackContent {
+        match &self.source {
+            Source::Record(record) => CallbackContent::Drawable(record(info, response).0.unwrap()),
+            Source::Picture(picture, fit) => CallbackContent::Picture(picture.clone(), *fit),
+        }
+    }
+
+    /// Like [`Self::new`], but the drawing is clipped (anti aliased) to the callback rect with
+    /// its corners rounded by `rounding`, e.g. to keep a map inside a rounded frame.
+    ///
+    /// The radii are in points, like the rest of the drawing.
+    pub fn new_clipped_rrect<F: Fn(&mut Canvas) + Send + Sync + 'static>(
+        rounding: egui::Rounding,
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        Self::with_bounds(move |canvas, bounds| {
+            let radii = [
+                Vector::new(rounding.nw, rounding.nw),
+                Vector::new(rounding.ne, rounding.ne),
+                Vector::new(rounding.se, rounding.se),
+                Vector::new(rounding.sw, rounding.sw),
+            ];
+            canvas.clip_rrect(
+                RRect::new_rect_radii(bounds, &radii),
+                ClipOp::default(),
+                true,
+            );
+            callback(canvas);
+        })
+    }
+
+    /// Draws `image` into the callback rect, scaled according to `fit`.
+    ///
+    /// Useful for showing a video frame or a decoded photo without registering it as an egui
+    /// texture.
+    pub fn image(image: Image, fit: Fit) -> EguiSkiaPaintCallback {
+        Self::with_bounds(move |canvas, bounds| {
+            let dst = fit.dst_rect((image.width() as f32, image.height() as f32), bounds);
+            canvas.clip_rect(bounds, ClipOp::default(), true);
+            canvas.draw_image_rect_with_sampling_options(
+                &image,
+                None,
+                dst,
+                SamplingOptions::new(FilterMode::Linear, MipmapMode::None),
+                &Paint::default(),
+            );
+        })
+    }
+
+    /// Draws `path` with `paint`, in points relative to the top left corner of the callback rect.
+    ///
+    /// Use this for smooth bezier and conic curves in charts or vector art; enable anti aliasing
+    /// on the paint for smooth edges.
+    pub fn path(path: Path, paint: Paint) -> EguiSkiaPaintCallback {
+        Self::new(move |canvas| {
+            canvas.draw_path(&path, &paint);
+        })
+    }
+
+    /// Draws the image `render` draws into a GPU surface of `cache`. `render` only runs when the
+    /// cache has no image of the size of the callback rect yet, e.g. for the first frame, after
+    /// a resize or after [`GpuCache::invalidate`], so expensive content like a 3D scene isn't
+    /// drawn again every frame.
+    ///
+    /// Keep the cache across frames and create the callback from it every frame. `render` draws
+    /// in points, like [`Self::new`].
+    #[cfg(feature = "gpu")]
+    pub fn gpu_cached<F: Fn(&mut Canvas) + Send + Sync + 'static>(
+        render: F,
+        cache: &Arc<GpuCache>,
+    ) -> EguiSkiaPaintCallback {
+        let cache = cache.clone();
+        Self::with_bounds(move |canvas, bounds| {
+            if let Some(image) = cache.image(bounds, &render) {
+                canvas.draw_image_rect(image, None, bounds, &Paint::default());
+            }
+        })
+    }
+
+    /// Strokes `path` with `paint` like [`Self::path`], but with the same stroke width in every
+    /// direction when the target canvas is scaled non-uniformly, see [`stroke_path_uniform`].
+    pub fn uniform_stroke_path(path: Path, paint: Paint) -> EguiSkiaPaintCallback {
+        Self::animated(move |canvas, info, _| {
+            stroke_path_uniform(canvas, &info.transform, &path, &paint);
+        })
+    }
+
+    /// Strokes the border of the callback rect with `paint`, dashed by skia's dash path effect.
+    ///
+    /// `intervals` alternates the lengths of the on and off segments in points, `phase` offsets
+    /// the start of the pattern. The border is inset by half the stroke width so it stays inside
+    /// the rect.
+    ///
+    /// Returns `None` if `intervals` is empty or has an odd number of entries.
+    pub fn dashed_rect(
+        intervals: &[f32],
+        phase: f32,
+        mut paint: Paint,
+    ) -> Option<EguiSkiaPaintCallback> {
+        paint.set_style(skia_safe::PaintStyle::Stroke);
+        paint.set_path_effect(PathEffect::dash(intervals, phase)?);
+        Some(Self::with_bounds(move |canvas, bounds| {
+            let inset = paint.stroke_width() / 2.0;
+            canvas.draw_rect(bounds.with_inset((inset, inset)), &paint);
+        }))
+    }
+}
+
+/// Strokes `path` with `paint` on `canvas`, which is drawn with `transform` (usually
+/// [`CallbackInfo::transform`]), keeping the stroke equally wide in every direction.
+///
+/// skia scales strokes with the matrix, so a canvas scaled twice as much horizontally draws
+/// vertical lines twice as wide as horizontal ones. This transforms the path to pixels instead
+/// and strokes it there, with the stroke width scaled by the average scale of `transform`, so
+/// uniformly scaled canvases look the same as with a plain `draw_path`.
+pub fn stroke_path_uniform(canvas: &mut Canvas, transform: &Matrix, path: &Path, paint: &Paint) {
+    let inverse = match transform.invert() {
+        Some(inverse) => inverse,
+        None => return,
+    };
+    let determinant =
+        transform.scale_x() * transform.scale_y() - transform.skew_x() * transform.skew_y();
+    let mut paint = paint.clone();
+    paint.set_stroke_width(paint.stroke_width() * determinant.abs().sqrt());
+
+    canvas.save();
+    canvas.concat(&inverse);
+    canvas.draw_path(&path.with_transform(transform), &paint);
+    canvas.restore();
+}
+
+pub(crate) struct SyncSendableDrawable(pub Sendable<Drawable>);
+
+unsafe impl Sync for SyncSendableDrawable {}
+
+/// The GPU context and the cached image of [`EguiSkiaPaintCallback::gpu_cached`].
+///
+/// The context and the image stay on the thread that created the cache, the thread that owns
+/// the context and paints egui. The cache itself only holds a key to them, so callbacks made from
+/// it can be sent with the rest of the frame; painted on any other thread they draw nothing.
+#[cfg(feature = "gpu")]
+pub struct GpuCache {
+    key: u64,
+    pixels_per_point: f32,
+    generation: AtomicU64,
+}
+
+#[cfg(feature = "gpu")]
+struct GpuCacheState {
+    context: skia_safe::gpu::DirectContext,
+    /// The image and the generation of the cache it was rendered at.
+    image: Option<(Image, u64)>,
+}
+
+#[cfg(feature = "gpu")]
+static NEXT_GPU_CACHE: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "gpu")]
+thread_local! {
+    static GPU_CACHES: RefCell<HashMap<u64, GpuCacheState>> = RefCell::default();
+}
+
+#[cfg(feature = "gpu")]
+impl GpuCache {
+    /// Creates a cache rendering on `context`, the context of the surface egui is painted to,
+    /// at `pixels_per_point`. Create it on the thread that paints with `context`.
+    pub fn new(context: skia_safe::gpu::DirectContext, pixels_per_point: f32) -> Arc<Self> {
+        let key = NEXT_GPU_CACHE.fetch_add(1, Ordering::Relaxed);
+        let state = GpuCacheState {
+            context,
+            image: None,
+        };
+        GPU_CACHES.with(|caches| caches.borrow_mut().insert(key, state));
+        Arc::new(Self {
+            key,
+            pixels_per_point,
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    /// Drops the cached image, so the content is rendered again the next time it is painted.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The cached image for `bounds`, rendering it with `render` first if needed.
+    fn image(&self, bounds: Rect, render: &dyn Fn(&mut Canvas)) -> Option<Image> {
+        let size = skia_safe::ISize::new(
+            (bounds.width() * self.pixels_per_point).ceil() as i32,
+            (bounds.height() * self.pixels_per_point).ceil() as i32,
+        );
+        let generation = self.generation.load(Ordering::Relaxed);
+        // Taken out of the map while rendering, so `render` may paint other cached callbacks.
+        let mut state = GPU_CACHES.with(|caches| caches.borrow_mut().remove(&self.key))?;
+        let cached = state
+            .image
+            .as_ref()
+            .filter(|(image, rendered)| *rendered == generation && image.dimensions() == size);
+        let image = match cached {
+            Some((image, _)) => Some(image.clone()),
+            None => self.render(&mut state.context, size, render),
+        };
+        state.image = image.clone().map(|image| (image, generation));
+        GPU_CACHES.with(|caches| caches.borrow_mut().insert(self.key, state));
+        image
+    }
+
+    fn render(
+        &self,
+        context: &mut skia_safe::gpu::DirectContext,
+        size: skia_safe::ISize,
+        render: &dyn Fn(&mut Canvas),
+    ) -> Option<Image> {
+        let mut surface = skia_safe::Surface::new_render_target(
+            context,
+            skia_safe::Budgeted::Yes,
+            &skia_safe::ImageInfo::new_n32_premul(size, None),
+            None,
+            skia_safe::gpu::SurfaceOrigin::TopLeft,
+            None,
+            false,
+        )?;
+        let canvas = surface.canvas();
+        canvas.scale((self.pixels_per_point, self.pixels_per_point));
+        render(canvas);
+        Some(surface.image_snapshot())
+    }
+}
+
+#[cfg(feature = "gpu")]
+impl Drop for GpuCache {
+    fn drop(&mut self) {
+        // Dropped on another thread, the state stays until the creating thread exits.
+        let _ = GPU_CACHES.try_with(|caches| caches.borrow_mut().remove(&self.key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+    use skia_safe::Color;
+
+    use super::*;
+    use crate::test_util::{
+        assert_color_near, callback_primitive, painter, pixel, solid_image, surface,
+    };
+    #[cfg(feature = "gl")]
+    use crate::test_util::{gl_context, read_back, render_target};
+
+    #[test]
+    fn image_contain_letterboxes() {
+        let painter = painter();
+        let callback = EguiSkiaPaintCallback::image(solid_image(10, 10, Color::RED), Fit::Contain);
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(20.0, 10.0));
+        let mut surface = surface(20, 10);
+        painter.paint_primitives(surface.canvas(), 1.0, &[callback_primitive(rect, callback)]);
+
+        // The square image is centered, leaving bars on the left and right of the wide rect.
+        assert_color_near(pixel(&mut surface, 2, 5), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 10, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 17, 5), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn clipped_rrect_keeps_the_rounded_corners_transparent() {
+        let painter = painter();
+        let rounding = egui::Rounding {
+            nw: 15.0,
+            ne: 0.0,
+            sw: 5.0,
+            se: 15.0,
+        };
+        let fill = |canvas: &mut Canvas| {
+            canvas.draw_color(Color::RED, None);
+        };
+        let callback = EguiSkiaPaintCallback::new_clipped_rrect(rounding, fill);
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(40.0, 40.0));
+        let mut surface = surface(80, 80);
+        painter.paint_primitives(surface.canvas(), 2.0, &[callback_primitive(rect, callback)]);
+
+        // The radii are scaled with the dpi: (6, 6) is outside of a 30 pixel corner only.
+        assert_color_near(pixel(&mut surface, 6, 6), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 78, 78), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 1, 78), Color::TRANSPARENT, 0);
+        // The corner without rounding and the inside are filled.
+        assert_color_near(pixel(&mut surface, 79, 0), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 40, 40), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 20, 20), Color::RED, 0);
+    }
+
+    #[test]
+    fn dashed_rect_alternates_on_and_off_segments() {
+        let painter = painter();
+        let mut paint = Paint::default();
+        paint.set_color(Color::RED);
+        paint.set_stroke_width(2.0);
+        let callback = EguiSkiaPaintCallback::dashed_rect(&[4.0, 4.0], 0.0, paint).unwrap();
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(40.0, 20.0));
+        let mut surface = surface(40, 20);
+        painter.paint_primitives(surface.canvas(), 1.0, &[callback_primitive(rect, callback)]);
+
+        // The top border starts at the inset corner (1, 1): on from 1 to 5, off to 9, on to 13.
+        assert_color_near(pixel(&mut surface, 3, 1), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 7, 1), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 11, 1), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 15, 1), Color::TRANSPARENT, 0);
+        // The inside isn't filled.
+        assert_color_near(pixel(&mut surface, 20, 10), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn dashed_rect_rejects_odd_intervals() {
+        let cases: [&[f32]; 3] = [&[], &[4.0], &[4.0, 2.0, 1.0]];
+        for intervals in cases {
+            assert!(EguiSkiaPaintCallback::dashed_rect(intervals, 0.0, Paint::default()).is_none());
+        }
+    }
+
+    #[test]
+    fn path_draws_an_anti_aliased_cubic_bezier() {
+        let painter = painter();
+        let mut path = Path::new();
+        path.move_to((2.0, 30.0));
+        path.cubic_to((10.0, 0.0), (30.0, 0.0), (38.0, 30.0));
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_style(skia_safe::PaintStyle::Stroke);
+        paint.set_stroke_width(2.0);
+        let callback = EguiSkiaPaintCallback::path(path, paint);
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(40.0, 40.0));
+        let mut surface = surface(40, 40);
+        painter.paint_primitives(surface.canvas(), 1.0, &[callback_primitive(rect, callback)]);
+
+        let alphas = (0..40)
+            .flat_map(|y| (0..40).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(&mut surface, x, y).a())
+            .collect::<Vec<_>>();
+        // The curve is covered, with partially covered pixels along its edges.
+        assert!(alphas.iter().any(|&a| a == 255));
+        assert!(alphas.iter().filter(|&&a| a > 0 && a < 255).count() > 20);
+        // The top of the curve is at y = 7.5, the area below the chord stays empty.
+        assert!(pixel(&mut surface, 20, 8).a() > 0);
+        assert_color_near(pixel(&mut surface, 20, 35), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn uniform_strokes_stay_uniform_under_a_non_uniform_scale() {
+        // A cross of a vertical and a horizontal line, on a canvas scaled twice as much
+        // horizontally.
+        let mut cross = Path::new();
+        cross.move_to((20.0, 5.0));
+        cross.line_to((20.0, 35.0));
+        cross.move_to((5.0, 20.0));
+        cross.line_to((35.0, 20.0));
+        let mut paint = Paint::default();
+        paint.set_color(Color::RED);
+        paint.set_style(skia_safe::PaintStyle::Stroke);
+        paint.set_stroke_width(2.0);
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(40.0, 40.0));
+        let widths = |callback: EguiSkiaPaintCallback| {
+            let painter = painter();
+            let mut surface = surface(80, 40);
+            surface.canvas().scale((2.0, 1.0));
+            painter.paint_primitives(surface.canvas(), 1.0, &[callback_primitive(rect, callback)]);
+            let mut covered = |(x, y): (i32, i32)| pixel(&mut surface, x, y).a() > 127;
+            // Across the vertical line in a row, and across the horizontal line in a column.
+            let vertical = (0..80).filter(|&x| covered((x, 10))).count();
+            let horizontal = (0..40).filter(|&y| covered((60, y))).count();
+            (vertical, horizontal)
+        };
+
+        let plain = EguiSkiaPaintCallback::path(cross.clone(), paint.clone());
+        assert_eq!(widths(plain), (4, 2));
+        // Both lines are as wide as the stroke scaled by the average scale, sqrt(2).
+        let uniform = EguiSkiaPaintCallback::uniform_stroke_path(cross, paint);
+        let (vertical, horizontal) = widths(uniform);
+        let uniform = vertical.abs_diff(horizontal) <= 1;
+        assert!(uniform, "{} {}", vertical, horizontal);
+        assert!((2..=3).contains(&vertical) && (2..=3).contains(&horizontal));
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn gpu_cached_renders_once_and_draws_the_cached_image() {
+        use std::sync::atomic::AtomicUsize;
+
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 20, 20, 0);
+        let cache = GpuCache::new(context.clone(), 2.0);
+        let renders = Arc::new(AtomicUsize::new(0));
+        let painter = painter();
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(5.0, 5.0));
+        let paint = |target: &mut skia_safe::Surface| {
+            let counter = renders.clone();
+            let render = move |canvas: &mut Canvas| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                canvas.draw_color(Color::RED, None);
+            };
+            let callback = EguiSkiaPaintCallback::gpu_cached(render, &cache);
+            target.canvas().clear(Color::TRANSPARENT);
+            painter.paint_primitives(target.canvas(), 2.0, &[callback_primitive(rect, callback)]);
+        };
+
+        for _ in 0..3 {
+            paint(&mut target);
+        }
+        assert_eq!(renders.load(Ordering::SeqCst), 1);
+        let mut readback = read_back(&mut target);
+        assert_color_near(pixel(&mut readback, 5, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut readback, 15, 15), Color::TRANSPARENT, 0);
+
+        cache.invalidate();
+        paint(&mut target);
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+
+        // Painted on another thread, the callback has no image and draws nothing.
+        let cache_elsewhere = cache.clone();
+        std::thread::spawn(move || {
+            let bounds = Rect::from_wh(5.0, 5.0);
+            assert!(cache_elsewhere.image(bounds, &|_| {}).is_none());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn pictures_are_fitted_into_differently_sized_rects() {
+        // A 20x10 picture, red on the left and blue on the right half, saved and loaded again.
+        let mut recorder = PictureRecorder::new();
+        let canvas = recorder.begin_recording(Rect::from_wh(20.0, 10.0), None);
+        let mut paint = Paint::default();
+        paint.set_color(Color::RED);
+        canvas.draw_rect(Rect::from_xywh(0.0, 0.0, 10.0, 10.0), &paint);
+        paint.set_color(Color::BLUE);
+        canvas.draw_rect(Rect::from_xywh(10.0, 0.0, 10.0, 10.0), &paint);
+        let recorded = recorder.finish_recording_as_picture(None).unwrap();
+        let picture = Picture::from_data(&recorded.serialize()).unwrap();
+
+        let widget = |min: (f32, f32), max: (f32, f32), fit: PictureFit| {
+            let rect = egui::Rect::from_min_max(pos2(min.0, min.1), pos2(max.0, max.1));
+            let callback = EguiSkiaPaintCallback::from_picture(picture.clone(), fit);
+            callback_primitive(rect, callback)
+        };
+        let primitives = [
+            widget((0.0, 0.0), (40.0, 40.0), PictureFit::Contain),
+            widget((50.0, 0.0), (70.0, 40.0), PictureFit::Cover),
+            widget((80.0, 0.0), (100.0, 40.0), PictureFit::None),
+            widget((100.0, 0.0), (130.0, 40.0), PictureFit::Stretch),
+        ];
+        let mut surface = surface(130, 40);
+        painter().paint_primitives(surface.canvas(), 1.0, &primitives);
+        let mut probe = |x, y, color| assert_color_near(pixel(&mut surface, x, y), color, 0);
+
+        // Contain: scaled twice to 40x20 and centered vertically.
+        probe(10, 5, Color::TRANSPARENT);
+        probe(10, 20, Color::RED);
+        probe(30, 20, Color::BLUE);
+        probe(30, 35, Color::TRANSPARENT);
+        // Cover: scaled four times to 80x40, centered and cropped to the rect.
+        probe(52, 5, Color::RED);
+        probe(68, 35, Color::BLUE);
+        probe(75, 20, Color::TRANSPARENT);
+        // None: unscaled and centered.
+        probe(85, 10, Color::TRANSPARENT);
+        probe(85, 20, Color::RED);
+        probe(95, 20, Color::BLUE);
+        // Stretch: the halves fill the rect.
+        probe(102, 2, Color::RED);
+        probe(128, 38, Color::BLUE);
+    }
+}
@@ -0,0 +1,88 @@
+use egui::TextureFilter;
+use skia_safe::{AlphaType, BlendMode, ColorSpace, ColorType, TileMode};
+
+use crate::painter::QualityTier;
+
+/// A summary of how a [`Painter`](crate::Painter) was built and configured, see
+/// [`Painter::capabilities`](crate::Painter::capabilities). Meant to be attached to bug reports.
+#[derive(Clone, Debug)]
+pub struct PainterCaps {
+    /// Whether the crate was built with the `gpu` feature.
+    pub gpu_feature: bool,
+    /// Whether the painter has a `DirectContext` and uploads its textures to the GPU.
+    pub gpu: bool,
+    /// Whether textures are sampled with mipmaps, `false` when built with the `cpu_fix`
+    /// feature.
+    pub mipmaps: bool,
+    /// Whether textures are sampled through the runtime effect instead of plain image shaders,
+    /// see [`TextureStore::with_raw_image_shaders`](crate::TextureStore::with_raw_image_shaders).
+    pub runtime_effect: bool,
+    /// The tile mode of the texture shaders.
+    pub tile_mode: TileMode,
+    /// The blend mode combining vertex colors with the textures.
+    pub vertex_blend_mode: BlendMode,
+    /// The output the painter is configured for, see
+    /// [`Painter::with_output_options`](crate::Painter::with_output_options).
+    pub color_type: ColorType,
+    pub alpha_type: AlphaType,
+    pub color_space: Option<ColorSpace>,
+    /// The filter forced onto every texture, see
+    /// [`Painter::with_forced_filter`](crate::Painter::with_forced_filter).
+    pub forced_filter: Option<TextureFilter>,
+    /// See [`Painter::with_quality`](crate::Painter::with_quality).
+    pub quality: QualityTier,
+    /// See [`Painter::with_msaa_coverage`](crate::Painter::with_msaa_coverage).
+    pub msaa_coverage: bool,
+    pub max_draw_calls: Option<usize>,
+    pub geometry_snap: bool,
+    pub viewport_culling: bool,
+    pub dithering: bool,
+    pub debug_annotations: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use super::*;
+    use crate::{OutputOptions, Painter, TextureStore};
+
+    #[test]
+    fn caps_reflect_the_configuration_of_the_painter() {
+        let caps = Painter::new().capabilities();
+        assert!(!caps.gpu);
+        assert!(caps.runtime_effect);
+        assert_eq!(caps.color_type, ColorType::N32);
+        assert_eq!(caps.quality, QualityTier::Medium);
+        assert_eq!(caps.forced_filter, None);
+        assert!(!caps.geometry_snap && !caps.dithering && !caps.debug_annotations);
+
+        let store = TextureStore::new().with_raw_image_shaders(true);
+        let output = OutputOptions {
+            color_type: ColorType::RGBAF16,
+            alpha_type: AlphaType::Opaque,
+            color_space: Some(ColorSpace::new_srgb_linear()),
+            ..Default::default()
+        };
+        let painter = Painter::with_texture_store(Arc::new(RwLock::new(store)))
+            .with_output_options(output)
+            .with_forced_filter(Some(TextureFilter::Linear))
+            .with_quality(QualityTier::High)
+            .with_msaa_coverage(true)
+            .with_max_draw_calls(Some(8))
+            .with_geometry_snap(true)
+            .with_viewport_culling(true)
+            .with_dithering(true)
+            .with_debug_annotations(true);
+        let caps = painter.capabilities();
+        assert!(!caps.runtime_effect);
+        assert_eq!(caps.color_type, ColorType::RGBAF16);
+        assert_eq!(caps.alpha_type, AlphaType::Opaque);
+        assert_eq!(caps.color_space, Some(ColorSpace::new_srgb_linear()));
+        assert_eq!(caps.forced_filter, Some(TextureFilter::Linear));
+        assert_eq!(caps.quality, QualityTier::High);
+        assert_eq!(caps.max_draw_calls, Some(8));
+        assert!(caps.msaa_coverage && caps.geometry_snap && caps.viewport_culling);
+        assert!(caps.dithering && caps.debug_annotations);
+    }
+}
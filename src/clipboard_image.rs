@@ -0,0 +1,102 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use egui::{ColorImage, Context};
+
+/// Converts an image read from the clipboard with arboard into an egui image.
+///
+/// arboard hands out unmultiplied RGBA rows without padding, the alpha channel is kept.
+pub fn clipboard_color_image(image: &arboard::ImageData) -> ColorImage {
+    ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.bytes)
+}
+
+/// Reads images from the clipboard on paste, see `EguiSkiaWinit::with_image_paste`.
+pub(crate) struct ImagePaste {
+    on_paste: Box<dyn FnMut(ColorImage)>,
+    sender: Sender<ColorImage>,
+    receiver: Receiver<ColorImage>,
+}
+
+impl ImagePaste {
+    pub(crate) fn new(on_paste: impl FnMut(ColorImage) + 'static) -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            on_paste: Box::new(on_paste),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Reads and converts the clipboard image on another thread, screenshots can take a while
+    /// to convert. Requests a repaint of `ctx` once the image is ready.
+    pub(crate) fn request(&self, ctx: &Context) {
+        self.request_with(ctx, || {
+            let image = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_image());
+            image.ok()
+        });
+    }
+
+    /// Like [`Self::request`], with the clipboard image read by `read`.
+    fn request_with(
+        &self,
+        ctx: &Context,
+        read: impl FnOnce() -> Option<arboard::ImageData<'static>> + Send + 'static,
+    ) {
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            if let Some(image) = read() {
+                if sender.send(clipboard_color_image(&image)).is_ok() {
+                    ctx.request_repaint();
+                }
+            }
+        });
+    }
+
+    /// Hands the images read since the last call to the paste hook.
+    pub(crate) fn deliver(&mut self) {
+        for image in self.receiver.try_iter() {
+            (self.on_paste)(image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use egui::Color32;
+
+    use super::*;
+
+    #[test]
+    fn pasted_images_reach_the_hook_as_unmultiplied_rgba() {
+        let pasted = Rc::new(RefCell::new(Vec::new()));
+        let mut image_paste = ImagePaste::new({
+            let pasted = pasted.clone();
+            move |image| pasted.borrow_mut().push(image)
+        });
+        // A 2x1 screenshot with an opaque red and a half transparent blue pixel.
+        let bytes = vec![255, 0, 0, 255, 0, 0, 255, 128];
+        let clipboard = arboard::ImageData {
+            width: 2,
+            height: 1,
+            bytes: Cow::Owned(bytes),
+        };
+        image_paste.request_with(&Context::default(), move || Some(clipboard));
+
+        let start = Instant::now();
+        while pasted.borrow().is_empty() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(1));
+            image_paste.deliver();
+        }
+        let pasted = pasted.borrow();
+        assert_eq!(pasted.len(), 1);
+        assert_eq!(pasted[0].size, [2, 1]);
+        let half_blue = Color32::from_rgba_unmultiplied(0, 0, 255, 128);
+        assert_eq!(pasted[0].pixels, [Color32::RED, half_blue]);
+        assert_eq!(pasted[0].pixels[1].a(), 128);
+    }
+}
@@ -0,0 +1,120 @@
+//! The color conversions used by the [`crate::Painter`].
+//!
+//! egui colors ([`Color32`]) are premultiplied sRGB. skia expects vertex colors to be
+//! unpremultiplied and texture pixels to be premultiplied, which is what these functions produce.
+
+use egui::Color32;
+use skia_safe::Color;
+
+/// The coverage gamma the painter applies to the font atlas, see [`font_coverage_bytes_premul`].
+pub const FONT_GAMMA: f32 = 1.0;
+
+/// Converts a premultiplied sRGB [`Color32`] to an unpremultiplied skia [`Color`], as used for
+/// vertex colors.
+///
+/// Fully transparent colors become [`Color::TRANSPARENT`] and opaque colors are passed through
+/// unchanged. The other colors are divided by their alpha in integer math, rounded, and clamped
+/// to 255, so invalid premultiplied colors (a channel above the alpha) saturate instead of
+/// wrapping around.
+pub fn color32_to_skia_unmultiplied(c: Color32) -> Color {
+    match c.a() {
+        0 => Color::TRANSPARENT,
+        255 => Color::from_argb(255, c.r(), c.g(), c.b()),
+        a => {
+            let unmultiply = |channel: u8| {
+                let a = a as u32;
+                ((channel as u32 * 255 + a / 2) / a).min(255) as u8
+            };
+            Color::from_argb(a, unmultiply(c.r()), unmultiply(c.g()), unmultiply(c.b()))
+        }
+    }
+}
+
+/// Returns the premultiplied sRGB bytes of `c` in RGBA order, as used for texture pixels.
+pub fn color32_bytes_premul(c: Color32) -> [u8; 4] {
+    c.to_array()
+}
+
+/// Converts a font atlas coverage value (`0.0..=1.0`) to premultiplied white in RGBA order,
+/// applying `gamma` to the coverage like [`egui::FontImage::srgba_pixels`] does.
+pub fn font_coverage_bytes_premul(coverage: f32, gamma: f32) -> [u8; 4] {
+    let alpha = coverage.powf(gamma);
+    let a = (alpha * 255.0 + 0.5).floor() as u8;
+    [a, a, a, a]
+}
+
+/// Converts a font atlas coverage value to the color the painter uploads for it, see
+/// [`font_coverage_bytes_premul`].
+pub fn font_coverage_to_color32(coverage: f32, gamma: f32) -> Color32 {
+    let [r, g, b, a] = font_coverage_bytes_premul(coverage, gamma);
+    Color32::from_rgba_premultiplied(r, g, b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Premultiplies `channel` by `alpha` like skia does for unpremultiplied colors.
+    fn premultiply(channel: u8, alpha: u8) -> u8 {
+        ((channel as u32 * alpha as u32 + 127) / 255) as u8
+    }
+
+    #[test]
+    fn unmultiplied_colors_premultiply_back_to_the_same_color() {
+        for a in 1..=255 {
+            for c in 0..=a {
+                let premultiplied = Color32::from_rgba_premultiplied(c, c, c, a);
+                let color = color32_to_skia_unmultiplied(premultiplied);
+                assert_eq!(color.a(), a);
+                assert_eq!(premultiply(color.r(), a), c, "channel {} alpha {}", c, a);
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_colors_are_transparent_whatever_their_channels() {
+        for c in 0..=255 {
+            let color = Color32::from_rgba_premultiplied(c, 255 - c, c / 2, 0);
+            assert_eq!(color32_to_skia_unmultiplied(color), Color::TRANSPARENT);
+        }
+    }
+
+    #[test]
+    fn channels_above_the_alpha_saturate() {
+        for a in 1..=255 {
+            for c in a..=255 {
+                let invalid = Color32::from_rgba_premultiplied(c, 0, 0, a);
+                let color = color32_to_skia_unmultiplied(invalid);
+                assert_eq!(color.r(), 255, "channel {} alpha {}", c, a);
+            }
+        }
+    }
+
+    #[test]
+    fn opaque_colors_are_exact() {
+        for c in 0..=255 {
+            let color = Color32::from_rgb(c, 255 - c, c / 3);
+            assert_eq!(color32_to_skia_unmultiplied(color), Color::from_rgb(c, 255 - c, c / 3));
+            assert_eq!(color32_bytes_premul(color), [c, 255 - c, c / 3, 255]);
+        }
+    }
+
+    #[test]
+    fn font_coverage_is_premultiplied_white() {
+        assert_eq!(font_coverage_bytes_premul(0.0, FONT_GAMMA), [0; 4]);
+        assert_eq!(font_coverage_bytes_premul(1.0, FONT_GAMMA), [255; 4]);
+        let mut last = 0;
+        for step in 0..=100 {
+            let coverage = step as f32 / 100.0;
+            for gamma in [0.5, FONT_GAMMA, 2.0] {
+                let [r, g, b, a] = font_coverage_bytes_premul(coverage, gamma);
+                assert!(r == a && g == a && b == a);
+            }
+            let [.., a] = font_coverage_bytes_premul(coverage, FONT_GAMMA);
+            assert!(a >= last);
+            last = a;
+        }
+        let color = font_coverage_to_color32(0.5, 2.0);
+        assert_eq!(color.to_array(), font_coverage_bytes_premul(0.5, 2.0));
+    }
+}
@@ -0,0 +1,129 @@
+use egui::{ClippedPrimitive, TexturesDelta};
+use skia_safe::{Canvas, Matrix};
+
+use crate::Painter;
+
+/// The output of one egui context to composite, see [`Compositor::composite`].
+pub struct CompositeFrame {
+    pub dpi: f32,
+    pub primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+    /// Concatenated with the canvas matrix while painting the frame, e.g. to place a sub ui.
+    pub transform: Matrix,
+}
+
+/// Paints the output of several egui contexts (e.g. the main ui and a debug overlay) onto one
+/// canvas in a fixed order.
+///
+/// Every context gets its own [`Painter`] and with it its own textures, so the texture ids of
+/// the contexts can't collide.
+pub struct Compositor {
+    painters: Vec<Painter>,
+}
+
+impl Compositor {
+    /// Creates a compositor for `layers` contexts.
+    pub fn new(layers: usize) -> Self {
+        Self::with_painters((0..layers).map(|_| Painter::new()).collect())
+    }
+
+    /// Creates a compositor painting layer `i` with `painters[i]`, e.g. to configure them.
+    pub fn with_painters(painters: Vec<Painter>) -> Self {
+        Self { painters }
+    }
+
+    /// The painter of layer `layer`.
+    pub fn painter(&mut self, layer: usize) -> &mut Painter {
+        &mut self.painters[layer]
+    }
+
+    /// Paints `frames` onto `canvas`, `frames[i]` with the painter of layer `i`. Later frames
+    /// are drawn on top of earlier ones.
+    ///
+    /// The canvas is cleared once, to the clear color of the painter of the first layer (see
+    /// [`OutputOptions::clear_color`](crate::OutputOptions::clear_color)). The clear colors of
+    /// the other layers are ignored, they would wipe the layers below.
+    ///
+    /// Panics if there are more frames than layers. Layers without a frame keep their textures
+    /// and paint nothing.
+    pub fn composite(&mut self, canvas: &mut Canvas, frames: Vec<CompositeFrame>) {
+        assert!(
+            frames.len() <= self.painters.len(),
+            "{} frames for {} layers",
+            frames.len(),
+            self.painters.len()
+        );
+        let clear_color = self
+            .painters
+            .first()
+            .and_then(|painter| painter.output_options().clear_color);
+        if let Some(clear_color) = clear_color {
+            canvas.clear(clear_color);
+        }
+        for (painter, frame) in self.painters.iter_mut().zip(frames) {
+            let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+            arc.concat(&frame.transform);
+            painter.paint_layer(&mut *arc, frame.dpi, frame.primitives, frame.textures_delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Context, Pos2};
+    use skia_safe::Color;
+
+    use super::*;
+    use crate::test_util::{assert_color_near, pixel, surface};
+    use crate::OutputOptions;
+
+    /// Runs `ui` in `ctx` on a screen of 40x40 points and returns the frame to composite.
+    fn frame(ctx: &Context, ui: impl FnOnce(&Context)) -> CompositeFrame {
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Pos2::ZERO,
+                egui::vec2(40.0, 40.0),
+            )),
+            pixels_per_point: Some(1.0),
+            ..Default::default()
+        };
+        let output = ctx.run(input, ui);
+        CompositeFrame {
+            dpi: 1.0,
+            primitives: ctx.tessellate(output.shapes),
+            textures_delta: output.textures_delta,
+            transform: Matrix::default(),
+        }
+    }
+
+    #[test]
+    fn later_contexts_draw_on_top_of_earlier_ones() {
+        // Both painters clear, as the presets for transparent windows do.
+        let output = OutputOptions {
+            clear_color: Some(Color::TRANSPARENT),
+            ..Default::default()
+        };
+        let painters = (0..2)
+            .map(|_| Painter::new().with_output_options(output))
+            .collect();
+        let mut compositor = Compositor::with_painters(painters);
+
+        let (main, overlay) = (Context::default(), Context::default());
+        let fill = |ctx: &Context, rect: egui::Rect, color: Color32| {
+            ctx.layer_painter(egui::LayerId::background())
+                .rect_filled(rect, 0.0, color);
+        };
+        let screen = egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(40.0, 40.0));
+        let corner = egui::Rect::from_min_size(Pos2::new(10.0, 10.0), egui::vec2(20.0, 20.0));
+        let frames = vec![
+            frame(&main, |ctx| fill(ctx, screen, Color32::RED)),
+            frame(&overlay, |ctx| fill(ctx, corner, Color32::BLUE)),
+        ];
+
+        let mut surface = surface(40, 40);
+        surface.canvas().clear(Color::WHITE);
+        compositor.composite(surface.canvas(), frames);
+        assert_color_near(pixel(&mut surface, 20, 20), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+    }
+}
@@ -0,0 +1,78 @@
+use egui::{ClippedPrimitive, TexturesDelta};
+use skia_safe::{Canvas, Surface};
+
+use crate::Painter;
+
+/// Something an [`EguiPainter`] can paint onto.
+pub trait PaintTarget {
+    /// The canvas to paint onto, `None` for targets that don't draw anything.
+    fn canvas(&mut self) -> Option<&mut Canvas>;
+}
+
+impl PaintTarget for Canvas {
+    fn canvas(&mut self) -> Option<&mut Canvas> {
+        Some(self)
+    }
+}
+
+impl PaintTarget for Surface {
+    fn canvas(&mut self) -> Option<&mut Canvas> {
+        Some(Surface::canvas(self))
+    }
+}
+
+/// A target that discards everything, for painters that don't draw like
+/// [`RecordingPainter`](crate::testing::RecordingPainter).
+impl PaintTarget for () {
+    fn canvas(&mut self) -> Option<&mut Canvas> {
+        None
+    }
+}
+
+/// The operations of a frame as the [`Painter`] performs them, so code driving a painter can be
+/// tested with [`RecordingPainter`](crate::testing::RecordingPainter) instead.
+///
+/// The methods are named apart from the inherent methods of [`Painter`], so having the trait in
+/// scope doesn't change which method a call on a `Painter` resolves to.
+pub trait EguiPainter {
+    /// Applies the `set` part of `textures_delta`, before painting the frame. Like
+    /// [`Painter::update_textures`].
+    fn begin_frame(&mut self, textures_delta: &TexturesDelta);
+
+    /// Draws `primitives` onto `target`.
+    fn paint(&mut self, target: &mut dyn PaintTarget, dpi: f32, primitives: &[ClippedPrimitive]);
+
+    /// Applies the `free` part of `textures_delta` and finishes the frame, after painting it.
+    /// Like [`Painter::free_textures`].
+    fn end_frame(&mut self, textures_delta: &TexturesDelta);
+
+    /// Updates the textures, paints `primitives` and frees the textures that are no longer
+    /// needed, in that order. Like [`Painter::paint_and_update_textures`].
+    fn paint_frame_with_textures(
+        &mut self,
+        target: &mut dyn PaintTarget,
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+        textures_delta: &TexturesDelta,
+    ) {
+        self.begin_frame(textures_delta);
+        self.paint(target, dpi, primitives);
+        self.end_frame(textures_delta);
+    }
+}
+
+impl EguiPainter for Painter {
+    fn begin_frame(&mut self, textures_delta: &TexturesDelta) {
+        self.update_textures(textures_delta);
+    }
+
+    fn end_frame(&mut self, textures_delta: &TexturesDelta) {
+        self.free_textures(textures_delta);
+    }
+
+    fn paint(&mut self, target: &mut dyn PaintTarget, dpi: f32, primitives: &[ClippedPrimitive]) {
+        if let Some(canvas) = target.canvas() {
+            self.paint_primitives(canvas, dpi, primitives);
+        }
+    }
+}
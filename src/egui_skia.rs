@@ -1,16 +1,63 @@
+use crate::history::{dump_frames, FrameHistory, HistoricalFrame};
+use crate::layers::{add_layer_markers, is_layer_marker, resolve_layer_markers, LayerDraw};
 use crate::painter::Painter;
-use egui::{Context, Pos2};
-use skia_safe::{Canvas, Surface};
+use crate::platform::OutputOptions;
+use crate::screenshot::{draw_cursor, ScreenshotOptions};
+use egui::{Color32, Context, Pos2, Vec2};
+use skia_safe::{
+    BlendMode, Canvas, Color, IRect, Image, Paint, PixelGeometry, Rect, Surface, SurfaceProps,
+    SurfacePropsFlags,
+};
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// How often [`EguiSkia::paint`] asks for a repaint while textures are converted in the
+/// background, see [`Painter::with_async_atlas_upload`].
+const PENDING_UPLOAD_POLL: Duration = Duration::from_millis(16);
+
 pub struct RasterizeOptions {
     pub pixels_per_point: f32,
+    /// Surface properties of the created surface. The default uses an unknown pixel geometry,
+    /// which makes skia use grayscale anti aliasing for text.
+    pub surface_props: SurfaceProps,
+    /// Only keep this region of the rendered frame, in points. It is clamped to the frame, crops
+    /// that don't overlap it are ignored. [`draw_onto_surface`] ignores the crop.
+    pub crop: Option<egui::Rect>,
+    /// Runs on the context before the first frame, to put egui into a state a single run of the
+    /// ui can't reach. Common cases, with `id` the id of the widget:
+    ///
+    /// - an open combo box or popup: `ctx.memory().open_popup(id.with("popup"))`, with the
+    ///   id of the combo box button
+    /// - a focused text field: `ctx.memory().request_focus(id)`
+    /// - restoring a whole session: `*ctx.memory() = memory`, e.g. deserialized from ron with
+    ///   egui's `persistence` feature
+    ///
+    /// Most of these need a frame to take effect, see `warmup_frames`.
+    pub setup: Option<Box<dyn FnOnce(&Context)>>,
+    /// Number of frames run before the rendered one, so state set in `setup` or by the ui
+    /// itself (layouts that settle, hover states) takes effect. Ignored by
+    /// [`rasterize_scrollable`].
+    pub warmup_frames: usize,
+    /// The pointer position in every frame, in points, e.g. to show the hover state of a button
+    /// or a tooltip (together with `warmup_frames`). Ignored by [`rasterize_scrollable`].
+    pub hover_pos: Option<Pos2>,
+    /// The format of the created surfaces and the output options of the painter, e.g.
+    /// [`OutputOptions::hdr`] to render in float precision. [`draw_onto_surface`] only uses the
+    /// painter options.
+    pub output: OutputOptions,
 }
 
 impl Default for RasterizeOptions {
     fn default() -> Self {
         Self {
             pixels_per_point: 1.0,
+            surface_props: SurfaceProps::new(SurfacePropsFlags::empty(), PixelGeometry::Unknown),
+            crop: None,
+            setup: None,
+            warmup_frames: 0,
+            hover_pos: None,
+            output: OutputOptions::default(),
         }
     }
 }
@@ -20,9 +67,74 @@ pub fn rasterize(
     ui: impl FnMut(&Context),
     options: Option<RasterizeOptions>,
 ) -> Surface {
-    let mut surface = Surface::new_raster_n32_premul(size).expect("Failed to create surface");
-    draw_onto_surface(&mut surface, ui, options);
-    surface
+    let options = options.unwrap_or_default();
+    let (pixels_per_point, crop) = (options.pixels_per_point, options.crop);
+    let mut surface = Surface::new_raster(
+        &options.output.image_info(size),
+        None,
+        Some(&options.surface_props),
+    )
+    .expect("Failed to create surface");
+    draw_onto_surface(&mut surface, ui, Some(options));
+
+    let (width, height) = (surface.width(), surface.height());
+    let crop = crop.and_then(|crop| crop_bounds(crop, pixels_per_point, width, height));
+    match crop {
+        Some(bounds) => {
+            let image = surface.image_snapshot_with_bounds(bounds).unwrap();
+            let mut cropped = surface
+                .new_surface_with_dimensions(bounds.size())
+                .expect("Failed to create surface");
+            cropped.canvas().draw_image(image, (0, 0), None);
+            cropped
+        }
+        None => surface,
+    }
+}
+
+/// Renders `ui` onto a surface of `size` pixels and returns the region `locate` finds in the
+/// finished frame, padded by `margin` points on every side, e.g. to capture a single widget for
+/// documentation.
+///
+/// The locator typically returns the [`egui::Response::rect`] the ui closure stashed, e.g. in
+/// egui's memory. Returns `None` if the locator finds nothing or the region is outside of the
+/// surface. The crop of `options` is ignored.
+pub fn rasterize_widget(
+    size: (i32, i32),
+    ui: impl FnMut(&Context),
+    locate: impl Fn(&Context) -> Option<egui::Rect>,
+    margin: f32,
+    options: Option<RasterizeOptions>,
+) -> Option<Image> {
+    let options = options.unwrap_or_default();
+    let pixels_per_point = options.pixels_per_point;
+    let mut surface = Surface::new_raster(
+        &options.output.image_info(size),
+        None,
+        Some(&options.surface_props),
+    )
+    .expect("Failed to create surface");
+    let backend = run_onto_surface(&mut surface, ui, options);
+
+    let rect = locate(&backend.egui_ctx)?.expand(margin);
+    let bounds = crop_bounds(rect, pixels_per_point, surface.width(), surface.height())?;
+    surface.image_snapshot_with_bounds(bounds)
+}
+
+/// The pixel bounds of `crop` (in points) clamped to a surface of `width` x `height` pixels, or
+/// `None` if nothing is left.
+fn crop_bounds(crop: egui::Rect, pixels_per_point: f32, width: i32, height: i32) -> Option<IRect> {
+    let crop = Rect::new(
+        crop.min.x * pixels_per_point,
+        crop.min.y * pixels_per_point,
+        crop.max.x * pixels_per_point,
+        crop.max.y * pixels_per_point,
+    );
+    let mut bounds = Rect::from_iwh(width, height);
+    if !bounds.intersect(crop) {
+        return None;
+    }
+    Some(bounds.round_out())
 }
 
 pub fn draw_onto_surface(
@@ -30,10 +142,31 @@ pub fn draw_onto_surface(
     ui: impl FnMut(&Context),
     options: Option<RasterizeOptions>,
 ) {
-    let RasterizeOptions { pixels_per_point } = options.unwrap_or_default();
+    run_onto_surface(surface, ui, options.unwrap_or_default());
+}
+
+/// Runs the frames of `ui` filling `surface` and paints the last one, returning the backend for
+/// inspecting the frame.
+fn run_onto_surface(
+    surface: &mut Surface,
+    mut ui: impl FnMut(&Context),
+    options: RasterizeOptions,
+) -> EguiSkia {
+    let RasterizeOptions {
+        pixels_per_point,
+        setup,
+        warmup_frames,
+        hover_pos,
+        output,
+        ..
+    } = options;
     let mut backend = EguiSkia::new();
+    backend.painter = Painter::new().with_output_options(output);
+    if let Some(setup) = setup {
+        setup(&backend.egui_ctx);
+    }
 
-    let input = egui::RawInput {
+    let input = || egui::RawInput {
         screen_rect: Some(
             [
                 Pos2::default(),
@@ -42,12 +175,181 @@ pub fn draw_onto_surface(
             .into(),
         ),
         pixels_per_point: Some(pixels_per_point),
+        events: hover_pos.map(egui::Event::PointerMoved).into_iter().collect(),
+        ..Default::default()
+    };
+
+    for _ in 0..warmup_frames {
+        backend.run(input(), &mut ui);
+    }
+    backend.run(input(), ui);
+
+    backend.paint(surface.canvas());
+    backend
+}
+
+/// Lays out `ui` in a screen of `logical_size` points and renders it at `scale` pixels per
+/// point, so the image looks the same whatever the dpi of the host is.
+///
+/// The image is `logical_size * scale` pixels big, rounded to whole pixels.
+pub fn render_logical(logical_size: Vec2, scale: f32, ui: impl FnMut(&Context)) -> Image {
+    let size = (logical_size * scale).round();
+    let mut surface = Surface::new_raster_n32_premul((size.x as i32, size.y as i32))
+        .expect("Failed to create surface");
+    let mut backend = EguiSkia::new();
+
+    let input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(Pos2::ZERO, logical_size)),
+        pixels_per_point: Some(scale),
         ..Default::default()
     };
 
     backend.run(input, ui);
 
     backend.paint(surface.canvas());
+    surface.image_snapshot()
+}
+
+/// Renders `content_ui`, which may be much taller than `viewport_size` points, into one image of
+/// its whole height, e.g. to export a full page.
+///
+/// The content is laid out in a vertical scroll area filling a screen of `viewport_size`. A first
+/// frame measures its height, then it is rendered in slices of the viewport height, scrolled to
+/// whole pixel offsets so neighbouring slices line up without duplicated or missing rows, and
+/// stitched together. The last slice is aligned to the bottom of the content and overlaps the one
+/// before it.
+///
+/// Content that doesn't scroll, like sticky headers, is drawn again in every slice.
+pub fn rasterize_scrollable(
+    viewport_size: Vec2,
+    mut content_ui: impl FnMut(&mut egui::Ui),
+    options: Option<RasterizeOptions>,
+) -> Image {
+    let RasterizeOptions {
+        pixels_per_point,
+        surface_props,
+        crop,
+        setup,
+        output,
+        ..
+    } = options.unwrap_or_default();
+    let mut backend = EguiSkia::new();
+    backend.painter = Painter::new().with_output_options(output.clone());
+    if let Some(setup) = setup {
+        setup(&backend.egui_ctx);
+    }
+
+    let viewport = (viewport_size * pixels_per_point).round();
+    let (width, slice_height) = (viewport.x as i32, viewport.y as i32);
+    let content_height = run_scrolled(
+        &mut backend,
+        viewport_size,
+        pixels_per_point,
+        0.0,
+        &mut content_ui,
+    );
+    let height = ((content_height * pixels_per_point).ceil() as i32).max(slice_height);
+
+    let mut page = Surface::new_raster(
+        &output.image_info((width, height)),
+        None,
+        Some(&surface_props),
+    )
+    .expect("Failed to create surface");
+    let mut slice = Surface::new_raster(
+        &output.image_info((width, slice_height)),
+        None,
+        Some(&surface_props),
+    )
+    .expect("Failed to create surface");
+
+    // Overlapping rows are replaced rather than blended, so translucent content isn't darkened.
+    let mut paint = Paint::default();
+    paint.set_blend_mode(BlendMode::Src);
+
+    let mut next_top = 0;
+    loop {
+        let top = next_top.min(height - slice_height);
+        run_scrolled(
+            &mut backend,
+            viewport_size,
+            pixels_per_point,
+            top as f32 / pixels_per_point,
+            &mut content_ui,
+        );
+        slice.canvas().clear(Color::TRANSPARENT);
+        backend.paint(slice.canvas());
+        page.canvas().draw_image(slice.image_snapshot(), (0, top), Some(&paint));
+        if top + slice_height >= height {
+            break;
+        }
+        next_top += slice_height;
+    }
+
+    let crop = crop.and_then(|crop| crop_bounds(crop, pixels_per_point, width, height));
+    match crop {
+        Some(bounds) => page.image_snapshot_with_bounds(bounds).unwrap(),
+        None => page.image_snapshot(),
+    }
+}
+
+/// Runs a frame of `content_ui` in a vertical scroll area scrolled to `offset` points and returns
+/// the height of the content in points.
+fn run_scrolled(
+    backend: &mut EguiSkia,
+    viewport_size: Vec2,
+    pixels_per_point: f32,
+    offset: f32,
+    content_ui: &mut dyn FnMut(&mut egui::Ui),
+) -> f32 {
+    let input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(Pos2::ZERO, viewport_size)),
+        pixels_per_point: Some(pixels_per_point),
+        ..Default::default()
+    };
+
+    let mut content_height = 0.0;
+    backend.run(input, |ctx| {
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none())
+            .show(ctx, |ui| {
+                ui.spacing_mut().scroll_bar_width = 0.0;
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .vertical_scroll_offset(offset)
+                    .show(ui, |ui| {
+                        content_ui(ui);
+                        content_height = ui.min_rect().height();
+                    });
+            });
+    });
+    content_height
+}
+
+/// Paints the output of an egui frame you ran yourself with [`Context::run`].
+///
+/// Tessellates the shapes of `output` with the pixels per point of `ctx`, then updates the
+/// textures and paints the frame. Returns the platform output for the caller to handle.
+///
+/// Every [`egui::FullOutput`] has to be painted, otherwise its texture updates are lost. If you
+/// skip painting a frame, [`egui::TexturesDelta::append`] its delta to the next one.
+pub fn draw_ui(
+    painter: &mut Painter,
+    canvas: &mut Canvas,
+    ctx: &Context,
+    output: egui::FullOutput,
+) -> egui::PlatformOutput {
+    let egui::FullOutput {
+        platform_output,
+        textures_delta,
+        shapes,
+        ..
+    } = output;
+
+    let primitives = ctx.tessellate(shapes);
+    painter.paint_and_update_textures(canvas, ctx.pixels_per_point(), primitives, textures_delta);
+
+    platform_output
 }
 
 /// Convenience wrapper for using [`egui`] from a [`skia`] app.
@@ -57,6 +359,11 @@ pub struct EguiSkia {
 
     shapes: Vec<egui::epaint::ClippedShape>,
     textures_delta: egui::TexturesDelta,
+    between_layers: Vec<(egui::Order, LayerDraw)>,
+    hidden_orders: Vec<egui::Order>,
+    cursor_icon: egui::CursorIcon,
+    content_regions: Vec<egui::Rect>,
+    history: Option<FrameHistory>,
 }
 
 impl EguiSkia {
@@ -67,40 +374,881 @@ impl EguiSkia {
             painter,
             shapes: Default::default(),
             textures_delta: Default::default(),
+            between_layers: Default::default(),
+            hidden_orders: Default::default(),
+            cursor_icon: Default::default(),
+            content_regions: Default::default(),
+            history: None,
+        }
+    }
+
+    /// Registers `draw` to be painted right after all egui layers of order `after`, and below the
+    /// layers of the orders above it.
+    ///
+    /// `draw` gets the canvas scaled to points and clipped to the screen only. Draws registered for
+    /// the same order are painted in registration order.
+    pub fn insert_between_layers(
+        &mut self,
+        after: egui::Order,
+        draw: impl Fn(&mut Canvas) + Send + Sync + 'static,
+    ) {
+        self.between_layers.push((after, Arc::new(draw)));
+    }
+
+    /// Shows or hides the egui layers of `order`, e.g. hide [`egui::Order::Debug`] to strip the
+    /// widget rects egui paints in debug mode from the output.
+    pub fn set_order_visible(&mut self, order: egui::Order, visible: bool) {
+        self.hidden_orders.retain(|hidden| *hidden != order);
+        if !visible {
+            self.hidden_orders.push(order);
         }
     }
 
     /// Returns a duration after witch egui should repaint.
     ///
-    /// Call [`Self::paint`] later to paint.
+    /// Call [`Self::paint`] later to paint. The `max_texture_side` of `input` is limited to
+    /// [`Painter::max_texture_side`].
     pub fn run(
         &mut self,
-        input: egui::RawInput,
-        run_ui: impl FnMut(&Context),
+        mut input: egui::RawInput,
+        mut run_ui: impl FnMut(&Context),
     ) -> (Duration, egui::PlatformOutput) {
+        self.limit_max_texture_side(&mut input);
+        let between_layers = &self.between_layers;
+        // Hiding an order needs to know where every order ends.
+        let all_orders = if self.hidden_orders.is_empty() {
+            &[][..]
+        } else {
+            &egui::Order::ALL[..]
+        };
         let egui::FullOutput {
             platform_output,
             textures_delta,
             shapes,
             repaint_after,
-        } = self.egui_ctx.run(input, run_ui);
+        } = self.egui_ctx.run(input, |ctx| {
+            run_ui(ctx);
+            let orders = between_layers.iter().map(|(order, _)| *order);
+            add_layer_markers(ctx, orders.chain(all_orders.iter().copied()));
+        });
 
+        self.content_regions = content_regions(&shapes);
         self.shapes = shapes;
         self.textures_delta.append(textures_delta);
+        let time = self.egui_ctx.input().time;
+        self.painter.set_animation_time(time);
+
+        self.cursor_icon = platform_output.cursor_icon;
 
         (repaint_after, platform_output)
     }
 
+    /// The regions of the screen, in points, where the last call to [`Self::run`] produced
+    /// visible content, e.g. for a host that handles input itself to forward clicks to egui only
+    /// over its content.
+    ///
+    /// Every region is the union of the clipped bounds of overlapping shapes, so the regions
+    /// don't overlap. Shapes that draw nothing (fully transparent without a stroke) are left out.
+    /// Paint callbacks count with their whole rect.
+    pub fn content_regions(&self) -> &[egui::Rect] {
+        &self.content_regions
+    }
+
+    /// The union of [`Self::content_regions`], `None` if the frame is empty.
+    pub fn content_bounds(&self) -> Option<egui::Rect> {
+        self.content_regions
+            .iter()
+            .copied()
+            .reduce(|bounds, region| bounds.union(region))
+    }
+
+    /// Keeps the last `frames` painted frames, downscaled to a quarter of their size by default,
+    /// for finding glitches that only show for a single frame. `0` turns the history off.
+    ///
+    /// Every [`Self::paint`] reads the frame back from the canvas, which stalls the GPU on GPU
+    /// canvases, so only enable this while debugging. Reducing `frames` drops the oldest frames.
+    pub fn enable_frame_history(&mut self, frames: usize) {
+        if frames == 0 {
+            self.history = None;
+        } else if let Some(history) = &mut self.history {
+            history.set_capacity(frames);
+        } else {
+            self.history = Some(FrameHistory::new(frames, 0.25));
+        }
+    }
+
+    /// Sets the factor frames are downscaled by before they are kept in the history, which
+    /// bounds its memory to `frames * scale² * 4` bytes per screen pixel. Applies to the frames
+    /// painted from now on.
+    pub fn set_frame_history_scale(&mut self, scale: f32) {
+        if let Some(history) = &mut self.history {
+            history.set_scale(scale.clamp(0.0, 1.0));
+        }
+    }
+
+    /// The frames kept by [`Self::enable_frame_history`], oldest first.
+    pub fn frame_history(&self) -> &[HistoricalFrame] {
+        match &self.history {
+            Some(history) => &history.frames,
+            None => &[],
+        }
+    }
+
+    /// Writes the frames of [`Self::frame_history`] into `dir` as numbered PNGs, creating `dir`
+    /// if needed.
+    pub fn dump_history(&self, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        dump_frames(self.frame_history(), dir.as_ref())
+    }
+
+    /// Rasterizes the glyphs of `text_samples` in every style of `styles` into the font atlas and
+    /// uploads the atlas to the painter right away.
+    ///
+    /// Call this during startup (e.g. behind a loading screen) so the first frame showing these
+    /// glyphs doesn't have to grow and re-upload the atlas. This runs an invisible egui frame
+    /// with `input`, which should have the pixels per point and `max_texture_side` of the frames
+    /// passed to [`Self::run`] later, as the atlas is rebuilt when they change.
+    pub fn prewarm_fonts(
+        &mut self,
+        mut input: egui::RawInput,
+        text_samples: &[&str],
+        styles: &[egui::TextStyle],
+    ) {
+        self.limit_max_texture_side(&mut input);
+        let output = self.egui_ctx.run(input, |ctx| {
+            let font_ids = styles
+                .iter()
+                .map(|style| style.resolve(&ctx.style()))
+                .collect::<Vec<_>>();
+            let fonts = ctx.fonts();
+            for font_id in font_ids {
+                for text in text_samples {
+                    fonts.layout_no_wrap(text.to_string(), font_id.clone(), Color32::WHITE);
+                }
+            }
+        });
+
+        let mut textures_delta = std::mem::take(&mut self.textures_delta);
+        textures_delta.append(output.textures_delta);
+        self.painter.update_textures(&textures_delta);
+        // Frees have to wait until the next paint, like for regular frames.
+        self.textures_delta.free = textures_delta.free;
+    }
+
+    /// Limits the `max_texture_side` of `input` to [`Painter::max_texture_side`].
+    fn limit_max_texture_side(&self, input: &mut egui::RawInput) {
+        if let Some(side) = self.painter.max_texture_side() {
+            input.max_texture_side = Some(input.max_texture_side.map_or(side, |max| max.min(side)));
+        }
+    }
+
+    /// Paints the results of the last call to [`Self::run`] into a new image of the screen size,
+    /// e.g. for tutorials or bug reports.
+    ///
+    /// This doesn't replace [`Self::paint`]: the frame can still be painted afterwards. Pending
+    /// texture updates are applied right away. Returns `None` if the screen is empty, e.g. while
+    /// the window is minimized.
+    pub fn screenshot(&mut self, options: &ScreenshotOptions) -> Option<Image> {
+        let dpi = self.egui_ctx.pixels_per_point();
+        let size = (self.egui_ctx.screen_rect().size() * dpi).round();
+        let mut surface = Surface::new_raster_n32_premul((size.x as i32, size.y as i32))?;
+
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        self.painter.update_textures(&textures_delta);
+        // Frees have to wait until the frame is painted.
+        self.textures_delta.free = textures_delta.free;
+
+        let primitives = resolve_layer_markers(
+            self.egui_ctx.tessellate(self.shapes.clone()),
+            &self.between_layers,
+            &self.hidden_orders,
+        );
+        self.painter.paint_primitives(surface.canvas(), dpi, &primitives);
+
+        if options.draw_cursor {
+            let pointer = self.egui_ctx.input().pointer.hover_pos();
+            if let Some(pos) = pointer {
+                draw_cursor(surface.canvas(), pos, self.cursor_icon, dpi);
+            }
+        }
+
+        Some(surface.image_snapshot())
+    }
+
     /// Paint the results of the last call to [`Self::run`].
-    pub fn paint(&mut self, canvas: &mut Canvas) {
+    ///
+    /// Returns the duration after which the paint callbacks or the textures still converted in
+    /// the background want a repaint, or [`Duration::MAX`] if they don't. Repaint after the
+    /// shorter of this and the duration returned by [`Self::run`].
+    pub fn paint(&mut self, canvas: &mut Canvas) -> Duration {
         let shapes = std::mem::take(&mut self.shapes);
         let textures_delta = std::mem::take(&mut self.textures_delta);
-        let clipped_primitives = self.egui_ctx.tessellate(shapes);
+        if self.painter.msaa_coverage() {
+            self.egui_ctx.tessellation_options().feathering = false;
+        }
+        let clipped_primitives = resolve_layer_markers(
+            self.egui_ctx.tessellate(shapes),
+            &self.between_layers,
+            &self.hidden_orders,
+        );
         self.painter.paint_and_update_textures(
             canvas,
             self.egui_ctx.pixels_per_point(),
             clipped_primitives,
             textures_delta,
         );
+        if let Some(history) = &mut self.history {
+            let time = self.egui_ctx.input().time;
+            history.capture(canvas, time, self.painter.frame_stats());
+        }
+        let repaint_after = self
+            .painter
+            .callback_repaint_after()
+            .unwrap_or(Duration::MAX);
+        // Keep painting until the textures converted in the background are shown.
+        if self.painter.has_pending_uploads() {
+            repaint_after.min(PENDING_UPLOAD_POLL)
+        } else {
+            repaint_after
+        }
+    }
+}
+
+/// The non-overlapping regions covered by the visible content of `shapes`.
+fn content_regions(shapes: &[egui::epaint::ClippedShape]) -> Vec<egui::Rect> {
+    let mut regions: Vec<egui::Rect> = Vec::new();
+    for egui::epaint::ClippedShape(clip_rect, shape) in shapes {
+        if let egui::Shape::Callback(callback) = shape {
+            if is_layer_marker(callback) {
+                continue;
+            }
+        }
+        let mut region = shape.visual_bounding_rect().intersect(*clip_rect);
+        if !region.is_positive() {
+            continue;
+        }
+        // Merging can make a region overlap ones it didn't before, so repeat until nothing
+        // overlaps the merged region.
+        while let Some(i) = regions.iter().position(|other| other.intersects(region)) {
+            region = region.union(regions.swap_remove(i));
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_color_near, image_pixel, pixel, surface, surface_bytes};
+
+    fn input() -> egui::RawInput {
+        egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(200.0, 100.0))),
+            pixels_per_point: Some(1.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prewarmed_glyphs_are_not_uploaded_again() {
+        let mut egui_skia = EguiSkia::new();
+        egui_skia.prewarm_fonts(input(), &["Hello"], &[egui::TextStyle::Body]);
+        assert!(egui_skia.textures_delta.set.is_empty());
+
+        egui_skia.run(input(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+        });
+        assert!(egui_skia.textures_delta.set.is_empty());
+    }
+
+    #[test]
+    fn rasterized_surfaces_keep_the_surface_props() {
+        let flags = SurfacePropsFlags::USE_DEVICE_INDEPENDENT_FONTS;
+        let props = SurfaceProps::new(flags, PixelGeometry::BGRH);
+        let options = |crop| RasterizeOptions {
+            surface_props: props,
+            crop,
+            ..Default::default()
+        };
+        let ui = |ctx: &Context| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+        };
+
+        let surface = rasterize((40, 20), ui, Some(options(None)));
+        assert_eq!(surface.props(), &props);
+        let crop = egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(10.0, 10.0));
+        let cropped = rasterize((40, 20), ui, Some(options(Some(crop))));
+        assert_eq!(cropped.props(), &props);
+
+        let default = rasterize((40, 20), ui, None);
+        assert_eq!(default.props().pixel_geometry(), PixelGeometry::Unknown);
+    }
+
+    #[test]
+    fn rasterize_widget_captures_the_padded_button() {
+        let id = egui::Id::new("button");
+        let ui = |ctx: &Context| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let rect = ui.button("Capture me").rect;
+                ctx.data().insert_temp(id, rect);
+            });
+        };
+        let located = std::cell::Cell::new(None);
+        let locate = |ctx: &Context| {
+            located.set(ctx.data().get_temp::<egui::Rect>(id));
+            located.get()
+        };
+        let options = RasterizeOptions {
+            pixels_per_point: 2.0,
+            ..Default::default()
+        };
+
+        let image = rasterize_widget((400, 200), ui, locate, 4.0, Some(options)).unwrap();
+        let padded = located.get().unwrap().expand(4.0);
+        let pixels = |min: f32, max: f32| (max * 2.0).ceil() as i32 - (min * 2.0).floor() as i32;
+        assert_eq!(image.width(), pixels(padded.min.x, padded.max.x));
+        assert_eq!(image.height(), pixels(padded.min.y, padded.max.y));
+
+        // The central panel starts at the surface origin, so a large margin is clamped to it.
+        let image = rasterize_widget((400, 200), ui, locate, 50.0, None).unwrap();
+        let clamped = located.get().unwrap().expand(50.0);
+        assert_eq!(image.width(), clamped.max.x.ceil() as i32);
+        assert_eq!(image.height(), clamped.max.y.ceil() as i32);
+    }
+
+    #[test]
+    fn setup_opens_a_combo_box_for_the_screenshot() {
+        let entry = std::cell::Cell::new(None);
+        let ui = |ctx: &Context| {
+            let area = egui::Area::new("combo_area").fixed_pos(egui::pos2(10.0, 10.0));
+            area.show(ctx, |ui| {
+                let mut selected = 0;
+                egui::ComboBox::from_id_source("combo")
+                    .selected_text("First")
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected, 0, "First");
+                        entry.set(Some(ui.selectable_value(&mut selected, 1, "Second").rect));
+                    });
+            });
+        };
+        // The ui of an area has the id of the area, the popup is keyed by the combo box button.
+        let popup = egui::Id::new("combo_area").with("combo").with("popup");
+        let open = move |ctx: &Context| ctx.memory().open_popup(popup);
+
+        let closed = RasterizeOptions {
+            warmup_frames: 1,
+            ..Default::default()
+        };
+        rasterize((200, 200), ui, Some(closed));
+        assert!(entry.take().is_none());
+
+        // The popup area is invisible in its first frame, the warmup frame shows it.
+        let options = RasterizeOptions {
+            setup: Some(Box::new(open)),
+            warmup_frames: 1,
+            ..Default::default()
+        };
+        let mut surface = rasterize((200, 200), ui, Some(options));
+        let rect = entry.get().expect("the popup entries are drawn");
+        // The popup opens below the button.
+        assert!(rect.min.y > 10.0, "{:?}", rect);
+        let colors = (rect.min.x as i32..rect.max.x as i32)
+            .flat_map(|x| (rect.min.y as i32..rect.max.y as i32).map(move |y| (x, y)))
+            .map(|(x, y)| pixel(&mut surface, x, y))
+            .map(|color| (color.a(), color.r(), color.g(), color.b()))
+            .collect::<std::collections::HashSet<_>>();
+        // The popup background, with the anti aliased text of the entry on it.
+        assert!(colors.iter().all(|&(alpha, ..)| alpha > 0));
+        assert!(colors.len() > 2, "{:?}", colors);
+    }
+
+    #[test]
+    fn content_regions_cover_the_drawn_widgets() {
+        let mut egui_skia = EguiSkia::new();
+        let mut buttons = Vec::new();
+        let areas = [
+            ("left", Pos2::new(10.0, 10.0)),
+            ("right", Pos2::new(120.0, 60.0)),
+        ];
+        let invisible = egui::Rect::from_min_max(Pos2::new(150.0, 5.0), Pos2::new(190.0, 15.0));
+        // New areas are invisible in their first frame, the second one draws them.
+        for _ in 0..2 {
+            buttons.clear();
+            egui_skia.run(input(), |ctx| {
+                for (name, pos) in areas {
+                    let area = egui::Area::new(name).fixed_pos(pos);
+                    buttons.push(area.show(ctx, |ui| ui.button(name)).inner.rect);
+                }
+                // Draws nothing, so it isn't content.
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                painter.rect_filled(invisible, 0.0, Color32::TRANSPARENT);
+            });
+        }
+
+        let regions = egui_skia.content_regions();
+        assert_eq!(regions.len(), 2, "{:?}", regions);
+        for button in &buttons {
+            let covering = regions.iter().filter(|r| r.contains_rect(*button));
+            assert_eq!(covering.count(), 1, "{:?} in {:?}", button, regions);
+        }
+        assert!(!regions.iter().any(|region| region.intersects(invisible)));
+        let bounds = egui_skia.content_bounds().unwrap();
+        assert!(bounds.contains_rect(buttons[0].union(buttons[1])));
+
+        egui_skia.run(input(), |_| {});
+        assert!(egui_skia.content_regions().is_empty());
+        assert_eq!(egui_skia.content_bounds(), None);
+    }
+
+    #[test]
+    fn draw_ui_paints_like_the_manual_sequence() {
+        let ctx = Context::default();
+        let input = egui::RawInput {
+            pixels_per_point: Some(2.0),
+            ..input()
+        };
+        let output = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+            ctx.output().copied_text = "copied".into();
+        });
+
+        let mut manual = surface(400, 200);
+        let primitives = ctx.tessellate(output.shapes.clone());
+        Painter::new().paint_and_update_textures(
+            manual.canvas(),
+            2.0,
+            primitives,
+            output.textures_delta.clone(),
+        );
+
+        let mut drawn = surface(400, 200);
+        let platform_output = draw_ui(&mut Painter::new(), drawn.canvas(), &ctx, output);
+
+        assert_eq!(platform_output.copied_text, "copied");
+        assert!(surface_bytes(&mut drawn) == surface_bytes(&mut manual));
+    }
+
+    #[test]
+    fn frames_after_a_dpi_change_paint_like_a_fresh_frame_at_the_new_dpi() {
+        let ui = |ctx: &Context| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.label("Hello");
+                let rect = egui::Rect::from_min_max(Pos2::new(50.0, 20.0), Pos2::new(60.0, 30.0));
+                ui.painter().rect_filled(rect, 0.0, Color32::RED);
+            });
+        };
+        // Paints a frame at every dpi in turn and returns the last one.
+        let paint = |dpis: &[f32]| {
+            let mut egui_skia = EguiSkia::new();
+            let mut last = None;
+            for &dpi in dpis {
+                let input = egui::RawInput {
+                    pixels_per_point: Some(dpi),
+                    ..input()
+                };
+                egui_skia.run(input, ui);
+                let mut surface = surface((200.0 * dpi) as i32, (100.0 * dpi) as i32);
+                egui_skia.paint(surface.canvas());
+                last = Some(surface);
+            }
+            last.unwrap()
+        };
+
+        let mut changed = paint(&[1.0, 2.0]);
+        let mut fresh = paint(&[2.0, 2.0]);
+        assert_eq!(changed.width(), 400);
+        // The rect covers 100..120 x 40..60 pixels at the new dpi.
+        assert_color_near(pixel(&mut changed, 110, 50), Color::RED, 0);
+        assert_ne!(pixel(&mut changed, 98, 50), Color::RED);
+        assert!(surface_bytes(&mut changed) == surface_bytes(&mut fresh));
+    }
+
+    #[test]
+    fn draws_between_layers_are_stacked_between_the_egui_orders() {
+        let mut egui_skia = EguiSkia::new();
+        egui_skia.insert_between_layers(egui::Order::Background, |canvas| {
+            let mut paint = Paint::default();
+            paint.set_color(Color::GREEN);
+            canvas.draw_rect(Rect::from_ltrb(0.0, 0.0, 100.0, 100.0), &paint);
+        });
+        egui_skia.run(input(), |ctx| {
+            let layer = |order| egui::LayerId::new(order, egui::Id::new("test"));
+            let rect = |min: (f32, f32), max: (f32, f32)| {
+                egui::Rect::from_min_max(Pos2::new(min.0, min.1), Pos2::new(max.0, max.1))
+            };
+            let background = ctx.layer_painter(layer(egui::Order::Background));
+            background.rect_filled(rect((0.0, 0.0), (200.0, 100.0)), 0.0, Color32::BLUE);
+            let tooltip = ctx.layer_painter(layer(egui::Order::Tooltip));
+            tooltip.rect_filled(rect((40.0, 40.0), (60.0, 60.0)), 0.0, Color32::RED);
+        });
+        let mut surface = surface(200, 100);
+        egui_skia.paint(surface.canvas());
+
+        // The background panel, the injected fill over it and the tooltip over both.
+        assert_color_near(pixel(&mut surface, 150, 50), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 20, 50), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 50, 50), Color::RED, 0);
+    }
+
+    #[test]
+    fn text_outline_adds_dark_pixels_around_white_text_on_white() {
+        let dark_pixels = |painter: Painter| {
+            let mut egui_skia = EguiSkia::new();
+            egui_skia.painter = painter;
+            egui_skia.run(input(), |ctx| {
+                let frame = egui::Frame::none().fill(Color32::WHITE);
+                egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+                    ui.label(egui::RichText::new("Hello").color(Color32::WHITE).size(20.0));
+                });
+            });
+            let mut surface = surface(200, 100);
+            egui_skia.paint(surface.canvas());
+            let pixmap = surface.peek_pixels().unwrap();
+            let mut dark = 0;
+            for y in 0..100 {
+                for x in 0..200 {
+                    let color = pixmap.get_color((x, y));
+                    if color.r() < 128 {
+                        dark += 1;
+                    }
+                }
+            }
+            dark
+        };
+
+        assert_eq!(dark_pixels(Painter::new()), 0);
+        assert!(dark_pixels(Painter::new().with_text_outline(Color32::BLACK, 2.0)) > 50);
+    }
+
+    #[test]
+    fn animated_callbacks_drive_the_repaint_cadence() {
+        use crate::EguiSkiaPaintCallback;
+        use std::sync::Mutex;
+
+        // Runs frames until the callback stops asking for them or 100 ms have passed, advancing
+        // the egui clock by the requested duration, and returns the times seen by the callback.
+        let frames = |repaint_after: Option<Duration>| {
+            let times = Arc::new(Mutex::new(Vec::new()));
+            let callback_times = times.clone();
+            let callback = Arc::new(EguiSkiaPaintCallback::animated(move |_, info, response| {
+                callback_times.lock().unwrap().push(info.time);
+                if let Some(duration) = repaint_after {
+                    response.request_repaint_after(duration);
+                }
+            }));
+            let mut egui_skia = EguiSkia::new();
+            let mut time = 0.0;
+            while time < 0.1 {
+                let input = egui::RawInput {
+                    time: Some(time),
+                    ..input()
+                };
+                egui_skia.run(input, |ctx| {
+                    let rect = egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(10.0, 10.0));
+                    ctx.layer_painter(egui::LayerId::background()).add(egui::PaintCallback {
+                        rect,
+                        callback: callback.clone(),
+                    });
+                });
+                let repaint_after = egui_skia.paint(surface(200, 100).canvas());
+                if repaint_after == Duration::MAX {
+                    break;
+                }
+                time += repaint_after.as_secs_f64();
+            }
+            let times = times.lock().unwrap().clone();
+            times
+        };
+
+        let animated = frames(Some(Duration::from_millis(16)));
+        let expected = (0..7).map(|frame| frame as f64 * 0.016).collect::<Vec<_>>();
+        assert_eq!(animated.len(), expected.len());
+        for (time, expected) in animated.iter().zip(expected) {
+            assert!((time - expected).abs() < 1e-9, "{} != {}", time, expected);
+        }
+        assert_eq!(frames(None), vec![0.0]);
+    }
+
+    #[test]
+    fn render_logical_scales_the_logical_layout() {
+        let image = render_logical(egui::vec2(400.0, 300.0), 2.0, |ctx| {
+            assert_eq!(ctx.screen_rect().size(), egui::vec2(400.0, 300.0));
+            // A square in the bottom right corner of the logical screen.
+            let corner = egui::Rect::from_min_max(Pos2::new(390.0, 290.0), Pos2::new(400.0, 300.0));
+            let painter = ctx.layer_painter(egui::LayerId::background());
+            painter.rect_filled(corner, 0.0, Color32::RED);
+        });
+
+        assert_eq!((image.width(), image.height()), (800, 600));
+        assert_color_near(image_pixel(&image, 781, 581), Color::RED, 0);
+        assert_color_near(image_pixel(&image, 798, 598), Color::RED, 0);
+        assert_ne!(image_pixel(&image, 778, 578), Color::RED);
+    }
+
+    #[test]
+    fn scrollable_rows_appear_exactly_once_in_the_stitched_image() {
+        // 100 numbered rows 13 points high, each in its own color, in a viewport of 120 points
+        // so the slices don't line up with the rows.
+        let row_color = |row: u8| Color32::from_rgb(row, 255 - row, 7);
+        let image = rasterize_scrollable(
+            egui::vec2(50.0, 120.0),
+            |ui| {
+                ui.spacing_mut().item_spacing.y = 0.0;
+                for row in 0..100 {
+                    let size = egui::vec2(50.0, 13.0);
+                    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    ui.painter().rect_filled(rect, 0.0, row_color(row));
+                }
+            },
+            None,
+        );
+        assert_eq!(image.height(), 1300);
+
+        // The runs of equal pixels down a column, with their lengths.
+        let mut runs: Vec<(Color, i32)> = Vec::new();
+        for y in 0..image.height() {
+            let color = image_pixel(&image, 5, y);
+            match runs.last_mut() {
+                Some((last, length)) if *last == color => *length += 1,
+                _ => runs.push((color, 1)),
+            }
+        }
+        let expected = (0..100)
+            .map(|row| (Color::from_rgb(row, 255 - row, 7), 13))
+            .collect::<Vec<_>>();
+        assert_eq!(runs, expected);
+    }
+
+    #[test]
+    fn debug_rects_render_and_can_be_hidden() {
+        let paint = |egui_skia: &mut EguiSkia| {
+            egui_skia.run(input(), |ctx| {
+                let screen = ctx.screen_rect();
+                let background = ctx.layer_painter(egui::LayerId::background());
+                background.rect_filled(screen, 0.0, Color32::BLUE);
+                // Widget bounds as egui's debug options draw them.
+                let bounds = egui::Rect::from_min_max(Pos2::new(10.0, 10.0), Pos2::new(50.0, 30.0));
+                ctx.debug_painter().rect_stroke(bounds, 0.0, (2.0, Color32::RED));
+            });
+            let mut surface = surface(200, 100);
+            egui_skia.paint(surface.canvas());
+            surface
+        };
+
+        let mut egui_skia = EguiSkia::new();
+        let mut shown = paint(&mut egui_skia);
+        assert_color_near(pixel(&mut shown, 10, 20), Color::RED, 8);
+        assert_color_near(pixel(&mut shown, 30, 20), Color::BLUE, 0);
+
+        egui_skia.set_order_visible(egui::Order::Debug, false);
+        let mut hidden = paint(&mut egui_skia);
+        assert_color_near(pixel(&mut hidden, 10, 20), Color::BLUE, 0);
+        assert_color_near(pixel(&mut hidden, 30, 20), Color::BLUE, 0);
+    }
+
+    #[test]
+    fn screenshots_draw_the_cursor_only_when_asked() {
+        let mut egui_skia = EguiSkia::new();
+        let mut moved = input();
+        moved.events.push(egui::Event::PointerMoved(Pos2::new(100.0, 50.0)));
+        egui_skia.run(moved, |_| {});
+
+        // The arrow spans about 12x20 points from its hotspot at the pointer.
+        let near_pointer = |x: i32, y: i32| (98..=114).contains(&x) && (48..=72).contains(&y);
+        let drawn_pixels = |image: &Image| {
+            let mut drawn = Vec::new();
+            for y in 0..image.height() {
+                for x in 0..image.width() {
+                    if image_pixel(image, x, y).a() > 0 {
+                        drawn.push((x, y));
+                    }
+                }
+            }
+            drawn
+        };
+
+        let with_cursor = ScreenshotOptions { draw_cursor: true };
+        let image = egui_skia.screenshot(&with_cursor).unwrap();
+        assert_color_near(image_pixel(&image, 102, 60), Color::BLACK, 0);
+        let drawn = drawn_pixels(&image);
+        assert!(drawn.iter().all(|&(x, y)| near_pointer(x, y)), "{:?}", drawn);
+
+        let image = egui_skia.screenshot(&ScreenshotOptions::default()).unwrap();
+        assert!(drawn_pixels(&image).is_empty());
+    }
+
+    #[test]
+    fn screenshots_of_an_empty_screen_are_none() {
+        let mut egui_skia = EguiSkia::new();
+        let mut empty = input();
+        empty.screen_rect = Some(egui::Rect::from_min_size(Pos2::ZERO, Vec2::ZERO));
+        egui_skia.run(empty, |_| {});
+        assert!(egui_skia.screenshot(&ScreenshotOptions::default()).is_none());
+    }
+
+    #[test]
+    fn the_frame_history_keeps_the_last_frames_in_order() {
+        let mut egui_skia = EguiSkia::new();
+        egui_skia.enable_frame_history(3);
+        let colors = [
+            Color32::RED,
+            Color32::GREEN,
+            Color32::BLUE,
+            Color32::YELLOW,
+            Color32::WHITE,
+        ];
+        let mut surface = surface(200, 100);
+        for color in colors {
+            egui_skia.run(input(), |ctx| {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                painter.rect_filled(ctx.screen_rect(), 0.0, color);
+            });
+            egui_skia.paint(surface.canvas());
+        }
+
+        let history = egui_skia.frame_history();
+        let frames = history.iter().map(|frame| frame.frame).collect::<Vec<_>>();
+        assert_eq!(frames, [2, 3, 4]);
+        for (frame, color) in history.iter().zip(&colors[2..]) {
+            // Downscaled to a quarter by default.
+            assert_eq!(frame.image.dimensions(), skia_safe::ISize::new(50, 25));
+            let expected = Color::from_argb(color.a(), color.r(), color.g(), color.b());
+            assert_color_near(image_pixel(&frame.image, 25, 12), expected, 1);
+        }
+    }
+
+    #[test]
+    fn hdr_backgrounds_keep_values_above_one_and_blend_unclamped() {
+        let output = OutputOptions::hdr();
+        let mut surface = Surface::new_raster(&output.image_info((20, 10)), None, None).unwrap();
+        let linear = skia_safe::ColorSpace::new_srgb_linear();
+        let mut background = Paint::new(skia_safe::Color4f::new(2.0, 1.5, 0.5, 1.0), &linear);
+        background.set_blend_mode(BlendMode::Src);
+        surface.canvas().draw_paint(&background);
+
+        let options = RasterizeOptions {
+            output,
+            ..Default::default()
+        };
+        draw_onto_surface(
+            &mut surface,
+            |ctx| {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                let left = egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(10.0, 10.0));
+                painter.rect_filled(left, 0.0, Color32::from_white_alpha(128));
+            },
+            Some(options),
+        );
+
+        let pixels = surface.peek_pixels().unwrap();
+        let channels = |x: i32| {
+            let color = pixels.get_color_4f((x, 5));
+            [color.r, color.g, color.b, color.a]
+        };
+        let assert_near = |actual: [f32; 4], expected: [f32; 4]| {
+            let mut pairs = actual.iter().zip(expected);
+            let near = pairs.all(|(a, e)| (a - e).abs() < 0.02);
+            assert!(near, "expected {:?}, got {:?}", expected, actual);
+        };
+        // Untouched where egui is transparent.
+        assert_near(channels(15), [2.0, 1.5, 0.5, 1.0]);
+        // Half white blended over the background in float precision, still above one.
+        let alpha = 128.0 / 255.0;
+        let blend = |background: f32| alpha + background * (1.0 - alpha);
+        assert_near(channels(5), [blend(2.0), blend(1.5), blend(0.5), 1.0]);
+    }
+
+    #[test]
+    fn font_atlases_stay_within_the_max_texture_side_of_the_painter() {
+        let atlas_size = |egui_skia: &mut EguiSkia| {
+            let mut input = input();
+            input.pixels_per_point = Some(2.0);
+            input.max_texture_side = Some(4096);
+            egui_skia.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+            });
+            let set = &egui_skia.textures_delta.set;
+            let font = set.iter().find(|(id, _)| *id == egui::TextureId::default());
+            font.unwrap().1.image.size()
+        };
+
+        let [width, _] = atlas_size(&mut EguiSkia::new());
+        assert_eq!(width, 4096);
+
+        // A mocked small device limit.
+        let mut egui_skia = EguiSkia::new();
+        egui_skia.painter = Painter::new().with_max_texture_side(512);
+        let [width, height] = atlas_size(&mut egui_skia);
+        assert_eq!(width, 512);
+        assert!(height <= 512);
+
+        let mut surface = surface(400, 200);
+        egui_skia.paint(surface.canvas());
+        // The text was drawn from the atlas.
+        let background = pixel(&mut surface, 399, 199);
+        assert!((0..60).any(|x| pixel(&mut surface, x, 30) != background));
+    }
+
+    #[test]
+    fn text_renders_the_same_from_a_font_atlas_split_into_tiles() {
+        let render = |painter: Painter| {
+            let mut egui_skia = EguiSkia::new();
+            egui_skia.painter = painter;
+            egui_skia.run(input(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("Sphinx of black quartz");
+                    ui.label("The quick brown fox jumps over the lazy dog 0123456789");
+                });
+            });
+            let mut surface = surface(200, 100);
+            egui_skia.paint(surface.canvas());
+            let textures = egui_skia.painter.texture_store().read().unwrap();
+            let font = textures.get(&egui::TextureId::default()).unwrap();
+            (surface_bytes(&mut surface), font.tiles.len())
+        };
+
+        let (whole, whole_tiles) = render(Painter::new());
+        // A mocked small device limit, the atlas is 2048 texels wide.
+        let (split, tiles) = render(Painter::new().with_max_atlas_size(256));
+        assert_eq!(whole_tiles, 0);
+        assert!(tiles > 1);
+        let background = whole[whole.len() - 4..].to_vec();
+        assert!(whole.chunks(4).any(|texel| texel != background));
+        let difference = whole.iter().zip(&split).map(|(a, b)| a.abs_diff(*b)).max();
+        assert!(difference.unwrap() <= 2, "{:?}", difference);
+    }
+
+    #[test]
+    fn clear_type_gammas_make_small_text_heavier() {
+        // The ink of small text: how far its pixels are from the background of the panel.
+        let ink = |painter: Painter, size: f32| {
+            let mut egui_skia = EguiSkia::new();
+            egui_skia.painter = painter;
+            egui_skia.run(input(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.label(egui::RichText::new("Hamburgefonstiv 0123").size(size));
+                });
+            });
+            let mut surface = surface(200, 100);
+            egui_skia.paint(surface.canvas());
+            let pixels = surface_bytes(&mut surface);
+            let background = pixels[pixels.len() - 4..].to_vec();
+            let channels = pixels.iter().zip(background.iter().cycle());
+            channels.map(|(a, b)| a.abs_diff(*b) as u64).sum::<u64>()
+        };
+
+        for size in [11.0, 12.0, 13.0] {
+            let default = ink(Painter::new(), size);
+            let gamma = |gamma| Painter::new().with_clear_type_gamma(gamma).unwrap();
+            let (low, high) = (ink(gamma(1.8), size), ink(gamma(2.2), size));
+            let heavier = default < low && low < high;
+            assert!(heavier, "{}: {} {} {}", size, default, low, high);
+        }
+        // Back to the coverage egui rasterized.
+        let restored = Painter::new()
+            .with_clear_type_gamma(2.2)
+            .and_then(|painter| painter.with_clear_type_gamma(1.0))
+            .unwrap();
+        assert_eq!(ink(restored, 12.0), ink(Painter::new(), 12.0));
     }
 }
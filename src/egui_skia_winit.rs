@@ -1,17 +1,122 @@
 use std::time::Duration;
 
-use egui::Context;
+use egui::{vec2, Context, Pos2, Vec2};
 pub use egui_winit;
+use egui_winit::winit::dpi::PhysicalPosition;
+use egui_winit::winit::event::{DeviceEvent, WindowEvent};
+#[cfg(feature = "clipboard-image")]
+use egui_winit::winit::event::{ElementState, ModifiersState, VirtualKeyCode};
 use egui_winit::winit::event_loop::EventLoopWindowTarget;
-use egui_winit::winit::window::Window;
+use egui_winit::winit::window::{CursorGrabMode, Window};
 use egui_winit::EventResponse;
-use skia_safe::Canvas;
+use skia_safe::{Canvas, PixelGeometry, SurfaceProps, SurfacePropsFlags};
 
+#[cfg(feature = "clipboard-image")]
+use crate::clipboard_image::ImagePaste;
 use crate::EguiSkia;
+#[cfg(feature = "gilrs")]
+use crate::GamepadNavigator;
+
+/// How far, in points, the pointer has to leave the hit region before the window lets pointer
+/// events through again, so it doesn't flicker between the states at the edges.
+const CLICK_THROUGH_MARGIN: f32 = 4.0;
+
+type HitTest = Box<dyn Fn(&Context, Pos2) -> bool>;
+
+/// The close state of a frame, kept in egui's temporary data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CloseState {
+    Requested,
+    Cancelled,
+    Close,
+}
+
+fn close_state_id() -> egui::Id {
+    egui::Id::new("egui_skia_close_state")
+}
+
+fn cursor_grab_id() -> egui::Id {
+    egui::Id::new("egui_skia_cursor_grab")
+}
+
+fn grab_motion_id() -> egui::Id {
+    egui::Id::new("egui_skia_grab_motion")
+}
+
+/// A cursor grab in effect.
+struct CursorGrab {
+    /// Where the pointer was when the grab started, in points.
+    restore: Option<Pos2>,
+}
+
+/// What has to happen to the cursor grab after a frame, see [`cursor_grab_change`].
+#[derive(Debug, PartialEq)]
+enum GrabChange {
+    Keep,
+    /// Grab the cursor, which was at `restore` when the grab started.
+    Grab {
+        restore: Option<Pos2>,
+    },
+    /// Release the grab and move the cursor back to `restore`.
+    Release {
+        restore: Option<Pos2>,
+    },
+}
+
+/// Trackpad gestures the host reports, see [`EguiSkiaWinit::on_touchpad_magnify`].
+struct Gestures {
+    /// The zoom events of the gestures since the last frame.
+    events: Vec<egui::Event>,
+    /// The zoom factor of a smart magnify, see [`EguiSkiaWinit::with_smart_magnify_zoom`].
+    smart_zoom: f32,
+    /// Whether the last smart magnify zoomed in, so the next one zooms back out.
+    smart_magnified: bool,
+}
+
+impl Default for Gestures {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            smart_zoom: 2.0,
+            smart_magnified: false,
+        }
+    }
+}
+
+impl Gestures {
+    fn magnify(&mut self, delta: f64) {
+        self.events.push(egui::Event::Zoom((delta as f32).exp()));
+    }
+
+    fn smart_magnify(&mut self) {
+        let zoom = match self.smart_magnified {
+            true => 1.0 / self.smart_zoom,
+            false => self.smart_zoom,
+        };
+        self.smart_magnified = !self.smart_magnified;
+        self.events.push(egui::Event::Zoom(zoom));
+    }
+}
 
 pub struct EguiSkiaWinit {
     pub egui_skia: EguiSkia,
     pub egui_winit: egui_winit::State,
+    /// Adds the gamepad input to the input of every frame in [`Self::run`].
+    #[cfg(feature = "gilrs")]
+    pub gamepad: Option<GamepadNavigator>,
+    hit_test: Option<HitTest>,
+    captures_pointer: bool,
+    close_requested: bool,
+    should_close: bool,
+    cursor_grab: Option<CursorGrab>,
+    /// The raw mouse motion since the last frame while the cursor is grabbed.
+    grab_motion: Vec2,
+    focus_lost: bool,
+    gestures: Gestures,
+    #[cfg(feature = "clipboard-image")]
+    image_paste: Option<ImagePaste>,
+    #[cfg(feature = "clipboard-image")]
+    modifiers: ModifiersState,
 }
 
 impl EguiSkiaWinit {
@@ -21,6 +126,181 @@ impl EguiSkiaWinit {
         Self {
             egui_winit,
             egui_skia: EguiSkia::new(),
+            #[cfg(feature = "gilrs")]
+            gamepad: None,
+            hit_test: None,
+            captures_pointer: true,
+            close_requested: false,
+            should_close: false,
+            cursor_grab: None,
+            grab_motion: Vec2::ZERO,
+            focus_lost: false,
+            gestures: Gestures::default(),
+            #[cfg(feature = "clipboard-image")]
+            image_paste: None,
+            #[cfg(feature = "clipboard-image")]
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    /// Whether the window should close, checked after [`Self::run`].
+    ///
+    /// A `CloseRequested` event passed to [`Self::on_event`] doesn't close the window right away:
+    /// the next frame sees it through [`Self::close_requested`] and can veto it with
+    /// [`Self::cancel_close`], e.g. to ask about unsaved changes first. If it doesn't, this
+    /// returns `true` after that frame. The ui can also close the window itself with
+    /// [`Self::request_close`].
+    pub fn should_close(&self) -> bool {
+        self.should_close
+    }
+
+    /// Whether the user asked to close the window in the current frame.
+    pub fn close_requested(ctx: &Context) -> bool {
+        ctx.data().get_temp(close_state_id()) == Some(CloseState::Requested)
+    }
+
+    /// Keeps the window open after a close request of the current frame.
+    pub fn cancel_close(ctx: &Context) {
+        if Self::close_requested(ctx) {
+            ctx.data().insert_temp(close_state_id(), CloseState::Cancelled);
+        }
+    }
+
+    /// Closes the window after the current frame, see [`Self::should_close`].
+    pub fn request_close(ctx: &Context) {
+        ctx.data().insert_temp(close_state_id(), CloseState::Close);
+    }
+
+    /// Grabs or releases the cursor after the current frame, e.g. grab it while the right button
+    /// is dragged in a 3D viewport.
+    ///
+    /// The grabbed cursor is hidden and locked in place, or confined to the window on platforms
+    /// that can't lock it (Windows and X11). egui's pointer position stops being useful then, read
+    /// the raw mouse motion with [`Self::grab_motion`] instead. On release the cursor shows up
+    /// where it was grabbed. Losing the focus, e.g. by alt-tabbing away, releases the grab.
+    ///
+    /// Needs the device events of the event loop passed to [`Self::on_device_event`].
+    pub fn set_cursor_grab(ctx: &Context, grab: bool) {
+        ctx.data().insert_temp(cursor_grab_id(), grab);
+    }
+
+    /// Whether the cursor is grabbed or was asked to be in the current frame.
+    pub fn cursor_grabbed(ctx: &Context) -> bool {
+        ctx.data().get_temp(cursor_grab_id()).unwrap_or(false)
+    }
+
+    /// The raw mouse motion since the previous frame while the cursor is grabbed, in unscaled
+    /// device units, see [`Self::set_cursor_grab`]. Zero while the cursor isn't grabbed.
+    pub fn grab_motion(ctx: &Context) -> Vec2 {
+        ctx.data().get_temp(grab_motion_id()).unwrap_or(Vec2::ZERO)
+    }
+
+    /// Collects the raw mouse motion while the cursor is grabbed, see [`Self::grab_motion`].
+    ///
+    /// Returns `true` if the event was used and the app should request a redraw.
+    pub fn on_device_event(&mut self, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::MouseMotion { delta } if self.cursor_grab.is_some() => {
+                self.grab_motion += vec2(delta.0 as f32, delta.1 as f32);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Grabs or releases the cursor as the last frame asked for.
+    fn update_cursor_grab(&mut self, window: &Window) {
+        let ctx = &self.egui_skia.egui_ctx;
+        let focus_lost = std::mem::take(&mut self.focus_lost);
+        match cursor_grab_change(ctx, self.cursor_grab.as_ref(), focus_lost) {
+            GrabChange::Grab { restore } => {
+                let grabbed = window
+                    .set_cursor_grab(CursorGrabMode::Locked)
+                    .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+                if grabbed.is_err() {
+                    ctx.data().insert_temp(cursor_grab_id(), false);
+                    return;
+                }
+                self.cursor_grab = Some(CursorGrab { restore });
+            }
+            GrabChange::Release { restore } => {
+                let _ = window.set_cursor_grab(CursorGrabMode::None);
+                window.set_cursor_visible(true);
+                if let Some(pos) = restore {
+                    let pixels_per_point = ctx.pixels_per_point();
+                    let position = PhysicalPosition::new(
+                        (pos.x * pixels_per_point) as f64,
+                        (pos.y * pixels_per_point) as f64,
+                    );
+                    let _ = window.set_cursor_position(position);
+                }
+                self.cursor_grab = None;
+                self.grab_motion = Vec2::ZERO;
+            }
+            GrabChange::Keep => {}
+        }
+        // egui_winit only sets the cursor visibility when the cursor icon changes.
+        if self.cursor_grab.is_some() {
+            window.set_cursor_visible(false);
+        }
+    }
+
+    /// Calls `on_paste` with the image on the clipboard when the user pastes (`Ctrl+V`, `Cmd+V`
+    /// on macOS) and the clipboard holds an image, e.g. to load a pasted screenshot as a texture.
+    ///
+    /// The image is read and converted on another thread and handed to `on_paste` in a later
+    /// [`Self::run`], before the ui runs. Text pastes still reach egui as usual.
+    #[cfg(feature = "clipboard-image")]
+    pub fn with_image_paste(mut self, on_paste: impl FnMut(egui::ColorImage) + 'static) -> Self {
+        self.image_paste = Some(ImagePaste::new(on_paste));
+        self
+    }
+
+    /// Lets pointer events outside of egui's areas (windows, menus, popups) pass through to the
+    /// windows below, e.g. for a transparent overlay. Panels are not areas, so they don't catch
+    /// the pointer.
+    ///
+    /// [`Self::run`] toggles the hit testing of the window, see [`Self::with_hit_test`].
+    pub fn with_click_through(self) -> Self {
+        self.with_hit_test(hits_area)
+    }
+
+    /// Like [`Self::with_click_through`], but `hit_test` decides whether the window captures the
+    /// pointer at a position in points.
+    ///
+    /// The window keeps capturing the pointer while a button is held or something is dragged.
+    /// winit sends no cursor events while they pass through the window, so to capture the
+    /// pointer again when it moves back over egui, poll the global cursor position and pass it
+    /// to [`Self::on_global_cursor_moved`].
+    pub fn with_hit_test(mut self, hit_test: impl Fn(&Context, Pos2) -> bool + 'static) -> Self {
+        self.hit_test = Some(Box::new(hit_test));
+        self
+    }
+
+    /// Updates the click through for the pointer at `position` in physical screen coordinates,
+    /// see [`Self::with_hit_test`].
+    pub fn on_global_cursor_moved(&mut self, window: &Window, position: PhysicalPosition<f64>) {
+        let origin = match window.inner_position() {
+            Ok(origin) => origin,
+            Err(_) => return,
+        };
+        let pixels_per_point = self.egui_skia.egui_ctx.pixels_per_point();
+        let pos = Pos2::new(
+            (position.x - origin.x as f64) as f32 / pixels_per_point,
+            (position.y - origin.y as f64) as f32 / pixels_per_point,
+        );
+        self.update_click_through(window, Some(pos));
+    }
+
+    fn update_click_through(&mut self, window: &Window, pointer: Option<Pos2>) {
+        let hit_test = match &self.hit_test {
+            Some(hit_test) => hit_test,
+            None => return,
+        };
+        let ctx = &self.egui_skia.egui_ctx;
+        let capture = captures_pointer(ctx, hit_test, pointer, self.captures_pointer);
+        if capture != self.captures_pointer && window.set_cursor_hittest(capture).is_ok() {
+            self.captures_pointer = capture;
         }
     }
 
@@ -30,25 +310,442 @@ impl EguiSkiaWinit {
     /// and only when this returns `false` pass on the events to your game.
     ///
     /// Note that egui uses `tab` to move focus between elements, so this will always return `true` for tabs.
-    pub fn on_event(&mut self, event: &egui_winit::winit::event::WindowEvent<'_>) -> EventResponse {
-        self.egui_winit.on_event(&self.egui_skia.egui_ctx, event)
+    ///
+    /// Pinch gestures: Windows precision touchpads send ctrl + scroll wheel events, which egui
+    /// turns into [`egui::Event::Zoom`]. Touchscreen pinches stay raw touch events, so egui's own
+    /// gesture recognition handles them once. winit 0.27 doesn't report the trackpad gestures of
+    /// macOS, pass them to [`Self::on_touchpad_magnify`] and [`Self::on_smart_magnify`] instead.
+    /// egui 0.20 has no rotation event, so rotation gestures don't reach egui.
+    pub fn on_event(&mut self, event: &WindowEvent<'_>) -> EventResponse {
+        match event {
+            WindowEvent::Focused(false) if self.cursor_grab.is_some() => {
+                self.focus_lost = true;
+                let mut response = self.egui_winit.on_event(&self.egui_skia.egui_ctx, event);
+                response.repaint = true;
+                response
+            }
+            WindowEvent::CloseRequested => {
+                self.close_requested = true;
+                EventResponse {
+                    consumed: true,
+                    repaint: true,
+                }
+            }
+            _ => {
+                #[cfg(feature = "clipboard-image")]
+                self.check_image_paste(event);
+                self.egui_winit.on_event(&self.egui_skia.egui_ctx, event)
+            }
+        }
+    }
+
+    /// Starts reading the clipboard image for paste key presses, see [`Self::with_image_paste`].
+    #[cfg(feature = "clipboard-image")]
+    fn check_image_paste(&mut self, event: &WindowEvent<'_>) {
+        let input = match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                return;
+            }
+            WindowEvent::KeyboardInput { input, .. } => input,
+            _ => return,
+        };
+        let command = if cfg!(target_os = "macos") {
+            self.modifiers.logo()
+        } else {
+            self.modifiers.ctrl()
+        };
+        let paste = match input.virtual_keycode {
+            Some(VirtualKeyCode::V) => command,
+            Some(VirtualKeyCode::Paste) => true,
+            _ => false,
+        };
+        if paste && input.state == ElementState::Pressed {
+            if let Some(image_paste) = &self.image_paste {
+                image_paste.request(&self.egui_skia.egui_ctx);
+            }
+        }
+    }
+
+    /// Zooms egui by `exp(delta)` in the next frame, for the trackpad pinches winit 0.27 doesn't
+    /// report, e.g. the `magnification` of the magnify events of macOS. Pinching out and back in
+    /// by the same amount cancels out.
+    pub fn on_touchpad_magnify(&mut self, delta: f64) {
+        self.gestures.magnify(delta);
+    }
+
+    /// Zooms egui in by the zoom of [`Self::with_smart_magnify_zoom`] in the next frame, or back
+    /// out if the last smart magnify zoomed in, for the double-tap of the smart magnify gesture
+    /// of macOS trackpads.
+    pub fn on_smart_magnify(&mut self) {
+        self.gestures.smart_magnify();
+    }
+
+    /// Sets the zoom factor of [`Self::on_smart_magnify`], `2.0` by default.
+    pub fn with_smart_magnify_zoom(mut self, zoom: f32) -> Self {
+        self.gestures.smart_zoom = zoom;
+        self
     }
 
     /// Returns a duration after witch egui should repaint.
     ///
     /// Call [`Self::paint`] later to paint.
     pub fn run(&mut self, window: &Window, run_ui: impl FnMut(&Context)) -> Duration {
-        let raw_input = self.egui_winit.take_egui_input(window);
+        let mut raw_input = self.egui_winit.take_egui_input(window);
+        raw_input.events.append(&mut self.gestures.events);
+        #[cfg(feature = "clipboard-image")]
+        if let Some(image_paste) = &mut self.image_paste {
+            // A clipboard holding only an image pastes no text.
+            raw_input
+                .events
+                .retain(|event| !matches!(event, egui::Event::Paste(text) if text.is_empty()));
+            image_paste.deliver();
+        }
+        #[cfg(feature = "gilrs")]
+        if let Some(gamepad) = &mut self.gamepad {
+            gamepad.push_events(&mut raw_input);
+        }
 
+        let ctx = self.egui_skia.egui_ctx.clone();
+        begin_close_frame(&ctx, std::mem::take(&mut self.close_requested));
+        let grab_motion = std::mem::take(&mut self.grab_motion);
+        ctx.data().insert_temp(grab_motion_id(), grab_motion);
         let (repaint_after, platform_output) = self.egui_skia.run(raw_input, run_ui);
+        self.should_close |= end_close_frame(&ctx);
 
         self.egui_winit
             .handle_platform_output(window, &self.egui_skia.egui_ctx, platform_output);
+
+        self.update_cursor_grab(window);
+
+        let pointer = self.egui_skia.egui_ctx.input().pointer.hover_pos();
+        self.update_click_through(window, pointer);
         repaint_after
     }
 
     /// Paint the results of the last call to [`Self::run`].
-    pub fn paint(&mut self, canvas: &mut Canvas) {
-        self.egui_skia.paint(canvas);
+    ///
+    /// Returns the duration after which the paint callbacks want a repaint, see
+    /// [`EguiSkia::paint`].
+    pub fn paint(&mut self, canvas: &mut Canvas) -> Duration {
+        self.egui_skia.paint(canvas)
+    }
+}
+
+/// Guesses the [`SurfaceProps`] for surfaces shown in `window`.
+///
+/// winit doesn't expose the subpixel layout of monitors, so this assumes the common horizontal
+/// RGB layout on low dpi monitors, where subpixel text rendering makes a visible difference, and
+/// an unknown layout (grayscale anti aliasing) on high dpi monitors. BGR and rotated screens are
+/// not detected.
+pub fn surface_props_for_window(window: &Window) -> SurfaceProps {
+    let pixel_geometry = if window.scale_factor() < 2.0 {
+        PixelGeometry::RGBH
+    } else {
+        PixelGeometry::Unknown
+    };
+    SurfaceProps::new(SurfacePropsFlags::empty(), pixel_geometry)
+}
+
+/// Hands a close request of the window to the ui of the next frame, see
+/// [`EguiSkiaWinit::should_close`].
+fn begin_close_frame(ctx: &Context, close_requested: bool) {
+    if close_requested {
+        ctx.data().insert_temp(close_state_id(), CloseState::Requested);
+    }
+}
+
+/// Whether the ui of the frame let a close request through or closed the window itself.
+fn end_close_frame(ctx: &Context) -> bool {
+    let close_state = ctx.data().get_temp::<CloseState>(close_state_id());
+    ctx.data().remove::<CloseState>(close_state_id());
+    matches!(close_state, Some(CloseState::Requested | CloseState::Close))
+}
+
+/// Whether the cursor has to be grabbed or released after the frame, given the `grab` in effect.
+///
+/// Losing the focus releases the grab, and the ui has to ask for it again to grab once more.
+fn cursor_grab_change(ctx: &Context, grab: Option<&CursorGrab>, focus_lost: bool) -> GrabChange {
+    if focus_lost {
+        ctx.data().insert_temp(cursor_grab_id(), false);
+    }
+    match (grab, EguiSkiaWinit::cursor_grabbed(ctx)) {
+        (None, true) => GrabChange::Grab {
+            restore: ctx.input().pointer.hover_pos(),
+        },
+        (Some(grab), false) => GrabChange::Release {
+            restore: grab.restore,
+        },
+        _ => GrabChange::Keep,
+    }
+}
+
+/// Whether `pos` is over one of egui's areas, the hit test of
+/// [`EguiSkiaWinit::with_click_through`].
+fn hits_area(ctx: &Context, pos: Pos2) -> bool {
+    ctx.layer_id_at(pos).is_some()
+}
+
+/// Whether the window captures the pointer at `pointer`, given whether it `captured` it so far.
+///
+/// A captured pointer has to leave the hit region by [`CLICK_THROUGH_MARGIN`] before it passes
+/// through again, and stays captured while a button is held or something is dragged.
+fn captures_pointer(
+    ctx: &Context,
+    hit_test: &dyn Fn(&Context, Pos2) -> bool,
+    pointer: Option<Pos2>,
+    captured: bool,
+) -> bool {
+    let busy = ctx.input().pointer.any_down() || ctx.memory().is_anything_being_dragged();
+    let margin = if captured { CLICK_THROUGH_MARGIN } else { 0.0 };
+    busy || pointer.map_or(false, |pos| {
+        [
+            Vec2::ZERO,
+            vec2(margin, 0.0),
+            vec2(-margin, 0.0),
+            vec2(0.0, margin),
+            vec2(0.0, -margin),
+        ]
+        .iter()
+        .any(|offset| hit_test(ctx, pos + *offset))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Event, PointerButton, Pos2, Rect, TouchDeviceId, TouchId, TouchPhase};
+
+    use super::*;
+
+    /// The zoom egui sees in a frame with `events`, following the frames of `before`.
+    fn zoom_delta(before: Vec<Vec<Event>>, events: Vec<Event>) -> f32 {
+        let mut egui_skia = EguiSkia::new();
+        let mut zoom = 0.0;
+        for events in before.into_iter().chain([events]) {
+            let input = egui::RawInput {
+                events,
+                ..Default::default()
+            };
+            egui_skia.run(input, |ctx| zoom = ctx.input().zoom_delta());
+        }
+        zoom
+    }
+
+    fn touch(id: u64, phase: TouchPhase, x: f32) -> Event {
+        Event::Touch {
+            device_id: TouchDeviceId(0),
+            id: TouchId(id),
+            phase,
+            pos: Pos2::new(x, 50.0),
+            force: 0.0,
+        }
+    }
+
+    #[test]
+    fn touchscreen_pinches_zoom_once() {
+        let start = vec![
+            touch(0, TouchPhase::Start, 50.0),
+            touch(1, TouchPhase::Start, 70.0),
+        ];
+        let spread = vec![
+            touch(0, TouchPhase::Move, 40.0),
+            touch(1, TouchPhase::Move, 80.0),
+        ];
+        let zoom = zoom_delta(vec![start], spread);
+        assert!((zoom - 2.0).abs() < 1e-4, "zoom {}", zoom);
+    }
+
+    #[test]
+    fn touchpad_pinches_out_and_back_in_cancel_out() {
+        // egui-winit turns the ctrl + scroll events of Windows precision touchpads into zoom
+        // factors of `exp(delta / 200)`.
+        let events = vec![Event::Zoom(0.5f32.exp()), Event::Zoom((-0.5f32).exp())];
+        let zoom = zoom_delta(Vec::new(), events);
+        assert!((zoom - 1.0).abs() < 1e-6, "zoom {}", zoom);
+    }
+
+    #[test]
+    fn trackpad_magnify_gestures_zoom_by_the_exponential_of_their_delta() {
+        let mut gestures = Gestures::default();
+        gestures.magnify(0.25);
+        gestures.magnify(0.5);
+        let zoom = zoom_delta(Vec::new(), std::mem::take(&mut gestures.events));
+        assert!((zoom - 0.75f32.exp()).abs() < 1e-5, "zoom {}", zoom);
+
+        gestures.magnify(0.5);
+        gestures.magnify(-0.5);
+        let zoom = zoom_delta(Vec::new(), std::mem::take(&mut gestures.events));
+        assert!((zoom - 1.0).abs() < 1e-6, "zoom {}", zoom);
+    }
+
+    #[test]
+    fn smart_magnify_zooms_in_by_its_step_and_back_out() {
+        let mut gestures = Gestures {
+            smart_zoom: 1.5,
+            ..Default::default()
+        };
+        for expected in [1.5, 1.0 / 1.5, 1.5] {
+            gestures.smart_magnify();
+            let zoom = zoom_delta(Vec::new(), std::mem::take(&mut gestures.events));
+            assert!((zoom - expected).abs() < 1e-6, "zoom {}", zoom);
+        }
+    }
+
+    /// Runs a frame with an area holding a button at (100, 100) and returns the rect of the area.
+    fn overlay_frame(egui_skia: &mut EguiSkia, events: Vec<Event>) -> Rect {
+        let input = egui::RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(400.0, 300.0))),
+            events,
+            ..Default::default()
+        };
+        let mut rect = Rect::NOTHING;
+        egui_skia.run(input, |ctx| {
+            let area = egui::Area::new("overlay").fixed_pos(Pos2::new(100.0, 100.0));
+            let button = egui::Button::new("Close");
+            // The area is as large as its content, the response of the area itself only gets
+            // its size in the next frame.
+            rect = area
+                .show(ctx, |ui| ui.add_sized([100.0, 40.0], button))
+                .inner
+                .rect;
+        });
+        rect
+    }
+
+    #[test]
+    fn click_through_captures_the_pointer_over_areas_with_hysteresis() {
+        let mut egui_skia = EguiSkia::new();
+        let rect = overlay_frame(&mut egui_skia, Vec::new());
+        let ctx = &egui_skia.egui_ctx;
+        assert!(rect.width() >= 100.0 && rect.height() >= 40.0, "{:?}", rect);
+        // egui grows the hit rect of interactable areas by the resize grab radius.
+        let edge = rect.right() + ctx.style().interaction.resize_grab_radius_side;
+
+        let at = |x: f32| Some(Pos2::new(x, rect.center().y));
+        let captures = |pointer, captured| captures_pointer(ctx, &hits_area, pointer, captured);
+        assert!(captures(at(rect.center().x), false));
+        assert!(!captures(at(edge + 2.0), false));
+        assert!(!captures(Some(Pos2::new(10.0, 10.0)), false));
+        assert!(!captures(None, false));
+        // Once captured, the pointer has to leave the area by the margin.
+        assert!(captures(at(edge + 2.0), true));
+        assert!(!captures(at(edge + 10.0), true));
+
+        // A held button keeps the pointer captured anywhere, e.g. while dragging a slider out.
+        let far = Pos2::new(350.0, 250.0);
+        let press = vec![
+            Event::PointerMoved(far),
+            Event::PointerButton {
+                pos: far,
+                button: PointerButton::Primary,
+                pressed: true,
+                modifiers: Default::default(),
+            },
+        ];
+        overlay_frame(&mut egui_skia, press);
+        let ctx = &egui_skia.egui_ctx;
+        assert!(captures_pointer(ctx, &hits_area, Some(far), true));
+    }
+
+    /// Runs a frame like [`EguiSkiaWinit::run`] and returns whether the window should close.
+    fn close_frame(
+        egui_skia: &mut EguiSkia,
+        close_requested: bool,
+        ui: impl FnMut(&Context),
+    ) -> bool {
+        let ctx = egui_skia.egui_ctx.clone();
+        begin_close_frame(&ctx, close_requested);
+        egui_skia.run(egui::RawInput::default(), ui);
+        end_close_frame(&ctx)
+    }
+
+    #[test]
+    fn the_ui_can_veto_close_requests_and_close_the_window_itself() {
+        let mut egui_skia = EguiSkia::new();
+        let unsaved_changes = std::cell::Cell::new(true);
+        let asked = std::cell::Cell::new(0);
+        let app = |ctx: &Context| {
+            if EguiSkiaWinit::close_requested(ctx) {
+                asked.set(asked.get() + 1);
+                if unsaved_changes.get() {
+                    EguiSkiaWinit::cancel_close(ctx);
+                }
+            }
+        };
+
+        // The request of the OS reaches the ui in the next frame, which cancels it.
+        assert!(!close_frame(&mut egui_skia, true, app));
+        assert_eq!(asked.get(), 1);
+        // The request is only seen once, the loop keeps running.
+        assert!(!close_frame(&mut egui_skia, false, app));
+        assert_eq!(asked.get(), 1);
+
+        // Without unsaved changes the next request goes through.
+        unsaved_changes.set(false);
+        assert!(close_frame(&mut egui_skia, true, app));
+        assert_eq!(asked.get(), 2);
+
+        // The ui can close the window without a request, and can't cancel what wasn't requested.
+        let (close, cancel) = (EguiSkiaWinit::request_close, EguiSkiaWinit::cancel_close);
+        assert!(close_frame(&mut egui_skia, false, close));
+        assert!(!close_frame(&mut egui_skia, false, cancel));
+    }
+
+    /// Runs a frame of `ui` with the pointer at `pointer` and applies the resulting grab change
+    /// to `grab`, like [`EguiSkiaWinit::update_cursor_grab`] with a window that grabs fine.
+    fn grab_frame(
+        egui_skia: &mut EguiSkia,
+        grab: &mut Option<CursorGrab>,
+        pointer: Pos2,
+        focus_lost: bool,
+        ui: impl FnMut(&Context),
+    ) -> GrabChange {
+        let input = egui::RawInput {
+            events: vec![Event::PointerMoved(pointer)],
+            ..Default::default()
+        };
+        egui_skia.run(input, ui);
+        let change = cursor_grab_change(&egui_skia.egui_ctx, grab.as_ref(), focus_lost);
+        match change {
+            GrabChange::Grab { restore } => *grab = Some(CursorGrab { restore }),
+            GrabChange::Release { .. } => *grab = None,
+            GrabChange::Keep => {}
+        }
+        change
+    }
+
+    #[test]
+    fn cursor_grabs_follow_the_ui_and_are_released_on_focus_loss() {
+        let mut egui_skia = EguiSkia::new();
+        let ctx = egui_skia.egui_ctx.clone();
+        let mut grab = None;
+        let start = Pos2::new(10.0, 20.0);
+        let idle = |_: &Context| {};
+        let set_grab = |grab| move |ctx: &Context| EguiSkiaWinit::set_cursor_grab(ctx, grab);
+        let mut frame = |pointer, focus_lost, ui: &dyn Fn(&Context)| {
+            grab_frame(&mut egui_skia, &mut grab, pointer, focus_lost, ui)
+        };
+
+        assert_eq!(frame(start, false, &idle), GrabChange::Keep);
+        // The grab remembers where the cursor was to put it back there on release.
+        let grabbed = GrabChange::Grab {
+            restore: Some(start),
+        };
+        assert_eq!(frame(start, false, &set_grab(true)), grabbed);
+        // The request stays in effect without asking again every frame.
+        let moved = Pos2::new(50.0, 60.0);
+        assert_eq!(frame(moved, false, &idle), GrabChange::Keep);
+        let released = GrabChange::Release {
+            restore: Some(start),
+        };
+        assert_eq!(frame(moved, false, &set_grab(false)), released);
+        assert_eq!(frame(moved, false, &idle), GrabChange::Keep);
+
+        // Alt-tabbing away releases the grab, which stays released until the ui asks again.
+        assert_eq!(frame(start, false, &set_grab(true)), grabbed);
+        assert_eq!(frame(moved, true, &idle), released);
+        assert!(!EguiSkiaWinit::cursor_grabbed(&ctx));
+        assert_eq!(frame(moved, false, &idle), GrabChange::Keep);
+        assert_eq!(frame(start, false, &set_grab(true)), grabbed);
     }
 }
@@ -0,0 +1,130 @@
+//! Loading system fonts through skia's [`FontMgr`] into egui, so text in scripts the bundled
+//! egui fonts don't cover (CJK, emoji, ...) doesn't render as boxes.
+
+use egui::{Context, FontData, FontDefinitions, FontFamily};
+use skia_safe::{FontMgr, FontStyle};
+
+/// The system font families [`install_system_fonts`] looks for, in order of preference.
+///
+/// Families that aren't installed are skipped, so the defaults list common families of several
+/// platforms.
+#[derive(Clone, Debug)]
+pub struct SystemFontConfig {
+    /// Families tried first for proportional text.
+    pub proportional: Vec<String>,
+    /// Families tried first for monospace text.
+    pub monospace: Vec<String>,
+    /// Families tried after the above and egui's own fonts for both kinds of text, e.g. CJK and
+    /// emoji fonts.
+    pub fallbacks: Vec<String>,
+    /// The style matched within each family.
+    pub style: FontStyle,
+}
+
+impl Default for SystemFontConfig {
+    fn default() -> Self {
+        let families = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Self {
+            proportional: families(&["Segoe UI", "Helvetica Neue", "Noto Sans", "DejaVu Sans"]),
+            monospace: families(&["Consolas", "Menlo", "Noto Sans Mono", "DejaVu Sans Mono"]),
+            fallbacks: families(&[
+                "Noto Sans CJK SC",
+                "PingFang SC",
+                "Microsoft YaHei",
+                "Noto Color Emoji",
+                "Apple Color Emoji",
+                "Segoe UI Emoji",
+            ]),
+            style: FontStyle::normal(),
+        }
+    }
+}
+
+/// Installs the system fonts of `config` in `ctx`, in front of and behind egui's default fonts.
+///
+/// Proportional text uses the proportional families, then egui's fonts, then the fallbacks.
+/// Monospace text does the same with the monospace families. Fonts whose data skia can't read
+/// (e.g. some system font collections) are skipped with a warning.
+pub fn install_system_fonts(ctx: &Context, config: &SystemFontConfig) {
+    let font_mgr = FontMgr::new();
+    let mut fonts = FontDefinitions::default();
+
+    let mut load = |families: &[String]| -> Vec<String> {
+        let mut names = Vec::new();
+        for family in families {
+            let typeface = match font_mgr.match_family_style(family, config.style) {
+                Some(typeface) => typeface,
+                None => continue,
+            };
+            let (bytes, index) = match typeface.to_font_data() {
+                Some(data) => data,
+                None => {
+                    log::warn!("Skipping system font {}, its data can't be read", family);
+                    continue;
+                }
+            };
+            let name = format!("system: {}", family);
+            let mut font_data = FontData::from_owned(bytes);
+            font_data.index = index as u32;
+            fonts.font_data.insert(name.clone(), font_data);
+            names.push(name);
+        }
+        names
+    };
+    let proportional = load(&config.proportional);
+    let monospace = load(&config.monospace);
+    let fallbacks = load(&config.fallbacks);
+
+    for (family, first) in [
+        (FontFamily::Proportional, proportional),
+        (FontFamily::Monospace, monospace),
+    ] {
+        let names = fonts.families.entry(family).or_default();
+        names.splice(0..0, first);
+        names.extend(fallbacks.iter().cloned());
+    }
+
+    ctx.set_fonts(fonts);
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::FontId;
+
+    use super::*;
+
+    #[test]
+    fn han_text_lays_out_with_a_system_fallback() {
+        // Needs a system font covering Han characters, e.g. Noto Sans CJK through fontconfig.
+        let han = FontMgr::new().match_family_style_character(
+            "",
+            FontStyle::normal(),
+            &["zh"],
+            '漢' as i32,
+        );
+        let family = match han {
+            Some(typeface) => typeface.family_name(),
+            None => return,
+        };
+        let text = "漢字 and Latin";
+        let ctx = Context::default();
+        let _ = ctx.run(Default::default(), |_| {});
+        assert!(!ctx.fonts().has_glyphs(&FontId::proportional(14.0), "漢字"));
+
+        let config = SystemFontConfig {
+            fallbacks: vec![family],
+            ..Default::default()
+        };
+        install_system_fonts(&ctx, &config);
+        let _ = ctx.run(Default::default(), |_| {});
+
+        for font_id in [FontId::proportional(14.0), FontId::monospace(14.0)] {
+            assert!(ctx.fonts().has_glyphs(&font_id, text), "{:?}", font_id);
+            let galley = ctx
+                .fonts()
+                .layout_no_wrap(text.into(), font_id, egui::Color32::WHITE);
+            assert_eq!(galley.rows.len(), 1);
+            assert_eq!(galley.rows[0].glyphs.len(), text.chars().count());
+        }
+    }
+}
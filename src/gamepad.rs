@@ -0,0 +1,362 @@
+use std::time::{Duration, Instant};
+
+use egui::{Event, Key, Modifiers, RawInput, Vec2};
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+/// Configuration of a [`GamepadNavigator`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GamepadConfig {
+    /// How far the left stick has to be pushed, from 0 to 1, before it counts as a direction.
+    pub dead_zone: f32,
+    /// How long a direction is held before it starts repeating.
+    pub repeat_delay: Duration,
+    /// The time between the first repeats.
+    pub repeat_interval: Duration,
+    /// The repeat interval is multiplied by this after every repeat, down to
+    /// `min_repeat_interval`, so holding a direction scrolls through long lists faster.
+    pub repeat_acceleration: f32,
+    /// The shortest time between repeats.
+    pub min_repeat_interval: Duration,
+    /// Move the focus with `Tab` and `Shift+Tab` (down/right and up/left) instead of the arrow
+    /// keys. egui moves the keyboard focus with `Tab`, arrow keys only reach widgets that handle
+    /// them themselves, like sliders.
+    pub tab_navigation: bool,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.5,
+            repeat_delay: Duration::from_millis(400),
+            repeat_interval: Duration::from_millis(150),
+            repeat_acceleration: 0.85,
+            min_repeat_interval: Duration::from_millis(50),
+            tab_navigation: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+struct Held {
+    direction: Direction,
+    next: Instant,
+    interval: Duration,
+}
+
+/// Maps gamepad input to the key events egui uses for keyboard navigation.
+///
+/// The d-pad and the left stick move the focus, holding them repeats with acceleration. The south
+/// face button (A on Xbox pads) presses `Enter`, the east one (B) `Escape`.
+///
+/// Call [`Self::push_events`] with the input of every frame, or set
+/// `EguiSkiaWinit::gamepad` to let the winit integration do it. gilrs events don't wake an event
+/// loop, so the loop has to run while a gamepad is in use, e.g. with `ControlFlow::Poll` or a
+/// timer, for the events and repeats to arrive in time.
+pub struct GamepadNavigator {
+    gilrs: Gilrs,
+    navigation: Navigation,
+}
+
+impl GamepadNavigator {
+    pub fn new() -> Result<Self, gilrs::Error> {
+        Ok(Self {
+            gilrs: Gilrs::new()?,
+            navigation: Navigation::new(GamepadConfig::default()),
+        })
+    }
+
+    pub fn with_config(mut self, config: GamepadConfig) -> Self {
+        self.navigation.config = config;
+        self
+    }
+
+    /// Polls gilrs and appends the resulting key events to `raw_input`.
+    pub fn push_events(&mut self, raw_input: &mut RawInput) {
+        raw_input.events.extend(self.poll(Instant::now()));
+    }
+
+    /// Polls gilrs and returns the resulting key events, including the repeats due at `now`.
+    pub fn poll(&mut self, now: Instant) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            events.extend(self.handle_event(&event.event, now));
+        }
+        self.navigation.repeat(now, &mut events);
+        events
+    }
+
+    /// Handles a single gilrs event that happened at `now` and returns the resulting key events.
+    ///
+    /// [`Self::poll`] calls this for the events of gilrs, call it directly to feed events from
+    /// elsewhere.
+    pub fn handle_event(&mut self, event: &EventType, now: Instant) -> Vec<Event> {
+        let navigation = &mut self.navigation;
+        match *event {
+            EventType::ButtonPressed(button, _) => navigation.button(button, true, now),
+            EventType::ButtonReleased(button, _) => navigation.button(button, false, now),
+            EventType::AxisChanged(axis, value, _) => navigation.axis(axis, value, now),
+            EventType::Disconnected => navigation.disconnected(now),
+            _ => navigation.input(now, Vec::new()),
+        }
+    }
+}
+
+/// The navigation state of a [`GamepadNavigator`], apart from gilrs.
+struct Navigation {
+    config: GamepadConfig,
+    /// The held d-pad directions, the last pressed one is used.
+    dpad: Vec<Direction>,
+    stick: Vec2,
+    held: Option<Held>,
+}
+
+impl Navigation {
+    fn new(config: GamepadConfig) -> Self {
+        Self {
+            config,
+            dpad: Vec::new(),
+            stick: Vec2::ZERO,
+            held: None,
+        }
+    }
+
+    fn button(&mut self, button: Button, pressed: bool, now: Instant) -> Vec<Event> {
+        let mut events = Vec::new();
+        match dpad_direction(button) {
+            Some(direction) => {
+                self.dpad.retain(|&held| held != direction);
+                if pressed {
+                    self.dpad.push(direction);
+                }
+            }
+            None => {
+                if let Some(key) = button_key(button) {
+                    events.push(key_event(key, pressed, Modifiers::default()));
+                }
+            }
+        }
+        self.input(now, events)
+    }
+
+    fn axis(&mut self, axis: Axis, value: f32, now: Instant) -> Vec<Event> {
+        match axis {
+            Axis::LeftStickX => self.stick.x = value,
+            Axis::LeftStickY => self.stick.y = value,
+            _ => {}
+        }
+        self.input(now, Vec::new())
+    }
+
+    fn disconnected(&mut self, now: Instant) -> Vec<Event> {
+        self.dpad.clear();
+        self.stick = Vec2::ZERO;
+        self.input(now, Vec::new())
+    }
+
+    /// Appends the step of a direction that started being held with the last input to `events`.
+    fn input(&mut self, now: Instant, mut events: Vec<Event>) -> Vec<Event> {
+        self.repeat(now, &mut events);
+        events
+    }
+    /// The direction currently held, the d-pad wins over the stick.
+    fn direction(&self) -> Option<Direction> {
+        if let Some(&direction) = self.dpad.last() {
+            return Some(direction);
+        }
+        let stick = self.stick;
+        if stick.length() < self.config.dead_zone {
+            None
+        } else if stick.x.abs() > stick.y.abs() {
+            Some(if stick.x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            })
+        } else {
+            // The y axis of gilrs points up.
+            Some(if stick.y > 0.0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        }
+    }
+
+    /// Emits a step when a direction starts being held or its next repeat is due.
+    fn repeat(&mut self, now: Instant, events: &mut Vec<Event>) {
+        let direction = match self.direction() {
+            Some(direction) => direction,
+            None => {
+                self.held = None;
+                return;
+            }
+        };
+        match &mut self.held {
+            Some(held) if held.direction == direction => {
+                if now < held.next {
+                    return;
+                }
+                // A late poll emits a single step instead of catching up on the missed ones.
+                held.next = now + held.interval;
+                held.interval = held
+                    .interval
+                    .mul_f32(self.config.repeat_acceleration)
+                    .max(self.config.min_repeat_interval);
+            }
+            _ => {
+                self.held = Some(Held {
+                    direction,
+                    next: now + self.config.repeat_delay,
+                    interval: self.config.repeat_interval,
+                });
+            }
+        }
+        self.step(direction, events);
+    }
+
+    fn step(&self, direction: Direction, events: &mut Vec<Event>) {
+        let (key, shift) = match (self.config.tab_navigation, direction) {
+            (true, Direction::Down | Direction::Right) => (Key::Tab, false),
+            (true, Direction::Up | Direction::Left) => (Key::Tab, true),
+            (false, Direction::Up) => (Key::ArrowUp, false),
+            (false, Direction::Down) => (Key::ArrowDown, false),
+            (false, Direction::Left) => (Key::ArrowLeft, false),
+            (false, Direction::Right) => (Key::ArrowRight, false),
+        };
+        let modifiers = Modifiers {
+            shift,
+            ..Default::default()
+        };
+        events.push(key_event(key, true, modifiers));
+        events.push(key_event(key, false, modifiers));
+    }
+}
+
+fn dpad_direction(button: Button) -> Option<Direction> {
+    match button {
+        Button::DPadUp => Some(Direction::Up),
+        Button::DPadDown => Some(Direction::Down),
+        Button::DPadLeft => Some(Direction::Left),
+        Button::DPadRight => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn button_key(button: Button) -> Option<Key> {
+    match button {
+        Button::South => Some(Key::Enter),
+        Button::East => Some(Key::Escape),
+        _ => None,
+    }
+}
+
+fn key_event(key: Key, pressed: bool, modifiers: Modifiers) -> Event {
+    Event::Key {
+        key,
+        pressed,
+        modifiers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(shift: bool) -> Vec<Event> {
+        let modifiers = Modifiers {
+            shift,
+            ..Default::default()
+        };
+        vec![
+            key_event(Key::Tab, true, modifiers),
+            key_event(Key::Tab, false, modifiers),
+        ]
+    }
+
+    #[test]
+    fn held_directions_repeat_with_acceleration() {
+        let mut navigation = Navigation::new(GamepadConfig::default());
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+        let polls_with_steps = |navigation: &mut Navigation, times: &[u64]| {
+            let mut steps = Vec::new();
+            for &millis in times {
+                let mut events = Vec::new();
+                navigation.repeat(at(millis), &mut events);
+                if !events.is_empty() {
+                    assert_eq!(events, tab(false));
+                    steps.push(millis);
+                }
+            }
+            steps
+        };
+
+        assert_eq!(navigation.button(Button::DPadDown, true, at(0)), tab(false));
+        // Polled every 10 ms: the delay, then 150 ms, 127.5 ms, 108.4 ms, ... each counted from
+        // the poll that emitted the previous step.
+        let times: Vec<u64> = (1..=100).map(|i| i * 10).collect();
+        let steps = polls_with_steps(&mut navigation, &times);
+        assert_eq!(steps, [400, 550, 680, 790, 890, 970]);
+
+        let released = navigation.button(Button::DPadDown, false, at(1000));
+        assert!(released.is_empty());
+        assert!(polls_with_steps(&mut navigation, &[1500]).is_empty());
+    }
+
+    #[test]
+    fn the_stick_counts_beyond_the_dead_zone() {
+        let mut navigation = Navigation::new(GamepadConfig::default());
+        let now = Instant::now();
+        assert!(navigation.axis(Axis::LeftStickY, 0.3, now).is_empty());
+        // The y axis points up, so a stick pushed up focuses the previous widget.
+        assert_eq!(navigation.axis(Axis::LeftStickY, 0.8, now), tab(true));
+        assert!(navigation.axis(Axis::LeftStickY, 0.0, now).is_empty());
+        assert_eq!(navigation.axis(Axis::LeftStickX, 0.9, now), tab(false));
+    }
+
+    #[test]
+    fn face_buttons_press_enter_and_escape() {
+        let mut navigation = Navigation::new(GamepadConfig::default());
+        let now = Instant::now();
+        let none = Modifiers::default();
+        let enter = navigation.button(Button::South, true, now);
+        assert_eq!(enter, [key_event(Key::Enter, true, none)]);
+        let enter = navigation.button(Button::South, false, now);
+        assert_eq!(enter, [key_event(Key::Enter, false, none)]);
+        let escape = navigation.button(Button::East, true, now);
+        assert_eq!(escape, [key_event(Key::Escape, true, none)]);
+    }
+
+    #[test]
+    fn arrow_navigation_uses_the_arrow_keys() {
+        let config = GamepadConfig {
+            tab_navigation: false,
+            ..Default::default()
+        };
+        let mut navigation = Navigation::new(config);
+        let now = Instant::now();
+        let none = Modifiers::default();
+        assert_eq!(
+            navigation.button(Button::DPadLeft, true, now),
+            [
+                key_event(Key::ArrowLeft, true, none),
+                key_event(Key::ArrowLeft, false, none),
+            ]
+        );
+        // The last pressed direction wins while both are held.
+        assert_eq!(
+            navigation.button(Button::DPadUp, true, now),
+            [
+                key_event(Key::ArrowUp, true, none),
+                key_event(Key::ArrowUp, false, none),
+            ]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! The GPU operations of the painter and the texture store, behind a trait so skia GPU backends
+//! other than Ganesh (`DirectContext`) can be added next to it.
+//!
+//! skia's Graphite backend is not exposed by the skia-safe versions this crate supports, so
+//! Ganesh is the only implementation for now. A Graphite implementation would upload images
+//! through its `Recorder` and submit the recordings of `flush_work` and `submit_work` to its
+//! `Context`; the helpers creating render targets from a `DirectContext` (e.g.
+//! [`crate::GpuCache`]) would need the same split.
+
+use skia_safe::gpu::{DirectContext, FlushInfo, Mipmapped};
+use skia_safe::Image;
+
+pub(crate) trait GpuBackend {
+    /// Creates a GPU texture with the content of the raster `image`.
+    fn upload_image(&mut self, image: &Image) -> Option<Image>;
+
+    /// The largest width and height of a texture the GPU supports.
+    fn max_texture_side(&self) -> usize;
+
+    /// Hands the recorded work to the GPU driver without submitting it.
+    fn flush_work(&mut self);
+
+    /// Flushes and submits the recorded work, and waits until the GPU finished it if `wait`.
+    fn submit_work(&mut self, wait: bool);
+}
+
+impl GpuBackend for DirectContext {
+    fn upload_image(&mut self, image: &Image) -> Option<Image> {
+        image.new_texture_image(self, Mipmapped::Yes)
+    }
+
+    fn max_texture_side(&self) -> usize {
+        self.max_texture_size().max(0) as usize
+    }
+
+    fn flush_work(&mut self) {
+        self.flush(&FlushInfo::default());
+    }
+
+    fn submit_work(&mut self, wait: bool) {
+        if wait {
+            self.flush_submit_and_sync_cpu();
+        } else {
+            self.flush_and_submit();
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use skia_safe::{
+    Canvas, Data, EncodedImageFormat, FilterMode, Image, ImageInfo, MipmapMode, Rect,
+    SamplingOptions, Surface,
+};
+
+use crate::stats::FrameStats;
+
+/// A frame kept by [`crate::EguiSkia::enable_frame_history`].
+#[derive(Clone)]
+pub struct HistoricalFrame {
+    /// The number of the frame, counting every frame painted since the history was enabled.
+    pub frame: u64,
+    /// The egui time the frame was run at, in seconds.
+    pub time: f64,
+    /// The painted frame, downscaled by the history scale.
+    pub image: Image,
+    pub stats: FrameStats,
+}
+
+/// The last painted frames, oldest first.
+pub(crate) struct FrameHistory {
+    capacity: usize,
+    scale: f32,
+    next_frame: u64,
+    pub(crate) frames: Vec<HistoricalFrame>,
+    /// The full size pixels of the last frame, kept to not allocate them every frame.
+    pixels: Vec<u8>,
+}
+
+impl FrameHistory {
+    pub(crate) fn new(capacity: usize, scale: f32) -> Self {
+        Self {
+            capacity,
+            scale,
+            next_frame: 0,
+            frames: Vec::with_capacity(capacity),
+            pixels: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.frames.len().saturating_sub(capacity);
+        self.frames.drain(..excess);
+    }
+
+    pub(crate) fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Reads back the frame painted onto `canvas` and keeps a downscaled copy, dropping the
+    /// oldest frame if the history is full.
+    ///
+    /// Frames that can't be read back, e.g. from canvases without pixels like picture
+    /// recorders, are skipped but still counted.
+    pub(crate) fn capture(&mut self, canvas: &mut Canvas, time: f64, stats: FrameStats) {
+        let frame = self.next_frame;
+        self.next_frame += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        let image = match self.read_back(canvas) {
+            Some(image) => image,
+            None => return,
+        };
+        if self.frames.len() == self.capacity {
+            self.frames.remove(0);
+        }
+        self.frames.push(HistoricalFrame {
+            frame,
+            time,
+            image,
+            stats,
+        });
+    }
+
+    fn read_back(&mut self, canvas: &mut Canvas) -> Option<Image> {
+        let size = canvas.base_layer_size();
+        if size.is_empty() {
+            return None;
+        }
+        let info = ImageInfo::new_n32_premul(size, None);
+        let row_bytes = info.min_row_bytes();
+        self.pixels.resize(row_bytes * size.height as usize, 0);
+        if !canvas.read_pixels(&info, &mut self.pixels, row_bytes, (0, 0)) {
+            return None;
+        }
+        let full = Image::from_raster_data(&info, Data::new_copy(&self.pixels), row_bytes)?;
+
+        let width = ((size.width as f32 * self.scale).round() as i32).max(1);
+        let height = ((size.height as f32 * self.scale).round() as i32).max(1);
+        let mut surface = Surface::new_raster_n32_premul((width, height))?;
+        surface.canvas().draw_image_rect_with_sampling_options(
+            &full,
+            None,
+            Rect::from_iwh(width, height),
+            SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear),
+            &Default::default(),
+        );
+        Some(surface.image_snapshot())
+    }
+}
+
+/// Writes `frames` into `dir` as `frame_<number>.png`.
+pub(crate) fn dump_frames(frames: &[HistoricalFrame], dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for frame in frames {
+        let data = frame
+            .image
+            .encode_to_data(EncodedImageFormat::PNG)
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::Other, "failed to encode frame")
+            })?;
+        std::fs::write(dir.join(format!("frame_{:06}.png", frame.frame)), data.as_bytes())?;
+    }
+    Ok(())
+}
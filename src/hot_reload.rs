@@ -0,0 +1,181 @@
+//! Reloading shaders when their SKSL files change, see
+//! [`Painter::set_texture_effect_file`](crate::Painter::set_texture_effect_file) and
+//! [`Painter::set_post_process_file`](crate::Painter::set_post_process_file).
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use egui::epaint::ahash::AHashSet;
+use egui::TextureId;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::textures::TextureError;
+
+/// What a watched shader file is loaded into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ShaderTarget {
+    Texture(TextureId),
+    PostProcess,
+}
+
+pub(crate) struct ShaderWatcher {
+    watcher: RecommendedWatcher,
+    /// The files changed since the last [`Self::take_changed`], filled by the watcher thread.
+    changed: Arc<Mutex<AHashSet<PathBuf>>>,
+    files: Vec<(PathBuf, ShaderTarget)>,
+    /// The reloads that failed since the last [`crate::Painter::take_shader_errors`].
+    pub(crate) errors: Vec<(PathBuf, TextureError)>,
+}
+
+impl ShaderWatcher {
+    pub(crate) fn new() -> Result<Self, TextureError> {
+        let changed = Arc::new(Mutex::new(AHashSet::new()));
+        let sender = changed.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if event.kind.is_create() || event.kind.is_modify() {
+                    sender.lock().unwrap().extend(event.paths);
+                }
+            }
+        })
+        .map_err(watch_error)?;
+        Ok(Self {
+            watcher,
+            changed,
+            files: Vec::new(),
+            errors: Vec::new(),
+        })
+    }
+
+    /// Watches `path` for changes of the shader of `target`, replacing the previous file of the
+    /// target.
+    ///
+    /// Editors often save by replacing the file, which ends watches on the file itself, so the
+    /// directory of the file is watched instead.
+    pub(crate) fn watch(&mut self, path: &Path, target: ShaderTarget) -> Result<(), TextureError> {
+        let path = path.canonicalize()?;
+        if let Some(dir) = path.parent() {
+            self.watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(watch_error)?;
+        }
+        self.files.retain(|(_, other)| *other != target);
+        self.files.push((path, target));
+        Ok(())
+    }
+
+    /// The watched files changed since the last call, with their targets.
+    pub(crate) fn take_changed(&mut self) -> Vec<(PathBuf, ShaderTarget)> {
+        let changed = std::mem::take(&mut *self.changed.lock().unwrap());
+        self.files
+            .iter()
+            .filter(|(path, _)| changed.contains(path))
+            .cloned()
+            .collect()
+    }
+}
+
+fn watch_error(err: notify::Error) -> TextureError {
+    TextureError::Io(std::io::Error::new(std::io::ErrorKind::Other, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use egui::{Color32, ColorImage, ImageData, TextureOptions, TexturesDelta};
+    use skia_safe::Color;
+
+    use super::*;
+    use crate::test_util::{assert_color_near, mesh_primitive, painter, pixel, surface};
+    use crate::Painter;
+
+    const PASSTHROUGH: &str =
+        "uniform shader image; half4 main(float2 p) { return image.eval(p); }";
+    const GREEN: &str = "uniform shader image; half4 main(float2 p) { return half4(0, 1, 0, 1); }";
+
+    /// Paints a frame with a quad of texture `id` and returns the color in its center.
+    fn paint(painter: &mut Painter, id: TextureId) -> Color {
+        let mut mesh = egui::epaint::Mesh::with_texture(id);
+        let rect = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(10.0, 10.0));
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        let primitives = vec![mesh_primitive(egui::Rect::EVERYTHING, mesh)];
+        let mut surface = surface(10, 10);
+        painter.paint_and_update_textures(
+            surface.canvas(),
+            1.0,
+            primitives,
+            TexturesDelta::default(),
+        );
+        pixel(&mut surface, 5, 5)
+    }
+
+    /// Paints frames until `done` holds for the painted color, for at most five seconds.
+    fn paint_until(
+        painter: &mut Painter,
+        id: TextureId,
+        done: impl Fn(&mut Painter, Color) -> bool,
+    ) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            let color = paint(painter, id);
+            if done(painter, color) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        panic!("the shader file wasn't reloaded");
+    }
+
+    #[test]
+    fn changed_shader_files_are_picked_up_by_the_next_frame() {
+        let path = std::env::temp_dir().join(format!("egui_skia_{}.sksl", std::process::id()));
+        std::fs::write(&path, PASSTHROUGH).unwrap();
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let image = ImageData::Color(ColorImage::new([1, 1], Color32::RED));
+        painter
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        painter.set_texture_effect_file(id, &path).unwrap();
+        assert_color_near(paint(&mut painter, id), Color::RED, 0);
+
+        std::fs::write(&path, GREEN).unwrap();
+        paint_until(&mut painter, id, |_, color| color == Color::GREEN);
+        // Reloads of the half written file may have failed on the way.
+        painter.take_shader_errors();
+
+        // A broken shader keeps the last working one and reports the error.
+        std::fs::write(&path, "half4 main(").unwrap();
+        paint_until(&mut painter, id, |painter, color| {
+            assert_color_near(color, Color::GREEN, 0);
+            !painter.take_shader_errors().is_empty()
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn changed_post_process_files_are_picked_up_by_the_next_frame() {
+        let name = format!("egui_skia_post_{}.sksl", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, "half4 main(half4 color) { return color; }").unwrap();
+        let mut painter = painter();
+        painter.set_post_process_file(&path).unwrap();
+        // The quad samples the white default texture of the test painter.
+        assert_color_near(paint(&mut painter, TextureId::default()), Color::WHITE, 0);
+
+        let green = "half4 main(half4 color) { return half4(0, color.a, 0, color.a); }";
+        std::fs::write(&path, green).unwrap();
+        let id = TextureId::default();
+        paint_until(&mut painter, id, |_, color| color == Color::GREEN);
+        painter.take_shader_errors();
+
+        std::fs::write(&path, "half4 main(").unwrap();
+        paint_until(&mut painter, id, |painter, color| {
+            assert_color_near(color, Color::GREEN, 0);
+            !painter.take_shader_errors().is_empty()
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use egui::epaint::ahash::AHashMap;
+use egui::epaint::{ClippedPrimitive, Primitive};
+use egui::{Context, Id, LayerId, Order, PaintCallback};
+use skia_safe::Canvas;
+
+use crate::callback::EguiSkiaPaintCallback;
+
+pub(crate) type LayerDraw = Arc<dyn Fn(&mut Canvas) + Send + Sync>;
+
+/// Marks the end of an egui [`Order`] in the shapes of a frame.
+///
+/// egui doesn't tell which layer a shape came from, so markers are added to the frame before it
+/// ends: one on top of the areas of the order and, for orders that have one, one at the end of the
+/// built-in non-area layer. The last marker of an order in the tessellated frame is where the
+/// order ends.
+struct LayerMarker(Order);
+
+/// Adds the markers for `orders` to the current frame. Call this after the ui of the frame ran.
+pub(crate) fn add_layer_markers(ctx: &Context, orders: impl Iterator<Item = Order>) {
+    let mut added = Vec::new();
+    for order in orders {
+        if added.contains(&order) {
+            continue;
+        }
+        added.push(order);
+
+        let marker = PaintCallback {
+            rect: ctx.screen_rect(),
+            callback: Arc::new(LayerMarker(order)),
+        };
+        let layer = LayerId::new(order, Id::new("egui_skia_layer_marker"));
+        ctx.move_to_top(layer);
+        ctx.layer_painter(layer).add(marker.clone());
+        match order {
+            Order::Background => {
+                ctx.layer_painter(LayerId::background()).add(marker);
+            }
+            Order::Debug => {
+                ctx.layer_painter(LayerId::debug()).add(marker);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces the layer markers in `primitives` by the draws registered for their order, in
+/// registration order, and drops the primitives of the `hidden` orders. Draws get a full screen
+/// callback, so they are painted with the dpi matrix applied and nothing but the screen clipped.
+///
+/// Hiding an order needs the markers of that order and of the order below it.
+pub(crate) fn resolve_layer_markers(
+    primitives: Vec<ClippedPrimitive>,
+    draws: &[(Order, LayerDraw)],
+    hidden: &[Order],
+) -> Vec<ClippedPrimitive> {
+    let mut ends = AHashMap::default();
+    for (i, primitive) in primitives.iter().enumerate() {
+        if let Some(order) = marker_order(primitive) {
+            ends.insert(order, i);
+        }
+    }
+    // The order of the primitive at `i`: the first order that doesn't end before it. Orders
+    // without markers are skipped, so this is only exact for orders with markers below them.
+    let order_at = |i: usize| {
+        Order::ALL
+            .iter()
+            .copied()
+            .find(|order| ends.get(order).map_or(false, |&end| end >= i))
+            .unwrap_or(Order::Debug)
+    };
+
+    let mut resolved = Vec::with_capacity(primitives.len());
+    for (i, primitive) in primitives.into_iter().enumerate() {
+        let order = match marker_order(&primitive) {
+            Some(order) => order,
+            None => {
+                if !hidden.contains(&order_at(i)) {
+                    resolved.push(primitive);
+                }
+                continue;
+            }
+        };
+        if ends.get(&order) != Some(&i) {
+            continue;
+        }
+        let rect = match &primitive.primitive {
+            Primitive::Callback(callback) => callback.rect,
+            Primitive::Mesh(_) => unreachable!(),
+        };
+        for (_, draw) in draws.iter().filter(|(after, _)| *after == order) {
+            let draw = draw.clone();
+            resolved.push(ClippedPrimitive {
+                clip_rect: primitive.clip_rect,
+                primitive: Primitive::Callback(PaintCallback {
+                    rect,
+                    callback: Arc::new(EguiSkiaPaintCallback::new(move |canvas| draw(canvas))),
+                }),
+            });
+        }
+    }
+    resolved
+}
+
+/// Whether `callback` is a layer marker rather than content.
+pub(crate) fn is_layer_marker(callback: &PaintCallback) -> bool {
+    callback.callback.downcast_ref::<LayerMarker>().is_some()
+}
+
+fn marker_order(primitive: &ClippedPrimitive) -> Option<Order> {
+    match &primitive.primitive {
+        Primitive::Callback(callback) => callback
+            .callback
+            .downcast_ref::<LayerMarker>()
+            .map(|marker| marker.0),
+        Primitive::Mesh(_) => None,
+    }
+}
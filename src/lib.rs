@@ -1,12 +1,75 @@
 extern crate core;
 
+mod atlas;
+mod batching;
+mod cached_canvas;
+mod callback;
+mod caps;
+#[cfg(feature = "clipboard-image")]
+mod clipboard_image;
+pub mod color;
+mod compositor;
+mod egui_painter;
 mod egui_skia;
+pub mod fonts;
+#[cfg(feature = "gilrs")]
+mod gamepad;
+#[cfg(feature = "gpu")]
+mod gpu_backend;
+mod history;
+#[cfg(feature = "shader-hot-reload")]
+mod hot_reload;
+mod layers;
 mod painter;
+mod pipeline;
+mod platform;
+#[cfg(feature = "recording")]
+pub mod recording;
+#[cfg(feature = "remote")]
+pub mod remote;
+mod screenshot;
+mod shader_widget;
+mod shadows;
+mod stats;
+pub mod testing;
+#[cfg(test)]
+mod test_util;
+mod textures;
+mod video;
 
 #[cfg(feature = "winit")]
 mod egui_skia_winit;
 #[cfg(feature = "winit")]
-pub use egui_skia_winit::EguiSkiaWinit;
+pub use egui_skia_winit::{surface_props_for_window, EguiSkiaWinit};
 
+pub use cached_canvas::CachedCanvas;
+#[cfg(feature = "gpu")]
+pub use callback::GpuCache;
+pub use callback::{
+    stroke_path_uniform, CallbackInfo, CallbackResponse, EguiSkiaPaintCallback, Fit, PictureFit,
+};
+pub use caps::PainterCaps;
+#[cfg(feature = "clipboard-image")]
+pub use clipboard_image::clipboard_color_image;
+pub use compositor::{CompositeFrame, Compositor};
+pub use egui_painter::{EguiPainter, PaintTarget};
 pub use egui_skia::*;
-pub use painter::EguiSkiaPaintCallback;
+#[cfg(feature = "gilrs")]
+pub use gamepad::{GamepadConfig, GamepadNavigator};
+pub use history::HistoricalFrame;
+#[cfg(feature = "gpu")]
+pub use painter::FlushSync;
+pub use painter::{
+    CallbackExport, ContentOrigin, CvdType, DrawCallback, PaintHooks, Painter, QualityTier,
+    RenderError,
+};
+pub use pipeline::{FrameData, PipelinedRenderer};
+pub use platform::{argb_pixels, OutputOptions, Platform};
+pub use screenshot::ScreenshotOptions;
+pub use shader_widget::{ShaderUniform, ShaderWidget, SkiaShaderExt};
+pub use shadows::ShadowRegion;
+pub use stats::FrameStats;
+pub use textures::{
+    TextureCacheStats, TextureColorUsage, TextureError, TextureStats, TextureStore,
+};
+pub use video::{YuvColorSpace, YuvPlanes};
@@ -1,39 +1,758 @@
-use std::ops::Deref;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use egui::epaint::ahash::AHashMap;
-use egui::epaint::Primitive;
-use egui::{ClippedPrimitive, ImageData, Pos2, TextureFilter, TextureId, TexturesDelta};
-use skia_safe::runtime_effect::ChildPtr;
+use egui::epaint::ahash::AHashSet;
+use egui::epaint::{Mesh, Primitive, Vertex};
+use egui::epaint::ImageDelta;
+use egui::{
+    ClippedPrimitive, ImageData, Pos2, TextureFilter, TextureId, TextureOptions, TexturesDelta,
+};
+use skia_safe::canvas::SaveLayerRec;
 use skia_safe::vertices::VertexMode;
 use skia_safe::{
-    scalar, BlendMode, Canvas, ClipOp, Color, ColorSpace, ConditionallySend, Data, Drawable, Image,
-    ImageInfo, Paint, PictureRecorder, Point, Rect, RuntimeEffect, Sendable, Surface, Vertices,
+    color_filters, gradient_shader, image_filters, BlendMode, Canvas, ClipOp, Color, ColorFilter,
+    AlphaType, ColorMatrix, ColorSpace, ColorType, ConditionallySend, Data, EncodedImageFormat,
+    FilterMode, IRect, Image, ImageInfo, MipmapMode, Paint, Path, Picture, PictureRecorder, Point,
+    Rect, RuntimeEffect, SamplingOptions, Sendable, Surface, TileMode, Vertices,
+};
+
+use crate::batching::{batch_primitives, merge_primitives};
+use crate::callback::{CallbackContent, CallbackInfo, CallbackResponse, EguiSkiaPaintCallback};
+use crate::caps::PainterCaps;
+use crate::color::color32_to_skia_unmultiplied;
+#[cfg(feature = "gpu")]
+use crate::gpu_backend::GpuBackend;
+#[cfg(feature = "shader-hot-reload")]
+use crate::hot_reload::{ShaderTarget, ShaderWatcher};
+use crate::platform::{OutputOptions, Platform};
+use crate::shadows::ShadowRegion;
+use crate::stats::FrameStats;
+use crate::textures::{
+    TextureCacheStats, TextureColorUsage, TextureError, TextureStore, TextureTile, TILE_MODE,
 };
+#[cfg(feature = "gpu")]
+use crate::video::yuv_to_texture;
+use crate::video::{yuv_to_image, YuvColorSpace, YuvPlanes};
+
+/// Draws the content of a paint callback, passed to [`PaintHooks::around_callback`].
+pub type DrawCallback<'a> = Box<dyn FnOnce(&mut Canvas) + 'a>;
 
-struct PaintHandle {
-    paint: Paint,
-    image: Image,
+/// Closures the [`Painter`] runs at fixed points of a frame, see [`Painter::set_hooks`].
+///
+/// Every hook runs between a save and a restore of the canvas, so changes it makes to the
+/// matrix or clip don't leak into the following primitives.
+#[derive(Default)]
+pub struct PaintHooks {
+    /// Runs before the first primitive is drawn, e.g. to draw the app background.
+    pub before_frame: Option<Box<dyn FnMut(&mut Canvas) + Send>>,
+    /// Runs after the last primitive is drawn, e.g. to draw a vignette over the ui.
+    pub after_frame: Option<Box<dyn FnMut(&mut Canvas) + Send>>,
+    /// Runs instead of drawing a paint callback, with the callback rect in points and the
+    /// function drawing its content. The hook is responsible for calling it.
+    ///
+    /// The canvas is already scaled, clipped and translated for the callback.
+    pub around_callback: Option<Box<dyn FnMut(&mut Canvas, egui::Rect, DrawCallback<'_>) + Send>>,
 }
 
+/// Draws egui primitives onto skia canvases.
+///
+/// Painting only reads the painter and its textures, so one prepared frame can be painted onto
+/// several canvases from several threads at once (e.g. the screen and a streaming surface). The
+/// texture updates need exclusive access and happen in between. Hooks are called under a lock,
+/// so they don't run concurrently.
 pub struct Painter {
-    paints: AHashMap<TextureId, PaintHandle>,
+    textures: Arc<RwLock<TextureStore>>,
+    hooks: Mutex<PaintHooks>,
+    max_draw_calls: Option<usize>,
+    stats: Mutex<FrameStats>,
+    /// The textures drawn since the last [`Self::free_textures`], which marks them as used on the
+    /// store.
+    used_textures: Mutex<Vec<TextureId>>,
+    saturation: f32,
+    cvd_simulation: Option<CvdType>,
+    /// The color filter of [`Self::set_post_process_effect`].
+    post_process: Option<ColorFilter>,
+    output: OutputOptions,
+    text_outline: Option<(egui::Color32, f32)>,
+    forced_filter: Option<TextureFilter>,
+    quality: QualityTier,
+    msaa_coverage: bool,
+    content_origin: ContentOrigin,
+    clip_expand: f32,
+    geometry_snap: bool,
+    viewport_culling: bool,
+    max_texture_side: Option<usize>,
+    dithering: bool,
+    callback_export: CallbackExport,
+    shadow_regions: Vec<ShadowRegion>,
+    debug_annotations: bool,
+    animation_time: f64,
+    callback_repaint_after: Mutex<Option<Duration>>,
+    #[cfg(feature = "gpu")]
+    context: Mutex<Option<skia_safe::gpu::DirectContext>>,
+    #[cfg(feature = "gpu")]
+    needs_flush: AtomicBool,
+    #[cfg(feature = "gpu")]
+    flush_interval: Option<usize>,
+    #[cfg(feature = "shader-hot-reload")]
+    shader_watcher: Option<ShaderWatcher>,
+}
+
+/// The point subtracted from all coordinates of a frame before they reach skia, see
+/// [`Painter::set_content_origin`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ContentOrigin {
+    /// Pass the coordinates as they are.
+    #[default]
+    Zero,
+    /// Use the top left corner of the clip rect of the first primitive of the frame.
+    FirstClipRect,
+    /// Use the given point, in points.
+    Point(Pos2),
+}
+
+/// Bundles the settings trading quality for speed, see [`Painter::with_quality`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QualityTier {
+    /// Aliased clips and nearest neighbour sampling without mipmaps, e.g. for thumbnails.
+    Low,
+    /// Anti aliased clips and the sampling each texture asks for.
+    #[default]
+    Medium,
+    /// Like [`Self::Medium`], with cubic sampling of linearly filtered textures and blending in
+    /// linear light.
+    High,
+}
+
+/// A color vision deficiency simulated by [`Painter::with_cvd_simulation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CvdType {
+    /// No working red cones.
+    Protanopia,
+    /// No working green cones, the most common form.
+    Deuteranopia,
+    /// No working blue cones.
+    Tritanopia,
+}
+
+impl CvdType {
+    /// The simulation matrix of Machado et al. (2009) at full severity, for linear RGB.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            CvdType::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            CvdType::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            CvdType::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// Applies the matrix in linear RGB, wrapped in the conversions from and to sRGB.
+    fn color_filter(self) -> Option<ColorFilter> {
+        let [r, g, b] = self.matrix();
+        #[rustfmt::skip]
+        let matrix = ColorMatrix::new(
+            r[0], r[1], r[2], 0.0, 0.0,
+            g[0], g[1], g[2], 0.0, 0.0,
+            b[0], b[1], b[2], 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        );
+        let simulation = color_filters::matrix(&matrix, None);
+        let linear = simulation.composed(color_filters::srgb_to_linear_gamma())?;
+        color_filters::linear_to_srgb_gamma().composed(linear)
+    }
 }
 
-const SKSL_SHADER: &'static str = r#"
-uniform shader image;
-vec4 main(float2 coord) {
-    return image.eval(coord);
+/// Errors returned by [`Painter::render_to_bytes`], [`Painter::paint_vector`] and
+/// [`Painter::render_to_pdf`].
+#[derive(Debug)]
+pub enum RenderError {
+    /// The raster surface of the requested size could not be created.
+    SurfaceCreation,
+    /// The rendered image could not be encoded in the requested format.
+    Encode,
+    /// The frame contains a paint callback, which [`CallbackExport::Error`] rejects.
+    VectorCallback,
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::SurfaceCreation => write!(f, "failed to create surface"),
+            RenderError::Encode => write!(f, "failed to encode image"),
+            RenderError::VectorCallback => {
+                write!(f, "paint callbacks can't be exported as vectors")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// How [`Painter::paint_vector`] and [`Painter::render_to_pdf`] export paint callbacks, whose
+/// drawables may use raster-only features vector backends can't serialize.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallbackExport {
+    /// Draw every callback into an image with `scale` times the resolution of the document and
+    /// embed that, clipped like the callback.
+    Rasterize { scale: f32 },
+    /// Fail with [`RenderError::VectorCallback`] if the frame contains a callback, for pure
+    /// vector output.
+    Error,
+}
+
+impl Default for CallbackExport {
+    fn default() -> Self {
+        CallbackExport::Rasterize { scale: 2.0 }
+    }
+}
+
+/// How [`Painter::flush`] waits for the GPU.
+#[cfg(feature = "gpu")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushSync {
+    /// Submit the work and return immediately.
+    Async,
+    /// Submit the work and block until the GPU has finished it.
+    SyncCpu,
 }
-"#;
 
 impl Painter {
     pub fn new() -> Painter {
+        Self::with_texture_store(Default::default())
+    }
+
+    /// Creates a painter that reads its textures from a [`TextureStore`] shared with other
+    /// painters.
+    pub fn with_texture_store(textures: Arc<RwLock<TextureStore>>) -> Painter {
         Self {
-            paints: AHashMap::new(),
+            textures,
+            hooks: Mutex::default(),
+            max_draw_calls: None,
+            stats: Mutex::default(),
+            used_textures: Mutex::default(),
+            saturation: 1.0,
+            cvd_simulation: None,
+            post_process: None,
+            output: OutputOptions::default(),
+            text_outline: None,
+            forced_filter: None,
+            quality: QualityTier::Medium,
+            msaa_coverage: false,
+            content_origin: ContentOrigin::Zero,
+            clip_expand: 0.0,
+            geometry_snap: false,
+            viewport_culling: false,
+            max_texture_side: None,
+            dithering: false,
+            callback_export: CallbackExport::default(),
+            shadow_regions: Vec::new(),
+            debug_annotations: false,
+            animation_time: 0.0,
+            callback_repaint_after: Mutex::default(),
+            #[cfg(feature = "gpu")]
+            context: Mutex::default(),
+            #[cfg(feature = "gpu")]
+            needs_flush: AtomicBool::new(false),
+            #[cfg(feature = "gpu")]
+            flush_interval: None,
+            #[cfg(feature = "shader-hot-reload")]
+            shader_watcher: None,
+        }
+    }
+
+    /// Creates a painter with the [`OutputOptions`] preset for `platform`.
+    ///
+    /// Create the surfaces for the painter with [`OutputOptions::image_info`] of
+    /// [`Self::output_options`], so they match what the platform compositor expects.
+    pub fn for_platform(platform: Platform) -> Painter {
+        Self::new().with_output_options(OutputOptions::for_platform(platform))
+    }
+
+    /// Sets the options for the output of the painter. The default paints over the existing
+    /// content of an N32 premultiplied surface without color management.
+    pub fn with_output_options(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// The options for the output of the painter.
+    pub fn output_options(&self) -> &OutputOptions {
+        &self.output
+    }
+
+    /// Changes the saturation of everything the painter draws, e.g. to desaturate the ui of an
+    /// inactive window. `1.0` (the default) keeps the colors, `0.0` draws in grayscale.
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.saturation = saturation;
+        self
+    }
+
+    /// Draws everything as someone with the color vision deficiency `cvd` would see it, to check
+    /// that the ui doesn't depend on colors they can't tell apart. Applied after
+    /// [`Self::with_saturation`]. Off by default.
+    pub fn with_cvd_simulation(mut self, cvd: CvdType) -> Self {
+        self.cvd_simulation = Some(cvd);
+        self
+    }
+
+    /// Runs every color the painter draws through the SKSL color filter `sksl`, after
+    /// [`Self::with_saturation`] and [`Self::with_cvd_simulation`], e.g. to color grade the whole
+    /// ui. `None` removes the effect.
+    ///
+    /// The filter has the signature `half4 main(half4 color)` and declares no uniforms or
+    /// children.
+    pub fn set_post_process_effect(&mut self, sksl: Option<&str>) -> Result<(), TextureError> {
+        self.post_process = sksl.map(post_process_filter).transpose()?;
+        Ok(())
+    }
+
+    /// Draws an outline of `width` points in `color` around text, e.g. to keep a HUD readable
+    /// over video or a 3D scene.
+    ///
+    /// The glyphs are drawn a second time underneath, dilated and filled with `color`. Other
+    /// shapes drawn with the font texture (rects, lines, ...) are not outlined.
+    pub fn with_text_outline(mut self, color: egui::Color32, width: f32) -> Self {
+        self.text_outline = Some((color, width));
+        self
+    }
+
+    /// Uses `filter` for magnifying and minifying every texture uploaded afterwards, overriding
+    /// the options egui or the caller asked for, e.g. [`TextureFilter::Nearest`] for a retro look.
+    /// `None` (the default) honors the options of each texture.
+    pub fn with_forced_filter(mut self, filter: Option<TextureFilter>) -> Self {
+        self.forced_filter = filter;
+        self
+    }
+
+    /// Sets the quality of the painter, from [`QualityTier::Low`] for cheap thumbnails to
+    /// [`QualityTier::High`] for a full view. The default is [`QualityTier::Medium`].
+    ///
+    /// The sampling of the tier applies to textures uploaded afterwards, see
+    /// [`TextureStore::with_quality`]. Blending in linear light paints every frame into a half
+    /// float layer, which is composited onto the canvas afterwards. Canvases without pixels,
+    /// e.g. picture recorders, are painted directly. egui's own anti aliasing feather is part
+    /// of the tessellation and is set with `egui::epaint::TessellationOptions::feathering`.
+    pub fn with_quality(mut self, quality: QualityTier) -> Self {
+        self.quality = quality;
+        self.textures.write().unwrap().set_quality(quality);
+        self
+    }
+
+    /// Leaves the anti aliasing of edges to the multisampling of the target surface instead of
+    /// egui's feathered geometry, for crisper edges on MSAA surfaces where both would smooth
+    /// every edge twice. Off by default.
+    ///
+    /// Clips are no longer anti aliased either, whatever [`Self::with_quality`] asks for.
+    /// Turning feathering off is up to whoever tessellates the frame; [`crate::EguiSkia`] does it
+    /// when this is set. Only use this with MSAA surfaces, other surfaces get aliased edges.
+    pub fn with_msaa_coverage(mut self, msaa_coverage: bool) -> Self {
+        self.msaa_coverage = msaa_coverage;
+        self
+    }
+
+    /// Whether edges are left to MSAA, see [`Self::with_msaa_coverage`].
+    pub fn msaa_coverage(&self) -> bool {
+        self.msaa_coverage
+    }
+
+    /// Whether clips are anti aliased, see [`Self::with_quality`] and
+    /// [`Self::with_msaa_coverage`].
+    fn clip_anti_alias(&self) -> bool {
+        self.quality != QualityTier::Low && !self.msaa_coverage
+    }
+
+    /// Clips `canvas` to `rect`, in its local coordinates.
+    ///
+    /// Under a matrix that doesn't keep rects axis aligned, e.g. a rotated ui, this clips to the
+    /// transformed quad, always anti aliased to keep its edges smooth. Scale and translation
+    /// keep the cheap rect clip.
+    fn clip_to_rect(&self, canvas: &mut Canvas, rect: Rect) {
+        if canvas.local_to_device_as_3x3().rect_stays_rect() {
+            canvas.clip_rect(rect, ClipOp::default(), self.clip_anti_alias());
+        } else {
+            canvas.clip_path(&Path::rect(rect, None), ClipOp::default(), true);
+        }
+    }
+
+    /// `options` with the filter forced by [`Self::with_forced_filter`], if any.
+    fn apply_forced_filter(&self, mut options: TextureOptions) -> TextureOptions {
+        if let Some(filter) = self.forced_filter {
+            options.magnification = filter;
+            options.minification = filter;
+        }
+        options
+    }
+
+    /// The paint for the outline pass of the text drawn with `paint`, if text is outlined.
+    fn text_outline_paint(&self, paint: &Paint) -> Option<Paint> {
+        let (color, width) = self.text_outline?;
+        let mut outline = paint.clone();
+        outline.set_color_filter(color_filters::blend(
+            color32_to_skia_unmultiplied(color),
+            BlendMode::SrcIn,
+        ));
+        outline.set_image_filter(image_filters::dilate((width, width), None, None));
+        Some(outline)
+    }
+
+    /// The color filter applied to the whole frame, if any.
+    fn frame_color_filter(&self) -> Option<ColorFilter> {
+        let saturation = (self.saturation != 1.0).then(|| {
+            let mut matrix = ColorMatrix::default();
+            matrix.set_saturation(self.saturation);
+            color_filters::matrix(&matrix, None)
+        });
+        let cvd = self.cvd_simulation.and_then(CvdType::color_filter);
+        [saturation, cvd, self.post_process.clone()]
+            .into_iter()
+            .flatten()
+            .reduce(|inner, outer| outer.composed(inner).unwrap_or(outer))
+    }
+
+    /// Uploads textures to the GPU of `context` when they are updated, instead of letting skia
+    /// upload them lazily while drawing.
+    ///
+    /// The painter never flushes or submits on its own, call [`Self::flush`] at the point where
+    /// egui's work should be submitted relative to the rest of the frame.
+    #[cfg(feature = "gpu")]
+    pub fn with_direct_context(mut self, context: skia_safe::gpu::DirectContext) -> Self {
+        self.context = Mutex::new(Some(context));
+        self
+    }
+
+    /// Drops the GPU textures and the reference to the context set by
+    /// [`Self::with_direct_context`]. Textures are drawn from their raster copies afterwards, so
+    /// the painter keeps working on raster canvases.
+    ///
+    /// Call this before destroying the GPU context (e.g. when the window closes or the device is
+    /// lost), otherwise dropping the painter afterwards can crash inside skia. If the context
+    /// has been abandoned, this happens automatically on the next paint.
+    #[cfg(feature = "gpu")]
+    pub fn release_gpu_resources(&mut self) {
+        self.textures.write().unwrap().release_gpu_resources();
+        *self.context.get_mut().unwrap() = None;
+        *self.needs_flush.get_mut() = false;
+    }
+
+    /// Releases the GPU resources if the context has been abandoned.
+    #[cfg(feature = "gpu")]
+    fn check_context(&self) {
+        let mut context = self.context.lock().unwrap();
+        if context.as_mut().map_or(false, |context| context.abandoned()) {
+            self.textures.write().unwrap().release_gpu_resources();
+            *context = None;
+            self.needs_flush.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns `true` if textures were uploaded or primitives drawn since the last
+    /// [`Self::flush`].
+    #[cfg(feature = "gpu")]
+    pub fn needs_flush(&self) -> bool {
+        self.needs_flush.load(Ordering::Relaxed)
+    }
+
+    /// Flushes the texture uploads and draws of this painter and submits them to the GPU in a
+    /// single submission. Does nothing without a context set by [`Self::with_direct_context`].
+    #[cfg(feature = "gpu")]
+    pub fn flush(&mut self, sync: FlushSync) {
+        self.check_context();
+        if let Some(context) = self.context.get_mut().unwrap() {
+            context.submit_work(sync == FlushSync::SyncCpu);
+            self.stats.get_mut().unwrap().gpu_submissions += 1;
+        }
+        *self.needs_flush.get_mut() = false;
+    }
+
+    /// Flushes the context set by [`Self::with_direct_context`] after every `interval`
+    /// primitives, so skia can release intermediate resources of very large frames early. This
+    /// lowers the peak GPU memory at the cost of some performance. `None` (the default) never
+    /// flushes while painting.
+    ///
+    /// These flushes don't submit, [`Self::flush`] is still needed at the end of the frame.
+    #[cfg(feature = "gpu")]
+    pub fn with_flush_interval(mut self, interval: Option<usize>) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Caps the number of meshes drawn per frame by merging compatible meshes. `None` (the
+    /// default) only merges consecutive meshes sharing a texture and clip rect.
+    ///
+    /// Consecutive meshes sharing a texture and clip rect are merged first. If there are still too
+    /// many, neighbouring meshes sharing a texture are merged with the union of their clip rects,
+    /// which can draw a little outside of the original clip rects. Meshes are never reordered or
+    /// merged across a paint callback, so the cap can't always be reached.
+    pub fn with_max_draw_calls(mut self, max_draw_calls: Option<usize>) -> Self {
+        self.max_draw_calls = max_draw_calls;
+        self
+    }
+
+    /// Expands every clip rect by `pixels` on each side, so the anti aliasing feather of shapes
+    /// ending right at a clip boundary (e.g. at panel edges) isn't cut off into a hard edge.
+    /// `0.0` (the default) clips exactly to egui's clip rects.
+    pub fn with_clip_expand(mut self, pixels: f32) -> Self {
+        self.clip_expand = pixels;
+        self
+    }
+
+    /// Rounds the vertices of thin axis aligned shapes (separators, borders, ...) to the pixel
+    /// centers, so 1px lines that straddle pixel boundaries are drawn as one crisp pixel instead of
+    /// two soft ones. Text, images and shapes with diagonal edges are left untouched.
+    ///
+    /// This assumes the canvas matrix maps points to whole pixels. Off by default.
+    pub fn with_geometry_snap(mut self, snap: bool) -> Self {
+        self.geometry_snap = snap;
+        self
+    }
+
+    /// Skips primitives whose clip rect lies outside the clip of every canvas (usually the
+    /// canvas bounds), e.g. content of a large scroll area far outside the window. Skipped
+    /// primitives are counted in [`FrameStats::culled_primitives`]. Off by default.
+    pub fn with_viewport_culling(mut self, culling: bool) -> Self {
+        self.viewport_culling = culling;
+        self
+    }
+
+    /// Limits [`Self::max_texture_side`] to `side` pixels, e.g. for raster painters whose
+    /// frames are later uploaded elsewhere.
+    pub fn with_max_texture_side(mut self, side: usize) -> Self {
+        self.max_texture_side = Some(side);
+        self
+    }
+
+    /// The largest texture width and height the painter can draw: the limit of the GPU with a
+    /// context from [`Self::with_direct_context`], capped by [`Self::with_max_texture_side`].
+    /// `None` for raster painters without a cap.
+    ///
+    /// [`crate::EguiSkia::run`] passes this to egui, which keeps its font atlas within it.
+    pub fn max_texture_side(&self) -> Option<usize> {
+        #[cfg(feature = "gpu")]
+        if let Some(context) = self.context.lock().unwrap().as_ref() {
+            let side = context.max_texture_side();
+            return Some(self.max_texture_side.map_or(side, |cap| cap.min(side)));
+        }
+        self.max_texture_side
+    }
+
+    /// Dithers the gradients of the vertex colors and textured draws (skia's `Paint::set_dither`),
+    /// breaking up the banding of subtle gradients on 8 bit surfaces, e.g. large shadows. Off by
+    /// default.
+    pub fn with_dithering(mut self, dithering: bool) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    /// Sets how [`Self::paint_vector`] and [`Self::render_to_pdf`] export paint callbacks. The
+    /// default rasterizes them at twice the resolution of the document.
+    pub fn with_callback_export(mut self, export: CallbackExport) -> Self {
+        self.callback_export = export;
+        self
+    }
+
+    /// Adds an annotation with the key `egui_skia.primitive` over the clip rect of every
+    /// primitive, so the draws can be told apart in the Skia debugger when the frame is recorded
+    /// into an `.skp`. The value is a string like `index=3;texture=Managed(0);clip=[0 0 80 20]`
+    /// (clip rect in points).
+    ///
+    /// egui doesn't tell which layer a primitive came from, so the layer isn't included. Off by
+    /// default, normal frames don't pay anything for it.
+    pub fn with_debug_annotations(mut self, annotate: bool) -> Self {
+        self.debug_annotations = annotate;
+        self
+    }
+
+    /// Packs small textures into one shared image, see [`TextureStore::with_texture_atlas`].
+    pub fn with_texture_atlas(self, enabled: bool) -> Self {
+        self.textures.write().unwrap().set_texture_atlas(enabled);
+        self
+    }
+
+    /// Converts the first font atlas in the background instead of stalling the first frame, see
+    /// [`TextureStore::with_async_atlas_upload`].
+    pub fn with_async_atlas_upload(self, enabled: bool) -> Self {
+        self.textures.write().unwrap().set_async_atlas_upload(enabled);
+        self
+    }
+
+    /// Whether a texture is still converted in the background, see
+    /// [`TextureStore::has_pending_uploads`].
+    pub fn has_pending_uploads(&self) -> bool {
+        self.textures.read().unwrap().has_pending_uploads()
+    }
+
+    /// Applies `gamma` to the coverage of the font texture, so text is drawn with a weight closer
+    /// to native ClearType or FreeType text, see [`TextureStore::set_font_gamma`]. Usual values
+    /// are between `1.8` and `2.2`. `1.0` (the default) draws text as egui rasterized it.
+    pub fn with_clear_type_gamma(self, gamma: f32) -> Result<Self, TextureError> {
+        self.textures.write().unwrap().set_font_gamma(gamma)?;
+        Ok(self)
+    }
+
+    /// Splits a font texture larger than `size` pixels into several images, see
+    /// [`TextureStore::with_max_atlas_size`].
+    pub fn with_max_atlas_size(self, size: u32) -> Self {
+        self.textures.write().unwrap().set_max_atlas_size(size);
+        self
+    }
+
+    /// Keeps textures that weren't drawn for `idle_frames` frames only encoded, see
+    /// [`TextureStore::with_low_memory`].
+    pub fn with_low_memory(self, idle_frames: u64) -> Self {
+        self.textures.write().unwrap().set_low_memory(idle_frames);
+        self
+    }
+
+    /// Sets the hooks that run while painting, replacing the previous ones.
+    pub fn set_hooks(&mut self, hooks: PaintHooks) {
+        *self.hooks.get_mut().unwrap() = hooks;
+    }
+
+    /// Sets the origin subtracted from the vertex positions, clip rects and callback rects of the
+    /// following frames and applied as a canvas translation instead.
+    ///
+    /// With content far away from the origin (e.g. a timeline millions of points long) this keeps
+    /// the per-vertex values small, so they don't lose precision when skia scales them by the
+    /// dpi. Typically the origin is updated with the scroll offset every frame.
+    pub fn set_content_origin(&mut self, origin: ContentOrigin) {
+        self.content_origin = origin;
+    }
+
+    /// The offset of the content origin for a frame of `primitives`.
+    fn frame_origin(&self, primitives: &[ClippedPrimitive]) -> egui::Vec2 {
+        let origin = match self.content_origin {
+            ContentOrigin::Zero => None,
+            ContentOrigin::FirstClipRect => primitives.first().map(|p| p.clip_rect.min),
+            ContentOrigin::Point(origin) => Some(origin),
+        };
+        origin
+            .filter(|origin| origin.is_finite())
+            .map_or(egui::Vec2::ZERO, |origin| origin.to_vec2())
+    }
+
+    /// Sets the shadows of the following frames that skia draws natively instead of egui's
+    /// shadow meshes, replacing the previous ones. Usually the host sets the shadows of its open
+    /// windows every frame.
+    ///
+    /// A mesh is replaced when its bounds match a region, see [`ShadowRegion`], so the shadow
+    /// stays in its place in the draw order. Shadows merged with other meshes by
+    /// [`Self::with_max_draw_calls`] are drawn by egui. Replaced meshes are counted in
+    /// [`FrameStats::native_shadows`].
+    pub fn set_shadow_regions(&mut self, regions: Vec<ShadowRegion>) {
+        self.shadow_regions = regions;
+    }
+
+    /// Sets the animation time in seconds passed to [`EguiSkiaPaintCallback::animated`]
+    /// callbacks, usually the `time` of egui's input for the frame. [`crate::EguiSkia`] sets it
+    /// on every run.
+    pub fn set_animation_time(&mut self, time: f64) {
+        self.animation_time = time;
+    }
+
+    /// The shortest repaint delay requested by the paint callbacks of the last frame, if any.
+    pub fn callback_repaint_after(&self) -> Option<Duration> {
+        *self.callback_repaint_after.lock().unwrap()
+    }
+
+    /// Statistics about the last frame painted with [`Self::paint_primitives`] or
+    /// [`Self::paint_and_update_textures`].
+    pub fn frame_stats(&self) -> FrameStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// The hits and misses of full texture uploads reusing the existing texture, see
+    /// [`TextureStore::cache_stats`].
+    pub fn texture_cache_stats(&self) -> TextureCacheStats {
+        self.textures.read().unwrap().cache_stats()
+    }
+
+    /// A hash of the geometry, colors, clip rects and texture ids of `primitives`, so a host
+    /// rendering reactively can skip painting a frame identical to the last one.
+    ///
+    /// The hash is stable across runs of the same build. It doesn't cover texture contents, so
+    /// only skip frames whose [`TexturesDelta`] is empty too. Paint callbacks are hashed by
+    /// their identity, so a frame with a callback created anew every frame never matches.
+    pub fn frame_hash(primitives: &[ClippedPrimitive]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for primitive in primitives {
+            let clip = primitive.clip_rect;
+            [clip.min.x, clip.min.y, clip.max.x, clip.max.y].map(f32::to_bits).hash(&mut hasher);
+            match &primitive.primitive {
+                Primitive::Mesh(mesh) => {
+                    mesh.texture_id.hash(&mut hasher);
+                    mesh.indices.hash(&mut hasher);
+                    for vertex in &mesh.vertices {
+                        let (pos, uv) = (vertex.pos, vertex.uv);
+                        [pos.x, pos.y, uv.x, uv.y].map(f32::to_bits).hash(&mut hasher);
+                        vertex.color.hash(&mut hasher);
+                    }
+                }
+                Primitive::Callback(callback) => {
+                    let rect = callback.rect;
+                    [rect.min.x, rect.min.y, rect.max.x, rect.max.y]
+                        .map(f32::to_bits)
+                        .hash(&mut hasher);
+                    (Arc::as_ptr(&callback.callback) as *const () as usize).hash(&mut hasher);
+                }
+            }
         }
+        hasher.finish()
     }
 
+    /// Summarizes the build features and configuration of this painter, e.g. to attach to bug
+    /// reports.
+    pub fn capabilities(&self) -> PainterCaps {
+        PainterCaps {
+            gpu_feature: cfg!(feature = "gpu"),
+            #[cfg(feature = "gpu")]
+            gpu: self.context.lock().unwrap().is_some(),
+            #[cfg(not(feature = "gpu"))]
+            gpu: false,
+            mipmaps: !cfg!(feature = "cpu_fix"),
+            runtime_effect: !self.textures.read().unwrap().raw_image_shaders(),
+            tile_mode: TILE_MODE,
+            vertex_blend_mode: BlendMode::Modulate,
+            color_type: self.output.color_type,
+            alpha_type: self.output.alpha_type,
+            color_space: self.output.color_space.clone(),
+            forced_filter: self.forced_filter,
+            quality: self.quality,
+            msaa_coverage: self.msaa_coverage,
+            max_draw_calls: self.max_draw_calls,
+            geometry_snap: self.geometry_snap,
+            viewport_culling: self.viewport_culling,
+            dithering: self.dithering,
+            debug_annotations: self.debug_annotations,
+        }
+    }
+
+    /// The texture store of this painter.
+    pub fn texture_store(&self) -> &Arc<RwLock<TextureStore>> {
+        &self.textures
+    }
+
+    /// Uploads the textures in `textures_delta`, draws `primitives` and frees the textures that
+    /// are no longer needed, see [`Self::update_textures`], [`Self::paint_primitives`] and
+    /// [`Self::free_textures`].
+    ///
+    /// The canvas state is saved and restored around every primitive and the dpi scale is
+    /// concatenated with the current matrix, so this can be called inside a `save_layer` or
+    /// under a transform pushed by the host.
     pub fn paint_and_update_textures(
         &mut self,
         canvas: &mut Canvas,
@@ -41,248 +760,2916 @@ impl Painter {
         primitives: Vec<ClippedPrimitive>,
         textures_delta: TexturesDelta,
     ) {
-        textures_delta.set.iter().for_each(|(id, image_delta)| {
-            let delta_image = match &image_delta.image {
-                ImageData::Color(color_image) => Image::from_raster_data(
-                    &ImageInfo::new_n32_premul(
-                        skia_safe::ISize::new(
-                            color_image.width() as i32,
-                            color_image.height() as i32,
-                        ),
-                        None,
-                    ),
-                    Data::new_copy(
-                        color_image
-                            .pixels
-                            .iter()
-                            .flat_map(|p| p.to_array())
-                            .collect::<Vec<_>>()
-                            .as_slice(),
-                    ),
-                    color_image.width() * 4,
-                )
-                .unwrap(),
-                ImageData::Font(font) => {
-                    let pixels = font.srgba_pixels(Some(1.0));
-                    Image::from_raster_data(
-                        &ImageInfo::new_n32_premul(
-                            skia_safe::ISize::new(font.width() as i32, font.height() as i32),
-                            None,
-                        ),
-                        Data::new_copy(
-                            pixels
-                                .flat_map(|p| p.to_array())
-                                .collect::<Vec<_>>()
-                                .as_slice(),
-                        ),
-                        font.width() * 4,
-                    )
-                    .unwrap()
-                }
-            };
+        self.update_textures(&textures_delta);
+
+        self.paint_primitives(canvas, dpi, &primitives);
+
+        self.free_textures(&textures_delta);
+    }
+
+    /// Like [`Self::paint_and_update_textures`], but paints over the canvas without clearing it
+    /// to the clear color, for the layers of a [`Compositor`](crate::Compositor).
+    pub(crate) fn paint_layer(
+        &mut self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) {
+        self.update_textures(&textures_delta);
+
+        self.paint_to(&mut [canvas], dpi, &primitives, None, false);
+
+        self.free_textures(&textures_delta);
+    }
+
+    /// Draws `primitives` without touching the textures.
+    ///
+    /// Use this with a [`TextureStore`] shared between painters, where the textures are updated
+    /// once per frame on the store.
+    ///
+    /// `dpi` may change from one frame to the next, e.g. when the window moves to a monitor with
+    /// a different scale factor. The painter keeps nothing that depends on it between frames:
+    /// the scale, clip rects and callback rects are derived from `dpi` for every primitive, and
+    /// egui re-rasterizes the font atlas itself when its pixels per point change.
+    ///
+    /// The canvas may carry any matrix, e.g. to rotate the whole ui. Under a rotation or skew
+    /// clip rects turn into anti aliased clips of their transformed quads, which compose with
+    /// the clips of the canvas like in the untransformed ui.
+    pub fn paint_primitives(
+        &self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+    ) {
+        self.paint_to(&mut [canvas], dpi, primitives, None, true);
+    }
 
-            let image = match image_delta.pos {
-                None => delta_image,
-                Some(pos) => {
-                    let old_image = self.paints.remove(&id).unwrap().image;
+    /// Draws a single primitive of a frame, e.g. to interleave egui's primitives with draws of
+    /// the app. Drawing all primitives of a frame one by one gives the same result as
+    /// [`Self::paint_primitives`] without a clear color, frame color filter or frame hooks.
+    ///
+    /// The textures must have been set already. Frame statistics and repaint requests of
+    /// callbacks are not recorded.
+    pub fn draw_primitive(&self, canvas: &mut Canvas, dpi: f32, primitive: &ClippedPrimitive) {
+        #[cfg(feature = "gpu")]
+        if self.context.lock().unwrap().is_some() {
+            self.needs_flush.store(true, Ordering::Relaxed);
+        }
+        self.prepare_textures(mesh_texture_ids(std::slice::from_ref(primitive)));
+        let origin = self.frame_origin(std::slice::from_ref(primitive));
+        self.draw_clipped_primitive(
+            &mut [canvas],
+            dpi,
+            origin,
+            primitive,
+            None,
+            &mut FrameStats::default(),
+            &CallbackResponse::default(),
+        );
+    }
 
-                    let mut surface = Surface::new_raster_n32_premul(skia_safe::ISize::new(
-                        old_image.width() as i32,
-                        old_image.height() as i32,
-                    ))
-                    .unwrap();
+    /// Like [`Self::paint_and_update_textures`], but draws the frame onto both `primary` and
+    /// `secondary`, e.g. the window and a `PictureRecorder` for streaming.
+    ///
+    /// The meshes are converted and the callbacks recorded once, then every draw is issued to
+    /// both canvases, so the second canvas only costs the extra draw calls. Each canvas keeps its
+    /// own matrix and clip. The hooks run once per canvas.
+    pub fn paint_tee(
+        &mut self,
+        primary: &mut Canvas,
+        secondary: &mut Canvas,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) {
+        self.update_textures(&textures_delta);
 
-                    let canvas = surface.canvas();
+        self.paint_to(&mut [primary, secondary], dpi, &primitives, None, true);
 
-                    canvas.draw_image(&old_image, Point::new(0.0, 0.0), None);
+        self.free_textures(&textures_delta);
+    }
 
-                    canvas.clip_rect(
-                        Rect::new(
-                            pos[0] as scalar,
-                            pos[1] as scalar,
-                            (pos[0] as i32 + delta_image.width()) as scalar,
-                            (pos[1] as i32 + delta_image.height()) as scalar,
-                        ),
-                        ClipOp::default(),
-                        false,
-                    );
+    /// Draws `primitives` onto every canvas of `canvases`, with the callbacks rasterized at
+    /// `callback_scale` times the resolution of the canvas if set. The canvases are cleared to
+    /// the clear color first if `clear`.
+    fn paint_to(
+        &self,
+        canvases: &mut [&mut Canvas],
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+        callback_scale: Option<f32>,
+        clear: bool,
+    ) {
+        #[cfg(feature = "gpu")]
+        {
+            self.check_context();
+            if self.context.lock().unwrap().is_some() && !primitives.is_empty() {
+                self.needs_flush.store(true, Ordering::Relaxed);
+            }
+        }
 
-                    canvas.clear(Color::TRANSPARENT);
-                    canvas.draw_image(&delta_image, Point::new(pos[0] as f32, pos[1] as f32), None);
+        // Cleared before the layer of the high quality tier, which is drawn over the canvas.
+        if let Some(clear_color) = self.output.clear_color.filter(|_| clear) {
+            for canvas in canvases.iter_mut() {
+                canvas.clear(clear_color);
+            }
+        }
 
-                    surface.image_snapshot()
+        if self.quality == QualityTier::High {
+            let layers: Option<Vec<Surface>> =
+                canvases.iter_mut().map(|canvas| linear_layer(canvas)).collect();
+            if let Some(mut layers) = layers {
+                let mut layer_canvases = layers.iter_mut().map(Surface::canvas).collect::<Vec<_>>();
+                self.paint_frame(&mut layer_canvases, dpi, primitives, callback_scale);
+                drop(layer_canvases);
+                for (canvas, layer) in canvases.iter_mut().zip(&mut layers) {
+                    let image = layer.image_snapshot();
+                    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+                    arc.reset_matrix();
+                    arc.draw_image(&image, (0, 0), None);
                 }
-            };
+                return;
+            }
+        }
 
-            let local_matrix =
-                skia_safe::Matrix::scale((1.0 / image.width() as f32, 1.0 / image.height() as f32));
+        self.paint_frame(canvases, dpi, primitives, callback_scale);
+    }
 
-            let sampling_options = {
-                let filter_mode = match image_delta.options.magnification {
-                    TextureFilter::Nearest => skia_safe::FilterMode::Nearest,
-                    TextureFilter::Linear => skia_safe::FilterMode::Linear,
-                };
-                let mm_mode = if cfg!(feature = "cpu_fix") {
-                    skia_safe::MipmapMode::None
-                } else {
-                    match image_delta.options.minification {
-                        TextureFilter::Nearest => skia_safe::MipmapMode::Nearest,
-                        TextureFilter::Linear => skia_safe::MipmapMode::Linear,
-                    }
-                };
-                let sampling_options = skia_safe::SamplingOptions::new(filter_mode, mm_mode);
-                sampling_options
-            };
-            let tile_mode = skia_safe::TileMode::Clamp;
+    /// Draws `primitives` onto every canvas of `canvases`, see [`Self::paint_to`].
+    fn paint_frame(
+        &self,
+        canvases: &mut [&mut Canvas],
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+        callback_scale: Option<f32>,
+    ) {
+        let batched = match self.max_draw_calls {
+            Some(max_draw_calls) => merge_primitives(primitives, max_draw_calls)
+                .into_iter()
+                .map(Cow::Owned)
+                .collect(),
+            // Shadow meshes stay apart to be matched against the shadow regions.
+            None => batch_primitives(primitives, |mesh| {
+                self.shadow_regions.iter().any(|region| region.matches(mesh))
+            }),
+        };
 
-            let mut paint = Paint::default();
+        self.prepare_textures(mesh_texture_ids(primitives));
 
-            let mut shader = image
-                .to_shader((tile_mode, tile_mode), sampling_options, &local_matrix)
-                .unwrap();
+        let mut stats = FrameStats {
+            batched_meshes: primitives.len() - batched.len(),
+            ..Default::default()
+        };
+        let callback_response = CallbackResponse::default();
+        let mut clip_rects = AHashSet::new();
 
-            shader = RuntimeEffect::make_for_shader(SKSL_SHADER, None)
-                .unwrap()
-                .make_shader(Data::new_empty(), &[ChildPtr::Shader(shader)], None)
-                .unwrap();
+        let frame_color_filter = self.frame_color_filter();
+        let mut layers = Vec::with_capacity(canvases.len());
+        for canvas in canvases.iter_mut() {
+            layers.push(frame_color_filter.as_ref().map(|filter| {
+                let mut paint = Paint::default();
+                paint.set_color_filter(filter.clone());
+                canvas.save_layer(&SaveLayerRec::default().paint(&paint))
+            }));
 
-            paint.set_shader(shader);
+            if let Some(before_frame) = &mut self.hooks.lock().unwrap().before_frame {
+                before_frame(&mut *skia_safe::AutoCanvasRestore::guard(canvas, true));
+            }
+        }
 
-            self.paints.insert(id.clone(), PaintHandle { paint, image });
-        });
+        let origin = self.frame_origin(primitives);
 
-        for primitive in primitives {
-            let skclip_rect = Rect::new(
-                primitive.clip_rect.min.x,
-                primitive.clip_rect.min.y,
-                primitive.clip_rect.max.x,
-                primitive.clip_rect.max.y,
-            );
-            match primitive.primitive {
-                Primitive::Mesh(mesh) => {
-                    canvas.set_matrix(&skia_safe::M44::new_identity().set_scale(dpi, dpi, 1.0));
-                    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        #[cfg(feature = "gpu")]
+        let mut since_flush = 0;
+        for (index, primitive) in batched.iter().map(|primitive| &**primitive).enumerate() {
+            if self.debug_annotations {
+                for canvas in canvases.iter_mut() {
+                    annotate_primitive(canvas, dpi, index, primitive);
+                }
+            }
 
-                    let meshes = mesh.split_to_u16();
+            #[cfg(feature = "gpu")]
+            if let Some(interval) = self.flush_interval {
+                if since_flush == interval.max(1) {
+                    if let Some(context) = self.context.lock().unwrap().as_mut() {
+                        context.flush_work();
+                        stats.gpu_flushes += 1;
+                    }
+                    since_flush = 0;
+                }
+                since_flush += 1;
+            }
 
-                    for mesh in &meshes {
-                        let texture_id = mesh.texture_id;
+            let clip = primitive.clip_rect;
+            clip_rects.insert([clip.min.x, clip.min.y, clip.max.x, clip.max.y].map(f32::to_bits));
+            stats.largest_clip_area = stats.largest_clip_area.max(clip.area());
 
-                        let mut pos = Vec::with_capacity(mesh.vertices.len());
-                        let mut texs = Vec::with_capacity(mesh.vertices.len());
-                        let mut colors = Vec::with_capacity(mesh.vertices.len());
+            let culling_clip = clip.expand(self.clip_expand / dpi);
+            if self.viewport_culling && is_culled(canvases, dpi, culling_clip) {
+                stats.culled_primitives += 1;
+                continue;
+            }
 
-                        let mut push_vert = |v: &egui::epaint::Vertex| {
-                            // Apparently vertices can be NaN and if they are NaN, nothing is rendered.
-                            // Replacing them with 0 works around this.
-                            // https://github.com/lucasmerlin/egui_skia/issues/4
-                            let fixed_pos = if v.pos.x.is_nan() || v.pos.y.is_nan() {
-                                Pos2::new(0.0, 0.0)
-                            } else {
-                                v.pos
-                            };
+            self.draw_clipped_primitive(
+                canvases,
+                dpi,
+                origin,
+                primitive,
+                callback_scale,
+                &mut stats,
+                &callback_response,
+            );
+        }
 
-                            pos.push(Point::new(fixed_pos.x, fixed_pos.y));
-                            texs.push(Point::new(v.uv.x, v.uv.y));
-
-                            let c = v.color;
-                            let c = Color::from_argb(c.a(), c.r(), c.g(), c.b());
-                            // un-premultply color
-                            let mut cf = skia_safe::Color4f::from(c);
-                            cf.r /= cf.a;
-                            cf.g /= cf.a;
-                            cf.b /= cf.a;
-                            colors.push(Color::from_argb(
-                                c.a(),
-                                (cf.r * 255.0) as u8,
-                                (cf.g * 255.0) as u8,
-                                (cf.b * 255.0) as u8,
-                            ));
-                        };
-
-                        let mut i = 0;
-                        while i < mesh.indices.len() {
-                            let v0 = mesh.vertices[mesh.indices[i] as usize];
-                            let mut v1 = mesh.vertices[mesh.indices[i + 1] as usize];
-                            let mut v2 = mesh.vertices[mesh.indices[i + 2] as usize];
-                            i += 3;
-
-                            // Egui use the uv coordinates 0,0 to get a white color when drawing vector graphics
-                            // 0,0 is always a white dot on the font texture
-                            // Unfortunately skia has a bug where it cannot get a color when the uv coordinates are equal
-                            // https://bugs.chromium.org/p/skia/issues/detail?id=13706
-                            // As a workaround, when the uv coordinates are equal, we move the uv coordinates a little bit
-                            if v0.uv == Pos2::ZERO && v1.uv == Pos2::ZERO && v2.uv == Pos2::ZERO {
-                                v1.uv = Pos2::new(0.0, 1.0 / 65536.0);
-                                v2.uv = Pos2::new(1.0 / 65536.0, 0.0);
-                            }
+        for (canvas, layer) in canvases.iter_mut().zip(layers) {
+            if let Some(after_frame) = &mut self.hooks.lock().unwrap().after_frame {
+                after_frame(&mut *skia_safe::AutoCanvasRestore::guard(canvas, true));
+            }
 
-                            push_vert(&v0);
-                            push_vert(&v1);
-                            push_vert(&v2);
-                        }
+            if let Some(save_count) = layer {
+                canvas.restore_to_count(save_count);
+            }
+        }
 
-                        let vertices =
-                            Vertices::new_copy(VertexMode::Triangles, &pos, &texs, &colors, None);
+        stats.unique_clip_rects = clip_rects.len();
+        *self.stats.lock().unwrap() = stats;
+        *self.callback_repaint_after.lock().unwrap() = callback_response.repaint_after();
+    }
 
-                        arc.clip_rect(skclip_rect, ClipOp::default(), true);
+    /// Gets the textures `ids` ready for drawing: decodes the demoted ones and takes the atlas
+    /// snapshot of the textures uploaded since the last one.
+    fn prepare_textures(&self, ids: Vec<TextureId>) {
+        self.mark_textures_used(ids);
+        if self.textures.read().unwrap().atlas_dirty() {
+            if let Err(err) = self.textures.write().unwrap().refresh_atlas() {
+                log::warn!("Leaving out textures packed since the last frame: {}", err);
+            }
+        }
+    }
 
-                        let paint = &self.paints[&texture_id].paint;
+    /// Decodes the demoted textures of `ids` again and remembers the textures as drawn, see
+    /// [`TextureStore::with_low_memory`]. The store only needs the write lock when a texture was
+    /// demoted, the textures are marked as used after the frame by [`Self::free_textures`].
+    fn mark_textures_used(&self, ids: Vec<TextureId>) {
+        if !self.textures.read().unwrap().low_memory() {
+            return;
+        }
+        let demoted = {
+            let textures = self.textures.read().unwrap();
+            ids.iter().any(|id| textures.is_demoted(*id))
+        };
+        if demoted {
+            if let Err(err) = self.textures.write().unwrap().promote(ids.iter().copied()) {
+                log::warn!("Leaving out a demoted texture: {}", err);
+            }
+        }
+        self.used_textures.lock().unwrap().extend(ids);
+    }
 
-                        arc.draw_vertices(&vertices, BlendMode::Modulate, paint);
+    /// Draws `primitive` with the clip, content origin and callback handling of a frame.
+    fn draw_clipped_primitive(
+        &self,
+        canvases: &mut [&mut Canvas],
+        dpi: f32,
+        origin: egui::Vec2,
+        primitive: &ClippedPrimitive,
+        callback_scale: Option<f32>,
+        stats: &mut FrameStats,
+        callback_response: &CallbackResponse,
+    ) {
+        let clip = primitive.clip_rect.expand(self.clip_expand / dpi);
+        let local_clip = clip.translate(-origin);
+        let skclip_rect = Rect::new(
+            local_clip.min.x,
+            local_clip.min.y,
+            local_clip.max.x,
+            local_clip.max.y,
+        );
+        match &primitive.primitive {
+            Primitive::Mesh(mesh) => {
+                let shadow = self.shadow_regions.iter().find(|region| region.matches(mesh));
+                match shadow {
+                    Some(region) => {
+                        stats.native_shadows += 1;
+                        stats.clip_operations += 1;
+                        for canvas in canvases.iter_mut() {
+                            let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+                            arc.scale((dpi, dpi));
+                            let skclip = Rect::new(clip.min.x, clip.min.y, clip.max.x, clip.max.y);
+                            self.clip_to_rect(&mut arc, skclip);
+                            region.draw(&mut arc);
+                        }
                     }
+                    None => self.draw_mesh_at(canvases, dpi, origin, Some(clip), mesh, stats),
                 }
-                Primitive::Callback(data) => {
-                    let callback: Arc<EguiSkiaPaintCallback> = data.callback.downcast().unwrap();
-                    let rect = data.rect;
+            }
+            Primitive::Callback(data) => {
+                let rect = data.rect;
+                // Ui code shared with other backends can add their callback types too.
+                let callback: Arc<EguiSkiaPaintCallback> = match data.callback.clone().downcast() {
+                    Ok(callback) => callback,
+                    Err(callback) => {
+                        log::warn!(
+                            "skipping paint callback at {:?} of a foreign type ({:?})",
+                            rect,
+                            (*callback).type_id()
+                        );
+                        stats.foreign_callbacks += 1;
+                        if cfg!(debug_assertions) {
+                            for canvas in canvases.iter_mut() {
+                                draw_placeholder(canvas, dpi, clip, rect);
+                            }
+                        }
+                        return;
+                    }
+                };
+
+                // The callback is recorded once, with the matrix of the first canvas.
+                let mut transform = canvases[0].local_to_device_as_3x3();
+                transform.pre_scale((dpi, dpi), None);
+                transform.pre_translate((rect.min.x, rect.min.y));
+                let info = CallbackInfo {
+                    bounds: Rect::from_wh(rect.width(), rect.height()),
+                    time: self.animation_time,
+                    transform,
+                };
+
+                let mut content = callback.content(&info, callback_response);
 
-                    let skia_rect = Rect::new(
-                        rect.min.x * dpi,
-                        rect.min.y * dpi,
-                        rect.max.x * dpi,
-                        rect.max.y * dpi,
-                    );
+                stats.clip_operations += 1;
+                stats.callbacks += 1;
 
-                    let mut drawable: Drawable = callback.callback.deref()(skia_rect).0.unwrap();
+                let image = callback_scale
+                    .and_then(|scale| rasterize_content(&mut content, rect.size(), dpi * scale));
 
+                // Meshes and callbacks each draw inside their own save/restore, so the
+                // callback starts from the matrix of the frame no matter which primitive
+                // came before, and a callback leaving saves behind can't leak into the next.
+                for canvas in canvases.iter_mut() {
                     let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
 
-                    arc.clip_rect(skclip_rect, ClipOp::default(), true);
-                    arc.translate((rect.min.x, rect.min.y));
+                    arc.scale((dpi, dpi));
+                    arc.translate((origin.x, origin.y));
+                    self.clip_to_rect(&mut arc, skclip_rect);
+                    arc.translate((rect.min.x - origin.x, rect.min.y - origin.y));
 
-                    drawable.draw(&mut arc, None);
+                    let draw = |canvas: &mut Canvas| match &image {
+                        Some(image) => {
+                            let bounds = Rect::from_wh(rect.width(), rect.height());
+                            let sampling =
+                                SamplingOptions::new(FilterMode::Linear, MipmapMode::None);
+                            canvas.draw_image_rect_with_sampling_options(
+                                image,
+                                None,
+                                bounds,
+                                sampling,
+                                &Paint::default(),
+                            );
+                        }
+                        None => content.draw(canvas, info.bounds),
+                    };
+                    match &mut self.hooks.lock().unwrap().around_callback {
+                        Some(around_callback) => around_callback(&mut *arc, rect, Box::new(draw)),
+                        None => draw(&mut *arc),
+                    }
                 }
             }
         }
+    }
 
-        textures_delta.free.iter().for_each(|id| {
-            self.paints.remove(id);
-        });
+    /// Blurs the content already on `canvas` inside `region` and then draws `primitives` on top,
+    /// giving a frosted glass effect behind e.g. a modal dialog.
+    ///
+    /// `region` is in points and `sigma` is the standard deviation of the blur in points. The
+    /// canvas is not cleared to the clear color of [`Self::with_output_options`] for this.
+    pub fn paint_with_backdrop_blur(
+        &self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        region: egui::Rect,
+        sigma: f32,
+        primitives: &[ClippedPrimitive],
+    ) {
+        if let Some(blur) = image_filters::blur((sigma, sigma), TileMode::Clamp, None, None) {
+            let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+            arc.scale((dpi, dpi));
+            arc.clip_rect(
+                Rect::new(region.min.x, region.min.y, region.max.x, region.max.y),
+                ClipOp::default(),
+                true,
+            );
+            arc.save_layer(&SaveLayerRec::default().backdrop(&blur));
+            arc.restore();
+        }
+
+        // Clearing to the clear color would wipe the blurred content again.
+        self.paint_to(&mut [canvas], dpi, primitives, None, false);
     }
-}
 
-pub struct EguiSkiaPaintCallback {
-    callback: Box<dyn Fn(Rect) -> SyncSendableDrawable + Send + Sync>,
-}
+    /// Draws `primitives` faded out towards the edges of `region`, so an overlay blends into the
+    /// content below instead of ending in a hard edge.
+    ///
+    /// `region` is in points. The alpha falls off linearly over `inset` points from every edge,
+    /// nothing outside of `region` is drawn.
+    pub fn paint_with_edge_feather(
+        &self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        region: egui::Rect,
+        inset: f32,
+        primitives: &[ClippedPrimitive],
+    ) {
+        let rect = Rect::new(region.min.x, region.min.y, region.max.x, region.max.y);
+        let pixels = Rect::new(
+            rect.left * dpi,
+            rect.top * dpi,
+            rect.right * dpi,
+            rect.bottom * dpi,
+        );
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.clip_rect(pixels, ClipOp::default(), true);
+        arc.save_layer(&SaveLayerRec::default().bounds(&pixels));
 
-impl EguiSkiaPaintCallback {
-    pub fn new<F: Fn(&mut Canvas) + Send + Sync + 'static>(callback: F) -> EguiSkiaPaintCallback {
-        EguiSkiaPaintCallback {
-            callback: Box::new(move |rect| {
-                let mut pr = PictureRecorder::new();
-                let mut canvas = pr.begin_recording(rect, None);
-                callback(&mut canvas);
-                SyncSendableDrawable(
-                    pr.finish_recording_as_drawable()
-                        .unwrap()
-                        .wrap_send()
-                        .unwrap(),
-                )
-            }),
+        self.paint_primitives(&mut arc, dpi, primitives);
+
+        if inset > 0.0 {
+            arc.scale((dpi, dpi));
+            let horizontal = (region.left_center(), region.right_center(), region.width());
+            let vertical = (region.center_top(), region.center_bottom(), region.height());
+            for (from, to, length) in [horizontal, vertical] {
+                let stop = (inset / length).clamp(0.0, 0.5);
+                let (from, to) = (Point::new(from.x, from.y), Point::new(to.x, to.y));
+                if let Some(mask) = feather_mask(from, to, stop) {
+                    arc.draw_rect(rect, &mask);
+                }
+            }
         }
     }
-}
 
-struct SyncSendableDrawable(pub Sendable<Drawable>);
-
-unsafe impl Sync for SyncSendableDrawable {}
+    /// Draws `primitives` and then `watermark` on top of them, placed in the corner (or edge, or
+    /// center) of the canvas given by `anchor` at its native pixel size.
+    ///
+    /// `opacity` is in `0.0..=1.0`. The watermark is placed in device pixels, whatever matrix the
+    /// canvas carries for the ui.
+    pub fn paint_with_watermark(
+        &self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+        watermark: &Image,
+        anchor: egui::Align2,
+        opacity: f32,
+    ) {
+        self.paint_primitives(canvas, dpi, primitives);
+
+        let canvas_size = canvas.base_layer_size();
+        let frame = egui::Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(canvas_size.width as f32, canvas_size.height as f32),
+        );
+        let rect = anchor.align_size_within_rect(
+            egui::vec2(watermark.width() as f32, watermark.height() as f32),
+            frame,
+        );
+
+        let mut paint = Paint::default();
+        paint.set_alpha_f(opacity.clamp(0.0, 1.0));
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.reset_matrix();
+        arc.draw_image(watermark, Point::new(rect.min.x, rect.min.y), Some(&paint));
+    }
+
+    /// Paints a frame onto a transparent raster surface of `size` pixels and encodes it in
+    /// `format`, e.g. for a thumbnail service.
+    ///
+    /// `quality` is in `0..=100` and only used by lossy formats like JPEG and WebP.
+    pub fn render_to_bytes(
+        &mut self,
+        size: (i32, i32),
+        dpi: f32,
+        format: EncodedImageFormat,
+        quality: u32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Vec<u8>, RenderError> {
+        let mut surface =
+            Surface::new_raster_n32_premul(size).ok_or(RenderError::SurfaceCreation)?;
+        self.paint_and_update_textures(surface.canvas(), dpi, primitives, textures_delta);
+        let data = surface
+            .image_snapshot()
+            .encode_to_data_with_quality(format, quality.min(100))
+            .ok_or(RenderError::Encode)?;
+        Ok(data.as_bytes().to_vec())
+    }
+
+    /// Draws `primitives` onto the canvas of a vector document, e.g. an SVG canvas, with the paint
+    /// callbacks exported as set by [`Self::with_callback_export`]. `dpi` is the scale of the
+    /// document in units per point.
+    pub fn paint_vector(
+        &self,
+        canvas: &mut Canvas,
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+    ) -> Result<(), RenderError> {
+        let callback_scale = match self.callback_export {
+            CallbackExport::Rasterize { scale } => Some(scale),
+            CallbackExport::Error => {
+                let has_callback = primitives
+                    .iter()
+                    .any(|primitive| matches!(primitive.primitive, Primitive::Callback(_)));
+                if has_callback {
+                    return Err(RenderError::VectorCallback);
+                }
+                None
+            }
+        };
+        self.paint_to(&mut [canvas], dpi, primitives, callback_scale, true);
+        Ok(())
+    }
+
+    /// Paints a frame onto a single PDF page of `size` PDF units (1/72 inch) and returns the
+    /// document, see [`Self::paint_vector`].
+    pub fn render_to_pdf(
+        &mut self,
+        size: (f32, f32),
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<Vec<u8>, RenderError> {
+        self.update_textures(&textures_delta);
+
+        let mut page = skia_safe::pdf::new_document(None).begin_page(size, None);
+        let painted = self.paint_vector(page.canvas(), dpi, &primitives);
+        let data = page.end_page().close();
+
+        self.free_textures(&textures_delta);
+
+        painted?;
+        Ok(data.as_bytes().to_vec())
+    }
+
+    /// Records `primitives` into a [`Picture`] instead of drawing them, for replaying them on
+    /// another thread with [`Canvas::draw_picture`].
+    ///
+    /// `bounds` is the area of the frame in pixels. The picture holds references to the textures
+    /// it draws, so record with a painter that draws from raster textures (no
+    /// [`Self::with_direct_context`]) if the replaying thread uses another GPU context.
+    pub fn record_picture(
+        &self,
+        bounds: Rect,
+        dpi: f32,
+        primitives: &[ClippedPrimitive],
+    ) -> Sendable<Picture> {
+        let mut recorder = PictureRecorder::new();
+        self.paint_primitives(recorder.begin_recording(bounds, None), dpi, primitives);
+        recorder
+            .finish_recording_as_picture(None)
+            .unwrap()
+            .wrap_send()
+            .unwrap()
+    }
+
+    /// Compiles the GPU pipeline used for drawing egui meshes by drawing an invisible textured
+    /// quad onto `surface` and submitting it, so the first real frame doesn't stall on it.
+    ///
+    /// Call this once at startup, after the surface has been created. The quad samples a
+    /// private texture that never enters the [`TextureStore`], so no texture of the store or of
+    /// the painters sharing it is touched.
+    #[cfg(feature = "gpu")]
+    pub fn warmup(
+        &mut self,
+        context: &mut skia_safe::gpu::DirectContext,
+        surface: &mut skia_safe::Surface,
+    ) -> Result<(), TextureError> {
+        let store = self.warmup_store(context)?;
+        let id = TextureId::default();
+        let paint = &store.get(&id).ok_or(TextureError::UnknownTexture(id))?.paint;
+
+        let positions = [Point::new(0.0, 0.0), Point::new(1.0, 0.0), Point::new(0.0, 1.0)];
+        let colors = [Color::TRANSPARENT; 3];
+        let vertices =
+            Vertices::new_copy(VertexMode::Triangles, &positions, &positions, &colors, None);
+        surface
+            .canvas()
+            .draw_vertices(&vertices, BlendMode::Modulate, paint);
+        context.submit_work(false);
+        Ok(())
+    }
+
+    /// The private store of [`Self::warmup`], with its texture uploaded to `context` as the
+    /// default texture.
+    #[cfg(feature = "gpu")]
+    fn warmup_store(
+        &self,
+        context: &mut skia_safe::gpu::DirectContext,
+    ) -> Result<TextureStore, TextureError> {
+        let mut store = self.textures.read().unwrap().empty_like();
+        let id = TextureId::default();
+        // Opaque textures skip the runtime effect, which has to be compiled like for the font
+        // texture.
+        let texel = egui::Color32::from_white_alpha(128);
+        let image = ImageData::Color(egui::ColorImage::new([1, 1], texel));
+        store.upload_texture(id, &image, TextureOptions::LINEAR)?;
+        store.upload_to_gpu(id, context)?;
+        Ok(store)
+    }
+
+    /// Applies the `set` part of `textures_delta`, before painting a frame.
+    ///
+    /// Together with [`Self::paint_primitives`] and [`Self::free_textures`] this splits up
+    /// [`Self::paint_and_update_textures`], e.g. to update the textures at another point of the
+    /// frame than painting, or to paint a frame several times into different canvases without
+    /// uploading its textures again.
+    ///
+    /// Textures failing to upload to the GPU are logged and drawn from their raster copies.
+    pub fn update_textures(&mut self, textures_delta: &TexturesDelta) {
+        #[cfg(feature = "gpu")]
+        self.check_context();
+
+        #[cfg(feature = "shader-hot-reload")]
+        self.reload_shaders();
+
+        let forced;
+        let textures_delta = match self.forced_filter {
+            Some(_) => {
+                forced = TexturesDelta {
+                    set: textures_delta
+                        .set
+                        .iter()
+                        .map(|(id, delta)| {
+                            let delta = ImageDelta {
+                                options: self.apply_forced_filter(delta.options),
+                                ..delta.clone()
+                            };
+                            (*id, delta)
+                        })
+                        .collect(),
+                    free: textures_delta.free.clone(),
+                };
+                &forced
+            }
+            None => textures_delta,
+        };
+
+        let mut textures = self.textures.write().unwrap();
+        if let Err(err) = textures.set_textures(textures_delta) {
+            log::warn!("Failed to update textures: {}", err);
+        }
+
+        #[cfg(feature = "gpu")]
+        if let Some(context) = self.context.get_mut().unwrap() {
+            for (i, (id, _)) in textures_delta.set.iter().enumerate() {
+                // Only upload the final state of textures updated several times.
+                if textures_delta.set[i + 1..]
+                    .iter()
+                    .all(|(other, _)| other != id)
+                    && !textures.is_pending(*id)
+                    && !textures.is_on_gpu(*id)
+                {
+                    if let Err(err) = textures.upload_to_gpu(*id, context) {
+                        log::warn!("Failed to upload texture {:?} to the GPU: {}", id, err);
+                    }
+                }
+            }
+            let arrived = textures.take_arrived_atlas();
+            if arrived && !textures_delta.set.iter().any(|(id, _)| *id == TextureId::default()) {
+                if let Err(err) = textures.upload_to_gpu(TextureId::default(), context) {
+                    log::warn!("Failed to upload the font atlas to the GPU: {}", err);
+                }
+            }
+            *self.needs_flush.get_mut() |= !textures_delta.set.is_empty() || arrived;
+        }
+    }
+
+    /// Applies the `free` part of `textures_delta` and finishes the frame, after its last paint.
+    ///
+    /// Freed textures stay usable for the primitives of the frame, like in egui's own backends,
+    /// and are dropped as described in [`TextureStore`].
+    pub fn free_textures(&mut self, textures_delta: &TexturesDelta) {
+        let mut textures = self.textures.write().unwrap();
+        textures.record_used(self.used_textures.get_mut().unwrap().drain(..));
+        textures.free_textures(&textures_delta.free);
+        textures.end_frame();
+    }
+
+    /// Uploads `image` as the texture `id`, replacing any previous texture with that id.
+    ///
+    /// See [`TextureStore::upload_texture`].
+    pub fn upload_texture(
+        &mut self,
+        id: TextureId,
+        image: &ImageData,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        let options = self.apply_forced_filter(options);
+        self.textures
+            .write()
+            .unwrap()
+            .upload_texture(id, image, options)
+    }
+
+    /// Decodes an encoded image (PNG, JPEG, WebP, ... anything skia's codecs support) and
+    /// uploads it as texture `id`.
+    pub fn upload_image_file(
+        &mut self,
+        id: TextureId,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        let image = Image::from_encoded(Data::new_copy(bytes)).ok_or(TextureError::Decode)?;
+        let options = self.apply_forced_filter(options);
+        self.textures
+            .write()
+            .unwrap()
+            .upload_image(id, image, options)
+    }
+
+    /// Replaces the region of texture `id` starting at `pos` with `image`.
+    ///
+    /// See [`TextureStore::update_texture_region`].
+    pub fn update_texture_region(
+        &mut self,
+        id: TextureId,
+        pos: [usize; 2],
+        image: &ImageData,
+    ) -> Result<(), TextureError> {
+        self.textures
+            .write()
+            .unwrap()
+            .update_texture_region(id, pos, image)
+    }
+
+    /// Clears `rect` of texture `id` to transparent.
+    ///
+    /// See [`TextureStore::free_texture_region`].
+    pub fn free_texture_region(&mut self, id: TextureId, rect: IRect) -> Result<(), TextureError> {
+        self.textures
+            .write()
+            .unwrap()
+            .free_texture_region(id, rect)
+    }
+
+    /// Sets how the pixels of texture `id` are interpreted, e.g. [`TextureColorUsage::Linear`] for
+    /// a user texture holding non-color data.
+    ///
+    /// See [`TextureStore::set_texture_color_usage`]. GPU textures are uploaded again right away.
+    pub fn set_texture_color_usage(
+        &mut self,
+        id: TextureId,
+        usage: TextureColorUsage,
+    ) -> Result<(), TextureError> {
+        let mut textures = self.textures.write().unwrap();
+        textures.set_texture_color_usage(id, usage)?;
+
+        #[cfg(feature = "gpu")]
+        if let Some(context) = self.context.get_mut().unwrap() {
+            if textures.texture_options(id).is_some() {
+                textures.upload_to_gpu(id, context)?;
+                *self.needs_flush.get_mut() = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the blend mode combining texture `id` with the vertex colors of the meshes drawing
+    /// it, e.g. [`BlendMode::Src`] for images that must never be tinted.
+    ///
+    /// See [`TextureStore::set_texture_blend_mode`].
+    pub fn set_texture_blend_mode(&mut self, id: TextureId, mode: BlendMode) {
+        self.textures
+            .write()
+            .unwrap()
+            .set_texture_blend_mode(id, mode);
+    }
+
+    /// Marks texture `id` as opaque or not, choosing whether it is sampled on the opaque fast
+    /// path.
+    ///
+    /// See [`TextureStore::set_texture_opaque`]. GPU textures are uploaded again right away.
+    pub fn set_texture_opaque(&mut self, id: TextureId, opaque: bool) -> Result<(), TextureError> {
+        let mut textures = self.textures.write().unwrap();
+        textures.set_texture_opaque(id, opaque)?;
+
+        #[cfg(feature = "gpu")]
+        if let Some(context) = self.context.get_mut().unwrap() {
+            if textures.texture_options(id).is_some() {
+                textures.upload_to_gpu(id, context)?;
+                *self.needs_flush.get_mut() = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples texture `id` through the SKSL shader `sksl`.
+    ///
+    /// See [`TextureStore::set_texture_shader`]. GPU textures are uploaded again right away.
+    pub fn set_texture_shader(&mut self, id: TextureId, sksl: &str) -> Result<(), TextureError> {
+        let mut textures = self.textures.write().unwrap();
+        textures.set_texture_shader(id, sksl)?;
+
+        #[cfg(feature = "gpu")]
+        if let Some(context) = self.context.get_mut().unwrap() {
+            if textures.texture_options(id).is_some() {
+                textures.upload_to_gpu(id, context)?;
+                *self.needs_flush.get_mut() = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples texture `id` through the SKSL shader in the file at `path`, and reloads it
+    /// whenever the file changes, for iterating on shaders without restarting.
+    ///
+    /// Changes are picked up before the next frame painted with
+    /// [`Self::paint_and_update_textures`]. If a changed shader fails to load, the texture keeps
+    /// the last working one and the error is logged and kept for [`Self::take_shader_errors`],
+    /// e.g. to show it in an in-app console. Loading the shader the first time fails with the
+    /// error instead.
+    #[cfg(feature = "shader-hot-reload")]
+    pub fn set_texture_effect_file(
+        &mut self,
+        id: TextureId,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TextureError> {
+        let path = path.as_ref();
+        self.set_texture_shader(id, &std::fs::read_to_string(path)?)?;
+        if self.shader_watcher.is_none() {
+            self.shader_watcher = Some(ShaderWatcher::new()?);
+        }
+        let target = ShaderTarget::Texture(id);
+        self.shader_watcher.as_mut().unwrap().watch(path, target)
+    }
+
+    /// Uses the SKSL color filter in the file at `path` as [`Self::set_post_process_effect`],
+    /// and reloads it whenever the file changes, like [`Self::set_texture_effect_file`].
+    #[cfg(feature = "shader-hot-reload")]
+    pub fn set_post_process_file(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), TextureError> {
+        let path = path.as_ref();
+        self.set_post_process_effect(Some(&std::fs::read_to_string(path)?))?;
+        if self.shader_watcher.is_none() {
+            self.shader_watcher = Some(ShaderWatcher::new()?);
+        }
+        let target = ShaderTarget::PostProcess;
+        self.shader_watcher.as_mut().unwrap().watch(path, target)
+    }
+
+    /// Returns the errors of the shader reloads that failed since the last call, with the files
+    /// they were loaded from.
+    #[cfg(feature = "shader-hot-reload")]
+    pub fn take_shader_errors(&mut self) -> Vec<(std::path::PathBuf, TextureError)> {
+        match &mut self.shader_watcher {
+            Some(watcher) => std::mem::take(&mut watcher.errors),
+            None => Vec::new(),
+        }
+    }
+
+    /// Reloads the shaders whose files changed.
+    #[cfg(feature = "shader-hot-reload")]
+    fn reload_shaders(&mut self) {
+        let changed = match &mut self.shader_watcher {
+            Some(watcher) => watcher.take_changed(),
+            None => return,
+        };
+        for (path, target) in changed {
+            let result = std::fs::read_to_string(&path)
+                .map_err(TextureError::from)
+                .and_then(|sksl| match target {
+                    ShaderTarget::Texture(id) => self.set_texture_shader(id, &sksl),
+                    ShaderTarget::PostProcess => self.set_post_process_effect(Some(&sksl)),
+                });
+            if let Err(err) = result {
+                log::warn!("Keeping the previous shader of {}: {}", path.display(), err);
+                let watcher = self.shader_watcher.as_mut().unwrap();
+                watcher.errors.push((path, err));
+            }
+        }
+    }
+
+    /// Replaces texture `id` with a video frame.
+    ///
+    /// With a context set by [`Self::with_direct_context`] the planes are uploaded as they are
+    /// and converted from YUV by the GPU, otherwise they are converted on the cpu. GPU frames
+    /// have no raster image, so they can't be updated partially and are dropped when the
+    /// context is lost.
+    ///
+    /// Keeps the options the texture was uploaded with, or uses linear filtering for a new
+    /// texture, so the texture id stays stable while the frames change.
+    pub fn update_video_texture(
+        &mut self,
+        id: TextureId,
+        planes: YuvPlanes<'_>,
+        color: YuvColorSpace,
+    ) -> Result<(), TextureError> {
+        let default_options = self.apply_forced_filter(TextureOptions::LINEAR);
+
+        #[cfg(feature = "gpu")]
+        {
+            self.check_context();
+            if let Some(context) = self.context.get_mut().unwrap() {
+                let texture = yuv_to_texture(&planes, color, context)?;
+                let mut textures = self.textures.write().unwrap();
+                let options = textures.texture_options(id).unwrap_or(default_options);
+                textures.insert_gpu_image(id, texture, options)?;
+                *self.needs_flush.get_mut() = true;
+                return Ok(());
+            }
+        }
+
+        let image = yuv_to_image(&planes, color)?;
+        let mut textures = self.textures.write().unwrap();
+        let options = textures.texture_options(id).unwrap_or(default_options);
+        textures.upload_image(id, image, options)
+    }
+
+    /// Draws a single [`Mesh`] onto `canvas`, going through the same code path as the meshes of
+    /// [`Self::paint_and_update_textures`].
+    ///
+    /// `clip` is in points, like the mesh vertices. Every texture referenced by the mesh must
+    /// already have been uploaded to this painter, otherwise this panics.
+    pub fn draw_mesh(&self, canvas: &mut Canvas, dpi: f32, clip: Option<egui::Rect>, mesh: &Mesh) {
+        self.prepare_textures(vec![mesh.texture_id]);
+        let mut stats = FrameStats::default();
+        self.draw_mesh_at(&mut [canvas], dpi, egui::Vec2::ZERO, clip, mesh, &mut stats);
+        let mut frame_stats = self.stats.lock().unwrap();
+        frame_stats.clip_operations += stats.clip_operations;
+        frame_stats.draw_calls += stats.draw_calls;
+    }
+
+    /// Draws `mesh` onto every canvas of `canvases`, with `origin` subtracted from its positions
+    /// and clip rect and applied as a translation instead, see [`Self::set_content_origin`].
+    fn draw_mesh_at(
+        &self,
+        canvases: &mut [&mut Canvas],
+        dpi: f32,
+        origin: egui::Vec2,
+        clip: Option<egui::Rect>,
+        mesh: &Mesh,
+        stats: &mut FrameStats,
+    ) {
+        let mut guards = canvases
+            .iter_mut()
+            .map(|canvas| {
+                let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+                arc.scale((dpi, dpi));
+                arc.translate((origin.x, origin.y));
+                if let Some(clip) = clip {
+                    let clip = clip.translate(-origin);
+                    let skclip_rect = Rect::new(clip.min.x, clip.min.y, clip.max.x, clip.max.y);
+                    self.clip_to_rect(&mut arc, skclip_rect);
+                }
+                arc
+            })
+            .collect::<Vec<_>>();
+        if clip.is_some() {
+            stats.clip_operations += 1;
+        }
+
+        let textures = self.textures.read().unwrap();
+        let texture_id = mesh.texture_id;
+        let blend_mode = textures.texture_blend_mode(texture_id);
+        // Only meshes too large for u16 indices are split, all others are drawn as they are.
+        let split;
+        let chunks: Vec<(&[Vertex], Cow<'_, [u32]>)> = if mesh.vertices.len() > u16::MAX as usize {
+            split = mesh.clone().split_to_u16();
+            split
+                .iter()
+                .map(|part| {
+                    let indices = part.indices.iter().copied().map(u32::from).collect();
+                    (&part.vertices[..], Cow::Owned(indices))
+                })
+                .collect()
+        } else {
+            vec![(&mesh.vertices[..], Cow::Borrowed(&mesh.indices[..]))]
+        };
+
+        for (mesh_vertices, mesh_indices) in &chunks {
+            let mut pos = Vec::with_capacity(mesh_vertices.len());
+            let mut texs = Vec::with_capacity(mesh_vertices.len());
+            let mut colors = Vec::with_capacity(mesh_vertices.len());
+            // Index of the first vertex of every glyph triangle, for the text outline.
+            let mut glyph_triangles = Vec::new();
+
+            let mut push_vert = |v: &egui::epaint::Vertex| {
+                // Apparently vertices can be NaN and if they are NaN, nothing is rendered.
+                // Replacing them with 0 works around this.
+                // https://github.com/lucasmerlin/egui_skia/issues/4
+                let fixed_pos = if v.pos.x.is_nan() || v.pos.y.is_nan() {
+                    Pos2::new(0.0, 0.0)
+                } else {
+                    v.pos - origin
+                };
+
+                pos.push(Point::new(fixed_pos.x, fixed_pos.y));
+                texs.push(Point::new(v.uv.x, v.uv.y));
+
+                colors.push(color32_to_skia_unmultiplied(v.color));
+            };
+
+            let mut i = 0;
+            while i < mesh_indices.len() {
+                let mut v0 = mesh_vertices[mesh_indices[i] as usize];
+                let mut v1 = mesh_vertices[mesh_indices[i + 1] as usize];
+                let mut v2 = mesh_vertices[mesh_indices[i + 2] as usize];
+                i += 3;
+
+                // Egui use the uv coordinates 0,0 to get a white color when drawing vector graphics
+                // 0,0 is always a white dot on the font texture
+                // Unfortunately skia has a bug where it cannot get a color when the uv coordinates are equal
+                // https://bugs.chromium.org/p/skia/issues/detail?id=13706
+                // As a workaround, when the uv coordinates are equal, we move the uv coordinates a little bit
+                if v0.uv == Pos2::ZERO && v1.uv == Pos2::ZERO && v2.uv == Pos2::ZERO {
+                    v1.uv = Pos2::new(0.0, 1.0 / 65536.0);
+                    v2.uv = Pos2::new(1.0 / 65536.0, 0.0);
+                    if self.geometry_snap && is_thin_axis_aligned([v0.pos, v1.pos, v2.pos], dpi) {
+                        for v in [&mut v0, &mut v1, &mut v2] {
+                            v.pos = snap_to_pixel_center(v.pos, dpi);
+                        }
+                    }
+                } else if texture_id == TextureId::default() {
+                    glyph_triangles.push(i - 3);
+                }
+
+                push_vert(&v0);
+                push_vert(&v1);
+                push_vert(&v2);
+            }
+
+            let handle = match textures.get(&texture_id) {
+                Some(handle) => handle,
+                // Text is left out until the font texture converted in the background arrives,
+                // demoted textures that can't be decoded again are left out as well.
+                None if textures.is_pending(texture_id) || textures.is_demoted(texture_id) => {
+                    continue
+                }
+                None => panic!("unknown texture {:?}", texture_id),
+            };
+            // Textures split into tiles draw every triangle from the tile holding it, the other
+            // textures draw all triangles at once.
+            let parts = if handle.tiles.is_empty() {
+                vec![(&handle.paint, None)]
+            } else {
+                let size = (handle.image.width() as f32, handle.image.height() as f32);
+                split_by_tile(&handle.tiles, size, &texs)
+                    .into_iter()
+                    .map(|(paint, triangles)| (paint, Some(triangles)))
+                    .collect()
+            };
+
+            for (mut paint, triangles) in parts {
+                let (vertices, tile_glyphs);
+                let (vertices, glyph_triangles) = match &triangles {
+                    None => {
+                        vertices =
+                            Vertices::new_copy(VertexMode::Triangles, &pos, &texs, &colors, None);
+                        (&vertices, &glyph_triangles[..])
+                    }
+                    Some(triangles) => {
+                        vertices = Vertices::new_copy(
+                            VertexMode::Triangles,
+                            &gather_triangles(&pos, triangles),
+                            &gather_triangles(&texs, triangles),
+                            &gather_triangles(&colors, triangles),
+                            None,
+                        );
+                        tile_glyphs = glyph_triangles
+                            .iter()
+                            .copied()
+                            .filter(|first| triangles.binary_search(first).is_ok())
+                            .collect::<Vec<_>>();
+                        (&vertices, &tile_glyphs[..])
+                    }
+                };
+
+                let mut dithered;
+                if self.dithering {
+                    dithered = paint.clone();
+                    dithered.set_dither(true);
+                    paint = &dithered;
+                }
+
+                if let Some(outline) = self.text_outline_paint(paint) {
+                    if !glyph_triangles.is_empty() {
+                        let outline_vertices = Vertices::new_copy(
+                            VertexMode::Triangles,
+                            &gather_triangles(&pos, glyph_triangles),
+                            &gather_triangles(&texs, glyph_triangles),
+                            &gather_triangles(&colors, glyph_triangles),
+                            None,
+                        );
+                        for arc in &mut guards {
+                            arc.draw_vertices(&outline_vertices, BlendMode::Modulate, &outline);
+                        }
+                        stats.draw_calls += 1;
+                    }
+                }
+
+                // The blend mode of draw_vertices combines the vertex colors (dst) with the
+                // paint's shader (src), it is not the blend mode used to composite onto the
+                // canvas; that is the paint's blend mode, which stays SrcOver. egui expects
+                // `texture * vertex color` for font coverage and images alike, so textures use
+                // Modulate unless the host set another mode for them.
+                for arc in &mut guards {
+                    arc.draw_vertices(vertices, blend_mode, paint);
+                }
+                stats.draw_calls += 1;
+            }
+        }
+    }
+}
+
+/// The values of the vertices of `triangles`, the indices of the first vertex of each triangle.
+fn gather_triangles<T: Copy>(values: &[T], triangles: &[usize]) -> Vec<T> {
+    triangles
+        .iter()
+        .flat_map(|&first| first..first + 3)
+        .map(|i| values[i])
+        .collect()
+}
+
+/// Splits the triangles of the uv coordinates `texs`, of a texture of `size` pixels, into runs
+/// drawn from one tile each, see [`TextureStore::with_max_atlas_size`]. Returns the paint of
+/// the tile of every run with the indices of the first vertex of its triangles, in drawing
+/// order.
+fn split_by_tile<'a>(
+    tiles: &'a [TextureTile],
+    size: (f32, f32),
+    texs: &[Point],
+) -> Vec<(&'a Paint, Vec<usize>)> {
+    let mut runs: Vec<(usize, Vec<usize>)> = Vec::new();
+    for first in (0..texs.len()).step_by(3) {
+        let uv = &texs[first..first + 3];
+        let min_x = uv.iter().map(|p| p.x).fold(f32::INFINITY, f32::min) * size.0;
+        let min_y = uv.iter().map(|p| p.y).fold(f32::INFINITY, f32::min) * size.1;
+        let max_x = uv.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max) * size.0;
+        let max_y = uv.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max) * size.1;
+        let holds_point = |tile: usize, x: f32, y: f32| {
+            let rect = tiles[tile].rect;
+            (rect.left as f32..=rect.right as f32).contains(&x)
+                && (rect.top as f32..=rect.bottom as f32).contains(&y)
+        };
+        let holds =
+            |tile: usize| holds_point(tile, min_x, min_y) && holds_point(tile, max_x, max_y);
+        // Staying on the tile of the current run keeps the runs long.
+        let tile = match runs.last() {
+            Some((tile, _)) if holds(*tile) => *tile,
+            _ => (0..tiles.len())
+                .find(|tile| holds(*tile))
+                .or_else(|| (0..tiles.len()).find(|tile| holds_point(*tile, min_x, min_y)))
+                .unwrap_or(0),
+        };
+        match runs.last_mut() {
+            Some((run_tile, triangles)) if *run_tile == tile => triangles.push(first),
+            _ => runs.push((tile, vec![first])),
+        }
+    }
+    runs
+        .into_iter()
+        .map(|(tile, triangles)| (&tiles[tile].paint, triangles))
+        .collect()
+}
+
+/// Whether the triangle `pos` is half of an axis aligned rect at most 2 pixels thin, as egui
+/// tessellates separators, borders and their anti aliasing feathers.
+fn is_thin_axis_aligned(pos: [Pos2; 3], dpi: f32) -> bool {
+    let distinct = |values: [f32; 3]| {
+        let mut count = 1;
+        if values[1] != values[0] {
+            count += 1;
+        }
+        if values[2] != values[0] && values[2] != values[1] {
+            count += 1;
+        }
+        count
+    };
+    let xs = pos.map(|p| p.x);
+    let ys = pos.map(|p| p.y);
+    if distinct(xs) != 2 || distinct(ys) != 2 {
+        return false;
+    }
+    let rect = egui::Rect::from_points(&pos);
+    rect.width().min(rect.height()) * dpi <= 2.0
+}
+
+/// `pos` moved to the nearest pixel center. Egui puts the opaque vertices of thin lines and the
+/// feather vertices of fills half a pixel from the edges, so on pixel centers they cover whole
+/// pixels.
+fn snap_to_pixel_center(pos: Pos2, dpi: f32) -> Pos2 {
+    let half = egui::vec2(0.5, 0.5);
+    (((pos.to_vec2() * dpi - half).round() + half) / dpi).to_pos2()
+}
+
+/// A paint keeping the content under it opaque between `stop` and `1 - stop` of the way from
+/// `from` to `to`, and fading it out towards both ends.
+fn feather_mask(from: Point, to: Point, stop: f32) -> Option<Paint> {
+    let colors = [Color::TRANSPARENT, Color::BLACK, Color::BLACK, Color::TRANSPARENT];
+    let stops = [0.0, stop, 1.0 - stop, 1.0];
+    let shader = gradient_shader::linear(
+        (from, to),
+        &colors[..],
+        &stops[..],
+        TileMode::Clamp,
+        None,
+        None,
+    )?;
+    let mut paint = Paint::default();
+    paint.set_shader(shader);
+    paint.set_blend_mode(BlendMode::DstIn);
+    Some(paint)
+}
+
+/// Marks `rect`, clipped to `clip`, as missing content with a translucent magenta cross.
+fn draw_placeholder(canvas: &mut Canvas, dpi: f32, clip: egui::Rect, rect: egui::Rect) {
+    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+    arc.scale((dpi, dpi));
+    let clip = Rect::new(clip.min.x, clip.min.y, clip.max.x, clip.max.y);
+    arc.clip_rect(clip, ClipOp::default(), true);
+    let rect = Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y);
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(Color::from_argb(64, 255, 0, 255));
+    arc.draw_rect(rect, &paint);
+    paint.set_color(Color::MAGENTA);
+    paint.set_style(skia_safe::PaintStyle::Stroke);
+    paint.set_stroke_width(1.0);
+    arc.draw_rect(rect, &paint);
+    arc.draw_line((rect.left, rect.top), (rect.right, rect.bottom), &paint);
+    arc.draw_line((rect.right, rect.top), (rect.left, rect.bottom), &paint);
+}
+
+/// The color filter of the SKSL `sksl`, see [`Painter::set_post_process_effect`].
+fn post_process_filter(sksl: &str) -> Result<ColorFilter, TextureError> {
+    let effect =
+        RuntimeEffect::make_for_color_filer(sksl, None).map_err(TextureError::ShaderCreation)?;
+    if effect.uniform_size() != 0 || !effect.children().is_empty() {
+        return Err(TextureError::ShaderCreation(
+            "the post-process effect must not declare uniforms or children".into(),
+        ));
+    }
+    let children = None::<&[skia_safe::runtime_effect::ChildPtr]>;
+    effect
+        .make_color_filter(Data::new_empty(), children)
+        .ok_or_else(|| TextureError::ShaderCreation("failed to create the color filter".into()))
+}
+
+/// A half float surface in linear sRGB with the size, matrix and clip of `canvas`, to paint a
+/// frame into with blending in linear light. `None` if `canvas` can't create surfaces.
+fn linear_layer(canvas: &mut Canvas) -> Option<Surface> {
+    let size = canvas.base_layer_size();
+    if size.is_empty() {
+        return None;
+    }
+    let info = ImageInfo::new(
+        size,
+        ColorType::RGBAF16,
+        AlphaType::Premul,
+        ColorSpace::new_srgb_linear(),
+    );
+    let mut surface = canvas.new_surface(&info, None)?;
+    let layer = surface.canvas();
+    layer.clip_irect(canvas.device_clip_bounds()?, ClipOp::Intersect);
+    layer.set_matrix(&canvas.local_to_device());
+    Some(surface)
+}
+
+/// The textures of the meshes of `primitives`.
+fn mesh_texture_ids(primitives: &[ClippedPrimitive]) -> Vec<TextureId> {
+    primitives
+        .iter()
+        .filter_map(|primitive| match &primitive.primitive {
+            Primitive::Mesh(mesh) => Some(mesh.texture_id),
+            Primitive::Callback(_) => None,
+        })
+        .collect()
+}
+
+/// Whether `clip`, in points, is outside the clip of every canvas of `canvases`.
+fn is_culled(canvases: &mut [&mut Canvas], dpi: f32, clip: egui::Rect) -> bool {
+    let clip = Rect::new(clip.min.x, clip.min.y, clip.max.x, clip.max.y);
+    canvases.iter_mut().all(|canvas| {
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.scale((dpi, dpi));
+        arc.quick_reject(clip)
+    })
+}
+
+/// Draws `content` of `size` points into an image with `pixels_per_point`.
+fn rasterize_content(
+    content: &mut CallbackContent,
+    size: egui::Vec2,
+    pixels_per_point: f32,
+) -> Option<Image> {
+    let pixels = (size * pixels_per_point).ceil();
+    let mut surface =
+        Surface::new_raster_n32_premul((pixels.x.max(1.0) as i32, pixels.y.max(1.0) as i32))?;
+    let canvas = surface.canvas();
+    canvas.scale((pixels_per_point, pixels_per_point));
+    content.draw(canvas, Rect::from_wh(size.x, size.y));
+    Some(surface.image_snapshot())
+}
+
+/// Annotates the clip rect of `primitive` with its index, texture and clip rect, see
+/// [`Painter::with_debug_annotations`].
+fn annotate_primitive(canvas: &mut Canvas, dpi: f32, index: usize, primitive: &ClippedPrimitive) {
+    let texture = match &primitive.primitive {
+        Primitive::Mesh(mesh) => format!("{:?}", mesh.texture_id),
+        Primitive::Callback(_) => "callback".to_string(),
+    };
+    let clip = primitive.clip_rect;
+    let value = format!(
+        "index={};texture={};clip=[{} {} {} {}]",
+        index, texture, clip.min.x, clip.min.y, clip.max.x, clip.max.y
+    );
+
+    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+    arc.scale((dpi, dpi));
+    arc.draw_annotation(
+        Rect::new(clip.min.x, clip.min.y, clip.max.x, clip.max.y),
+        "egui_skia.primitive",
+        &Data::new_copy(value.as_bytes()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, Color32, ColorImage};
+
+    use super::*;
+    #[cfg(feature = "gl")]
+    use crate::test_util::gl_context;
+    #[cfg(all(feature = "metal", target_os = "macos"))]
+    use crate::test_util::metal_context;
+    use crate::test_util::{
+        assert_color_near, callback_primitive, image_pixel, mesh_primitive, painter, pixel,
+        rect_mesh, rect_primitive, surface, surface_bytes,
+    };
+    #[cfg(feature = "gpu")]
+    use crate::test_util::{read_back, render_target};
+
+    fn rect(min: (f32, f32), max: (f32, f32)) -> egui::Rect {
+        egui::Rect::from_min_max(pos2(min.0, min.1), pos2(max.0, max.1))
+    }
+
+    #[test]
+    fn host_save_layer_alpha_applies_group_opacity() {
+        let painter = painter();
+        let mut surface = surface(20, 20);
+        let canvas = surface.canvas();
+        canvas.save_layer_alpha(None, 128);
+        canvas.translate((5.0, 5.0));
+        let save_count = canvas.save_count();
+
+        let primitive = rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED);
+        painter.paint_primitives(canvas, 1.0, &[primitive]);
+
+        // The layer, matrix and clip of the host are left as they were.
+        assert_eq!(canvas.save_count(), save_count);
+        assert_eq!(canvas.local_to_device_as_3x3(), skia_safe::Matrix::translate((5.0, 5.0)));
+        assert_eq!(canvas.device_clip_bounds(), Some(IRect::from_wh(20, 20)));
+        canvas.restore();
+
+        assert_color_near(pixel(&mut surface, 10, 10), Color::from_argb(128, 255, 0, 0), 2);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn draw_mesh_textured_quad() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let image = ColorImage::from_rgba_unmultiplied([2, 1], &[255, 0, 0, 255, 0, 0, 255, 255]);
+        painter
+            .upload_texture(id, &ImageData::Color(image), TextureOptions::NEAREST)
+            .unwrap();
+
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (20.0, 10.0)), uv, Color32::WHITE);
+        let mut surface = surface(20, 10);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+
+        assert_color_near(pixel(&mut surface, 4, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 15, 5), Color::BLUE, 0);
+    }
+
+    #[test]
+    fn forced_linear_filter_smooths_nearest_textures() {
+        // A red and a blue texel stretched over 20 pixels, sampled where they meet.
+        let paint = |mut painter: Painter| {
+            let id = TextureId::User(1);
+            let texels = [255, 0, 0, 255, 0, 0, 255, 255];
+            let image = ColorImage::from_rgba_unmultiplied([2, 1], &texels);
+            painter
+                .upload_texture(id, &ImageData::Color(image), TextureOptions::NEAREST)
+                .unwrap();
+            let mut mesh = Mesh::with_texture(id);
+            let uv = rect((0.0, 0.0), (1.0, 1.0));
+            mesh.add_rect_with_uv(rect((0.0, 0.0), (20.0, 10.0)), uv, Color32::WHITE);
+            let mut surface = surface(20, 10);
+            painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+            pixel(&mut surface, 9, 5)
+        };
+
+        assert_color_near(paint(Painter::new()), Color::RED, 0);
+        let smooth = paint(Painter::new().with_forced_filter(Some(TextureFilter::Linear)));
+        assert!(smooth.r() > 100 && smooth.b() > 80, "{:?} isn't a blend", smooth);
+    }
+
+    /// A 2x2 RGBA png: red and green on the first row, blue and white on the second.
+    const PNG_2X2: [u8; 75] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x72,
+        0xb6, 0x0d, 0x24, 0x00, 0x00, 0x00, 0x12, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xf8,
+        0xcf, 0xc0, 0xf0, 0x1f, 0x0c, 0x81, 0x34, 0x18, 0x00, 0x00, 0x49, 0xc8, 0x09, 0xf7, 0x03,
+        0xd9, 0x64, 0xf1, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    fn upload_image_file_decodes_and_draws_a_png() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        painter
+            .upload_image_file(id, &PNG_2X2, TextureOptions::NEAREST)
+            .unwrap();
+
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (20.0, 20.0)), uv, Color32::WHITE);
+        let mut surface = surface(20, 20);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 15, 5), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 5, 15), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 15, 15), Color::WHITE, 0);
+    }
+
+    #[test]
+    fn upload_image_file_rejects_undecodable_bytes() {
+        let mut painter = painter();
+        let bytes = b"not an image";
+        let result = painter.upload_image_file(TextureId::User(1), bytes, TextureOptions::NEAREST);
+        assert!(matches!(result, Err(TextureError::Decode)));
+    }
+
+    #[test]
+    fn draw_mesh_flat_triangle() {
+        let painter = painter();
+        let vertex = |x, y| Vertex {
+            pos: pos2(x, y),
+            uv: Pos2::ZERO,
+            color: Color32::GREEN,
+        };
+        let mesh = Mesh {
+            indices: vec![0, 1, 2],
+            vertices: vec![vertex(0.0, 0.0), vertex(10.0, 0.0), vertex(0.0, 10.0)],
+            texture_id: TextureId::default(),
+        };
+        let mut surface = surface(20, 20);
+        let clip = rect((0.0, 0.0), (5.0, 10.0));
+        painter.draw_mesh(surface.canvas(), 2.0, Some(clip), &mesh);
+
+        // The triangle is scaled by the dpi and clipped to the left half.
+        assert_color_near(pixel(&mut surface, 3, 3), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 12, 2), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 8, 18), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 18, 18), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn draw_mesh_splits_meshes_too_large_for_u16_indices() {
+        let painter = painter();
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED);
+        while mesh.vertices.len() < 70_000 {
+            mesh.add_colored_rect(rect((-2.0, -2.0), (-1.0, -1.0)), Color32::GREEN);
+        }
+        mesh.add_colored_rect(rect((10.0, 0.0), (20.0, 10.0)), Color32::BLUE);
+        let mut surface = surface(20, 10);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 15, 5), Color::BLUE, 0);
+    }
+
+    #[test]
+    fn update_texture_region_replaces_part_of_an_uploaded_texture() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let red = ImageData::Color(ColorImage::new([2, 1], Color32::RED));
+        let blue = ImageData::Color(ColorImage::new([1, 1], Color32::BLUE));
+        assert!(matches!(
+            painter.update_texture_region(id, [1, 0], &blue),
+            Err(TextureError::UnknownTexture(_))
+        ));
+        painter.upload_texture(id, &red, TextureOptions::NEAREST).unwrap();
+        painter.update_texture_region(id, [1, 0], &blue).unwrap();
+
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (20.0, 10.0)), uv, Color32::WHITE);
+        let mut surface = surface(20, 10);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+
+        assert_color_near(pixel(&mut surface, 4, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 15, 5), Color::BLUE, 0);
+    }
+
+    fn draw_translucent_blue_image(painter: &mut Painter, tint: Color32) -> Color {
+        let id = TextureId::User(1);
+        let image = ColorImage::new([1, 1], Color32::from_rgba_unmultiplied(0, 0, 255, 128));
+        painter
+            .upload_texture(id, &ImageData::Color(image), TextureOptions::NEAREST)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (10.0, 10.0)), rect((0.0, 0.0), (1.0, 1.0)), tint);
+        let mut surface = surface(10, 10);
+        surface.canvas().clear(Color::RED);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+        pixel(&mut surface, 5, 5)
+    }
+
+    #[test]
+    fn translucent_images_composite_src_over() {
+        let mut painter = painter();
+        // Modulate onto the canvas would give black, the blue image multiplied by the red canvas.
+        let color = draw_translucent_blue_image(&mut painter, Color32::WHITE);
+        assert_color_near(color, Color::from_rgb(127, 0, 128), 2);
+    }
+
+    #[test]
+    fn texture_blend_mode_src_ignores_the_vertex_colors() {
+        let mut painter = painter();
+        painter.set_texture_blend_mode(TextureId::User(1), BlendMode::Src);
+        let color = draw_translucent_blue_image(&mut painter, Color32::GREEN);
+        assert_color_near(color, Color::from_rgb(127, 0, 128), 2);
+    }
+
+    #[test]
+    fn textures_modulate_the_vertex_colors_by_default() {
+        let mut painter = painter();
+        let color = draw_translucent_blue_image(&mut painter, Color32::GREEN);
+        // Modulated with green, the blue image is black and half transparent.
+        assert_color_near(color, Color::from_rgb(127, 0, 0), 2);
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn warmup_leaves_the_texture_store_alone() {
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 16, 16, 0);
+        let mut painter = painter();
+        let id = TextureId::User(u64::MAX);
+        crate::test_util::upload_white(&mut painter, id);
+
+        painter.warmup(&mut context, &mut target).unwrap();
+
+        {
+            let textures = painter.texture_store().read().unwrap();
+            assert!(textures.texture_options(id).is_some());
+            assert_eq!(textures.stats().textures, 2);
+        }
+        // The quad is drawn through the runtime effect of the store, not a plain image shader.
+        let store = painter.warmup_store(&mut context).unwrap();
+        let paint = &store.get(&TextureId::default()).unwrap().paint;
+        assert!(!paint.shader().unwrap().is_a_image());
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn frames_are_submitted_once_when_flushed() {
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 16, 16, 0);
+        // The flushes while painting hand the work to the driver without submitting it.
+        let mut painter = Painter::new()
+            .with_direct_context(context)
+            .with_flush_interval(Some(1));
+        assert!(!painter.needs_flush());
+
+        for _ in 0..2 {
+            let mut delta = TexturesDelta::default();
+            let image = ImageData::Color(ColorImage::new([1, 1], Color32::WHITE));
+            delta
+                .set
+                .push((TextureId::default(), ImageDelta::full(image, TextureOptions::NEAREST)));
+            // Each primitive has its own clip rect, so they aren't batched.
+            let primitives = [rect((0.0, 0.0), (8.0, 8.0)), rect((8.0, 8.0), (16.0, 16.0))]
+                .map(|cell| mesh_primitive(cell, rect_mesh(cell, Color32::RED)))
+                .to_vec();
+            painter.paint_and_update_textures(target.canvas(), 1.0, primitives, delta);
+            assert!(painter.needs_flush());
+            assert_eq!(painter.frame_stats().gpu_flushes, 1);
+            assert_eq!(painter.frame_stats().gpu_submissions, 0);
+
+            painter.flush(FlushSync::SyncCpu);
+            assert!(!painter.needs_flush());
+            assert_eq!(painter.frame_stats().gpu_submissions, 1);
+        }
+        assert!(painter.texture_store().read().unwrap().is_on_gpu(TextureId::default()));
+        assert_color_near(pixel(&mut read_back(&mut target), 4, 4), Color::RED, 0);
+        painter.release_gpu_resources();
+    }
+
+    #[cfg(all(feature = "metal", target_os = "macos"))]
+    #[test]
+    fn textures_are_uploaded_and_drawn_on_metal() {
+        let mut context = match metal_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 16, 16, 0);
+        let mut painter = Painter::new().with_direct_context(context);
+        let id = TextureId::User(1);
+        let mut delta = TexturesDelta::default();
+        for (texture, color) in [(TextureId::default(), Color32::WHITE), (id, Color32::BLUE)] {
+            let image = ImageData::Color(ColorImage::new([2, 2], color));
+            let options = TextureOptions::NEAREST;
+            delta.set.push((texture, ImageDelta::full(image, options)));
+        }
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((8.0, 0.0), (16.0, 16.0)), uv, Color32::WHITE);
+        let primitives = vec![
+            rect_primitive(rect((0.0, 0.0), (8.0, 16.0)), Color32::RED),
+            mesh_primitive(egui::Rect::EVERYTHING, mesh),
+        ];
+        painter.paint_and_update_textures(target.canvas(), 1.0, primitives, delta);
+        painter.flush(FlushSync::SyncCpu);
+
+        let textures = painter.texture_store().read().unwrap();
+        assert!(textures.is_on_gpu(TextureId::default()) && textures.is_on_gpu(id));
+        drop(textures);
+        let mut readback = read_back(&mut target);
+        assert_color_near(pixel(&mut readback, 4, 8), Color::RED, 0);
+        assert_color_near(pixel(&mut readback, 12, 8), Color::BLUE, 0);
+        painter.release_gpu_resources();
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn painter_outlives_an_abandoned_context() {
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 16, 16, 0);
+        let mut painter = Painter::new().with_direct_context(context.clone());
+        let primitive = rect_primitive(rect((0.0, 0.0), (8.0, 8.0)), Color32::RED);
+        let mut delta = TexturesDelta::default();
+        let image = ImageData::Color(ColorImage::new([1, 1], Color32::WHITE));
+        delta
+            .set
+            .push((TextureId::default(), ImageDelta::full(image, TextureOptions::NEAREST)));
+        painter.paint_and_update_textures(target.canvas(), 1.0, vec![primitive.clone()], delta);
+        painter.flush(FlushSync::SyncCpu);
+        assert!(painter.texture_store().read().unwrap().is_on_gpu(TextureId::default()));
+
+        // The device is lost and the window's surface and context are destroyed first.
+        context.abandon();
+        drop(target);
+        drop(context);
+
+        // Painting degrades to the raster copies of the textures instead of crashing.
+        let mut surface = surface(16, 16);
+        let delta = TexturesDelta::default();
+        painter.paint_and_update_textures(surface.canvas(), 1.0, vec![primitive], delta);
+        assert_color_near(pixel(&mut surface, 4, 4), Color::RED, 0);
+        assert!(!painter.needs_flush());
+        drop(painter);
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn flush_interval_flushes_every_n_primitives() {
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        let mut target = render_target(&mut context, 40, 10, 0);
+        // Each primitive has its own clip rect, so they aren't batched.
+        let primitives = (0..10)
+            .map(|i| {
+                let cell = rect((i as f32 * 4.0, 0.0), (i as f32 * 4.0 + 4.0, 10.0));
+                mesh_primitive(cell, rect_mesh(cell, Color32::RED))
+            })
+            .collect::<Vec<_>>();
+
+        let mut painter = painter().with_direct_context(context);
+        painter.paint_primitives(target.canvas(), 1.0, &primitives);
+        assert_eq!(painter.frame_stats().gpu_flushes, 0);
+
+        painter = painter.with_flush_interval(Some(3));
+        painter.paint_primitives(target.canvas(), 1.0, &primitives);
+        // Before the 4th, 7th and 10th primitive.
+        assert_eq!(painter.frame_stats().gpu_flushes, 3);
+        painter.flush(FlushSync::SyncCpu);
+        painter.release_gpu_resources();
+    }
+
+    #[test]
+    fn hooks_run_in_order_beneath_and_above_the_widgets() {
+        let mut painter = painter();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let log = |name: &'static str| {
+            let calls = calls.clone();
+            move || calls.lock().unwrap().push(name)
+        };
+        let (before, around, after) = (log("before"), log("around"), log("after"));
+        painter.set_hooks(PaintHooks {
+            before_frame: Some(Box::new(move |canvas| {
+                before();
+                canvas.draw_color(Color::BLUE, None);
+            })),
+            after_frame: Some(Box::new(move |canvas| {
+                after();
+                let mut paint = Paint::default();
+                paint.set_color(Color::GREEN);
+                canvas.draw_rect(Rect::from_ltrb(15.0, 0.0, 20.0, 20.0), &paint);
+            })),
+            around_callback: Some(Box::new(move |canvas, _, draw| {
+                around();
+                draw(canvas);
+            })),
+        });
+
+        let callback = EguiSkiaPaintCallback::new(|canvas| {
+            canvas.draw_color(Color::YELLOW, None);
+        });
+        let primitives = [
+            rect_primitive(rect((5.0, 5.0), (20.0, 20.0)), Color32::RED),
+            callback_primitive(rect((0.0, 15.0), (5.0, 20.0)), callback),
+        ];
+        let mut surface = surface(20, 20);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        assert_eq!(*calls.lock().unwrap(), ["before", "around", "after"]);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 10, 10), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 17, 10), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 2, 17), Color::YELLOW, 0);
+    }
+
+    #[test]
+    fn backdrop_blur_blurs_only_the_region_and_keeps_it() {
+        let painter = painter().with_output_options(OutputOptions {
+            clear_color: Some(Color::TRANSPARENT),
+            ..Default::default()
+        });
+        let mut surface = surface(40, 20);
+        let canvas = surface.canvas();
+        canvas.clear(Color::WHITE);
+        let mut black = Paint::default();
+        black.set_color(Color::BLACK);
+        canvas.draw_rect(Rect::from_wh(20.0, 20.0), &black);
+
+        let region = rect((10.0, 0.0), (30.0, 10.0));
+        painter.paint_with_backdrop_blur(surface.canvas(), 1.0, region, 3.0, &[]);
+
+        // The edge between black and white is blurred inside the region and sharp below it.
+        for x in [19, 20] {
+            let gray = pixel(&mut surface, x, 5);
+            assert_eq!(gray.a(), 255);
+            assert!((40..=215).contains(&gray.r()), "{:?} is not blurred", gray);
+        }
+        assert_color_near(pixel(&mut surface, 19, 15), Color::BLACK, 0);
+        assert_color_near(pixel(&mut surface, 20, 15), Color::WHITE, 0);
+    }
+
+    #[test]
+    fn max_draw_calls_caps_the_draw_calls_without_changing_the_output() {
+        // A grid of small meshes, each with its own clip rect so they aren't batched anyway.
+        let primitives = (0..16)
+            .map(|i| {
+                let min = pos2((i % 4) as f32 * 5.0, (i / 4) as f32 * 5.0);
+                let cell = egui::Rect::from_min_size(min, egui::vec2(5.0, 5.0));
+                let color = Color32::from_rgb(i * 16, 255 - i * 16, 128);
+                mesh_primitive(cell, rect_mesh(cell, color))
+            })
+            .collect::<Vec<_>>();
+        let paint = |painter: &Painter| {
+            let mut surface = surface(20, 20);
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            (surface_bytes(&mut surface), painter.frame_stats().draw_calls)
+        };
+
+        let (uncapped, uncapped_calls) = paint(&painter());
+        let (capped, capped_calls) = paint(&painter().with_max_draw_calls(Some(4)));
+        assert_eq!(uncapped_calls, 16);
+        assert_eq!(capped_calls, 4);
+        assert_eq!(capped, uncapped);
+    }
+
+    #[test]
+    fn watermark_is_drawn_over_the_content_in_the_anchor_corner() {
+        let painter = painter();
+        let watermark = crate::test_util::solid_image(4, 4, Color::BLUE);
+        let primitives = [rect_primitive(rect((0.0, 0.0), (20.0, 20.0)), Color32::RED)];
+        let mut surface = surface(20, 20);
+        let anchor = egui::Align2::RIGHT_BOTTOM;
+        painter.paint_with_watermark(surface.canvas(), 1.0, &primitives, &watermark, anchor, 0.5);
+
+        // Half transparent blue over red in the bottom right corner, the content elsewhere.
+        let blended = Color::from_rgb(127, 0, 128);
+        assert_color_near(pixel(&mut surface, 17, 17), blended, 2);
+        assert_color_near(pixel(&mut surface, 19, 19), blended, 2);
+        assert_color_near(pixel(&mut surface, 15, 19), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::RED, 0);
+    }
+
+    #[test]
+    fn watermarks_stay_in_the_canvas_corner_under_a_host_transform() {
+        let painter = painter();
+        let watermark = crate::test_util::solid_image(4, 4, Color::BLUE);
+        let primitives = [rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED)];
+        let mut surface = surface(20, 20);
+        surface.canvas().translate((5.0, 5.0));
+        let anchor = egui::Align2::RIGHT_BOTTOM;
+        painter.paint_with_watermark(surface.canvas(), 1.0, &primitives, &watermark, anchor, 1.0);
+
+        // The ui follows the translation, the watermark stays in the corner of the canvas.
+        assert_color_near(pixel(&mut surface, 7, 7), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::TRANSPARENT, 0);
+        assert_color_near(pixel(&mut surface, 17, 17), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 19, 19), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 15, 15), Color::TRANSPARENT, 0);
+        // The matrix of the host is kept.
+        let matrix = surface.canvas().local_to_device_as_3x3();
+        assert_eq!(matrix, skia_safe::Matrix::translate((5.0, 5.0)));
+    }
+
+    #[test]
+    fn clip_stats_count_the_clips_of_a_window_with_scroll_areas() {
+        let window = rect((0.0, 0.0), (100.0, 100.0));
+        let scroll_areas = [
+            rect((10.0, 10.0), (40.0, 40.0)),
+            rect((50.0, 10.0), (90.0, 40.0)),
+            rect((10.0, 50.0), (90.0, 90.0)),
+        ];
+        let mut primitives = vec![mesh_primitive(window, rect_mesh(window, Color32::GRAY))];
+        for area in scroll_areas {
+            // Two rows per scroll area, which are batched into one draw.
+            let row = rect((area.min.x, area.min.y), (area.max.x, area.min.y + 5.0));
+            primitives.push(mesh_primitive(area, rect_mesh(row, Color32::RED)));
+            let row = row.translate(egui::vec2(0.0, 10.0));
+            primitives.push(mesh_primitive(area, rect_mesh(row, Color32::BLUE)));
+        }
+        // The scroll bars, drawn by the window again.
+        let bar = rect((95.0, 0.0), (100.0, 100.0));
+        primitives.push(mesh_primitive(window, rect_mesh(bar, Color32::WHITE)));
+
+        let painter = painter();
+        let mut surface = surface(100, 100);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        let stats = painter.frame_stats();
+        assert_eq!(stats.batched_meshes, 3);
+        assert_eq!(stats.clip_operations, 5);
+        assert_eq!(stats.unique_clip_rects, 4);
+        assert_eq!(stats.largest_clip_area, 100.0 * 100.0);
+    }
+
+    #[test]
+    fn post_process_effects_filter_the_frame_after_the_saturation() {
+        let mut painter = painter().with_saturation(0.0);
+        let swap = "half4 main(half4 color) { return color.bgra; }";
+        painter.set_post_process_effect(Some(swap)).unwrap();
+        let primitive = rect_primitive(rect((0.0, 0.0), (4.0, 4.0)), Color32::RED);
+        let mut surface = surface(4, 4);
+        painter.paint_primitives(surface.canvas(), 1.0, &[primitive.clone()]);
+        // Gray stays gray when its channels are swapped, the red of the rect would turn blue.
+        let color = pixel(&mut surface, 2, 2);
+        let gray = color.r().abs_diff(color.b()) <= 1;
+        assert!(gray, "{:?} is not gray", color);
+
+        let mut painter = painter.with_saturation(1.0);
+        painter.paint_primitives(surface.canvas(), 1.0, &[primitive.clone()]);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::BLUE, 0);
+
+        let uniform = "uniform half4 tint; half4 main(half4 color) { return tint; }";
+        assert!(painter.set_post_process_effect(Some(uniform)).is_err());
+        painter.set_post_process_effect(None).unwrap();
+        painter.paint_primitives(surface.canvas(), 1.0, &[primitive]);
+        assert_color_near(pixel(&mut surface, 2, 2), Color::RED, 0);
+    }
+
+    #[test]
+    fn saturation_zero_paints_in_grayscale() {
+        let colors = [Color32::RED, Color32::GREEN, Color32::BLUE, Color32::GOLD];
+        let primitives = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let min = i as f32 * 5.0;
+                rect_primitive(rect((min, 0.0), (min + 5.0, 5.0)), *color)
+            })
+            .collect::<Vec<_>>();
+
+        let painter = painter().with_saturation(0.0);
+        let mut surface = surface(20, 5);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        let mut lightness = Vec::new();
+        for i in 0..colors.len() as i32 {
+            let color = pixel(&mut surface, i * 5 + 2, 2);
+            assert_eq!(color.a(), 255);
+            assert!(color.r().abs_diff(color.g()) <= 1, "{:?} is not gray", color);
+            assert!(color.g().abs_diff(color.b()) <= 1, "{:?} is not gray", color);
+            lightness.push(color.g());
+        }
+        // The colors keep their different luminance.
+        assert!(lightness[1] > lightness[0] && lightness[0] > lightness[2]);
+    }
+
+    #[test]
+    fn content_origin_far_away_paints_like_content_at_zero() {
+        // A clipped rect, a triangle and a callback, shifted by `offset` points.
+        let frame = |offset: f32| {
+            let shift = egui::vec2(offset, 0.0);
+            let clip = rect((1.0, 1.0), (30.0, 19.0)).translate(shift);
+            let triangle = Mesh {
+                indices: vec![0, 1, 2],
+                vertices: [(2.25, 2.5), (17.75, 4.0), (6.5, 17.25)]
+                    .map(|(x, y)| Vertex {
+                        pos: pos2(x + offset, y),
+                        uv: Pos2::ZERO,
+                        color: Color32::GREEN,
+                    })
+                    .to_vec(),
+                texture_id: TextureId::default(),
+            };
+            let callback = crate::EguiSkiaPaintCallback::with_bounds(|canvas, bounds| {
+                let mut paint = Paint::default();
+                paint.set_color(Color::BLUE);
+                canvas.draw_rect(bounds, &paint);
+            });
+            let band = rect((0.0, 0.0), (40.0, 10.0)).translate(shift);
+            vec![
+                mesh_primitive(clip, rect_mesh(band, Color32::RED)),
+                mesh_primitive(clip, triangle),
+                callback_primitive(rect((32.0, 2.0), (38.0, 18.0)).translate(shift), callback),
+            ]
+        };
+        let dpi = 2.0;
+
+        let mut painter = painter();
+        let mut at_zero = surface(80, 40);
+        painter.paint_primitives(at_zero.canvas(), dpi, &frame(0.0));
+
+        let offset = 4e6;
+        painter.set_content_origin(ContentOrigin::Point(pos2(offset, 0.0)));
+        let mut far_away = surface(80, 40);
+        // The host scrolls the content back into view.
+        far_away.canvas().translate((-offset * dpi, 0.0));
+        painter.paint_primitives(far_away.canvas(), dpi, &frame(offset));
+
+        assert_color_near(pixel(&mut at_zero, 70, 20), Color::BLUE, 0);
+        assert!(surface_bytes(&mut far_away) == surface_bytes(&mut at_zero));
+    }
+
+    #[test]
+    fn pictures_recorded_on_one_thread_replay_identically_on_another() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let image = ColorImage::from_rgba_unmultiplied([2, 1], &[255, 0, 0, 255, 0, 0, 255, 128]);
+        painter
+            .upload_texture(id, &ImageData::Color(image), TextureOptions::LINEAR)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((2.0, 2.0), (18.0, 10.0)), uv, Color32::WHITE);
+        let callback = crate::EguiSkiaPaintCallback::with_bounds(|canvas, bounds| {
+            let mut paint = Paint::default();
+            paint.set_anti_alias(true);
+            paint.set_color(Color::GREEN);
+            canvas.draw_oval(bounds, &paint);
+        });
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (20.0, 20.0)), Color32::from_gray(40)),
+            mesh_primitive(rect((0.0, 0.0), (15.0, 20.0)), mesh),
+            callback_primitive(rect((4.0, 11.0), (16.0, 19.0)), callback),
+        ];
+
+        let mut direct = surface(40, 40);
+        painter.paint_primitives(direct.canvas(), 2.0, &primitives);
+
+        let picture = painter.record_picture(Rect::from_wh(40.0, 40.0), 2.0, &primitives);
+        let replayed = std::thread::spawn(move || {
+            let mut replayed = surface(40, 40);
+            replayed.canvas().draw_picture(picture.unwrap(), None, None);
+            surface_bytes(&mut replayed)
+        })
+        .join()
+        .unwrap();
+
+        assert!(replayed == surface_bytes(&mut direct));
+    }
+
+    #[test]
+    fn one_frame_paints_identically_from_two_threads() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let image = ColorImage::from_rgba_unmultiplied([2, 1], &[255, 0, 0, 255, 0, 0, 255, 128]);
+        painter
+            .upload_texture(id, &ImageData::Color(image), TextureOptions::LINEAR)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((2.0, 2.0), (38.0, 18.0)), uv, Color32::WHITE);
+        let translucent = Color32::from_rgba_premultiplied(0, 80, 0, 128);
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (40.0, 40.0)), Color32::from_gray(40)),
+            mesh_primitive(rect((0.0, 0.0), (30.0, 40.0)), mesh),
+            rect_primitive(rect((5.0, 25.0), (35.0, 35.0)), translucent),
+        ];
+
+        // The screen and a recording surface, painted at the same time.
+        let (screen, recording) = std::thread::scope(|scope| {
+            let paint = || {
+                let mut surface = surface(80, 80);
+                for _ in 0..20 {
+                    painter.paint_primitives(surface.canvas(), 2.0, &primitives);
+                }
+                surface_bytes(&mut surface)
+            };
+            let screen = scope.spawn(paint);
+            let recording = scope.spawn(paint);
+            (screen.join().unwrap(), recording.join().unwrap())
+        });
+
+        let mut reference = surface(80, 80);
+        for _ in 0..20 {
+            painter.paint_primitives(reference.canvas(), 2.0, &primitives);
+        }
+        assert!(screen == recording);
+        assert!(screen == surface_bytes(&mut reference));
+    }
+
+    #[test]
+    fn clip_expand_keeps_the_feather_of_panel_edges() {
+        // A panel filling its clip rect, with egui's one point anti aliasing feather fading out
+        // beyond the right edge of the clip rect.
+        let mut mesh = Mesh::default();
+        let transparent = Color32::TRANSPARENT;
+        for (x, color) in [(0.0, Color32::RED), (10.0, Color32::RED), (11.0, transparent)] {
+            mesh.colored_vertex(pos2(x, 0.0), color);
+            mesh.colored_vertex(pos2(x, 10.0), color);
+        }
+        for quad in [0, 2] {
+            mesh.add_triangle(quad, quad + 1, quad + 2);
+            mesh.add_triangle(quad + 1, quad + 2, quad + 3);
+        }
+        let primitive = mesh_primitive(rect((0.0, 0.0), (10.0, 10.0)), mesh);
+        let edge = |painter: Painter| {
+            let mut surface = surface(20, 10);
+            painter.paint_primitives(surface.canvas(), 1.0, &[primitive.clone()]);
+            pixel(&mut surface, 10, 5).a()
+        };
+
+        assert_eq!(edge(painter()), 0);
+        let feather = edge(painter().with_clip_expand(1.0));
+        assert!((100..=155).contains(&feather), "alpha {}", feather);
+    }
+
+    #[test]
+    fn render_to_bytes_round_trips_through_png() {
+        let red = rect_primitive(rect((10.0, 5.0), (30.0, 15.0)), Color32::RED);
+        let bytes = painter()
+            .render_to_bytes(
+                (40, 20),
+                1.0,
+                EncodedImageFormat::PNG,
+                100,
+                vec![red],
+                TexturesDelta::default(),
+            )
+            .unwrap();
+
+        let image = Image::from_encoded(Data::new_copy(&bytes))
+            .and_then(|image| image.new_raster_image())
+            .unwrap();
+        assert_eq!((image.width(), image.height()), (40, 20));
+        assert_color_near(image_pixel(&image, 20, 10), Color::RED, 0);
+        assert_eq!(image_pixel(&image, 2, 2).a(), 0);
+    }
+
+    #[test]
+    fn geometry_snap_draws_separators_on_whole_pixels() {
+        // A 1px separator centered on a pixel boundary, as egui tessellates it.
+        let mut tessellator = egui::epaint::Tessellator::new(
+            1.0,
+            egui::epaint::TessellationOptions::default(),
+            [1, 1],
+            Vec::new(),
+        );
+        let mut mesh = Mesh::default();
+        let stroke = egui::Stroke::new(1.0, Color32::BLACK);
+        tessellator.tessellate_line([pos2(0.0, 5.0), pos2(20.0, 5.0)], stroke, &mut mesh);
+        let primitive = mesh_primitive(egui::Rect::EVERYTHING, mesh);
+        let rows = |painter: Painter| {
+            let mut surface = surface(20, 10);
+            painter.paint_primitives(surface.canvas(), 1.0, &[primitive.clone()]);
+            [4, 5, 6].map(|y| pixel(&mut surface, 10, y).a())
+        };
+
+        let [above, below, _] = rows(painter());
+        assert!((100..=155).contains(&above) && (100..=155).contains(&below));
+        assert_eq!(rows(painter().with_geometry_snap(true)), [0, 255, 0]);
+    }
+
+    #[test]
+    fn debug_annotations_are_serialized_into_the_skp() {
+        // Different clip rects, so the primitives aren't batched into one draw.
+        let top = rect((0.0, 0.0), (40.0, 10.0));
+        let bottom = rect((0.0, 10.0), (40.0, 20.0));
+        let primitives = [
+            mesh_primitive(top, rect_mesh(top, Color32::RED)),
+            mesh_primitive(bottom, rect_mesh(bottom, Color32::BLUE)),
+        ];
+        let skp = |painter: Painter| {
+            let mut recorder = PictureRecorder::new();
+            let canvas = recorder.begin_recording(Rect::from_wh(80.0, 40.0), None);
+            painter.paint_primitives(canvas, 2.0, &primitives);
+            let picture = recorder.finish_recording_as_picture(None).unwrap();
+            picture.serialize().as_bytes().to_vec()
+        };
+        let contains = |bytes: &[u8], text: &str| {
+            bytes
+                .windows(text.len())
+                .any(|window| window == text.as_bytes())
+        };
+
+        let annotated = skp(painter().with_debug_annotations(true));
+        assert!(contains(&annotated, "egui_skia.primitive"));
+        for value in [
+            "index=0;texture=Managed(0);clip=[0 0 40 10]",
+            "index=1;texture=Managed(0);clip=[0 10 40 20]",
+        ] {
+            assert!(contains(&annotated, value), "{} is missing", value);
+        }
+        assert!(!contains(&skp(painter()), "egui_skia.primitive"));
+    }
+
+    #[test]
+    fn paint_tee_paints_both_canvases_like_a_single_paint() {
+        let primitives = || {
+            let callback = EguiSkiaPaintCallback::new(|canvas| {
+                let mut paint = Paint::default();
+                paint.set_anti_alias(true);
+                paint.set_color(Color::GREEN);
+                canvas.draw_circle((10.0, 10.0), 8.0, &paint);
+            });
+            let translucent = Color32::from_rgba_premultiplied(0, 0, 120, 120);
+            vec![
+                rect_primitive(rect((0.0, 0.0), (40.0, 30.0)), Color32::from_gray(60)),
+                callback_primitive(rect((5.0, 5.0), (25.0, 25.0)), callback),
+                mesh_primitive(
+                    rect((10.0, 0.0), (30.0, 20.0)),
+                    rect_mesh(rect((0.0, 10.0), (40.0, 30.0)), translucent),
+                ),
+            ]
+        };
+
+        let mut painter = painter();
+        let mut screen = surface(80, 60);
+        let mut recorder = PictureRecorder::new();
+        let stream = recorder.begin_recording(Rect::from_wh(80.0, 60.0), None);
+        painter.paint_tee(
+            screen.canvas(),
+            stream,
+            2.0,
+            primitives(),
+            TexturesDelta::default(),
+        );
+        let picture = recorder.finish_recording_as_picture(None).unwrap();
+        let mut replayed = surface(80, 60);
+        replayed.canvas().draw_picture(&picture, None, None);
+
+        let mut reference = surface(80, 60);
+        painter.paint_and_update_textures(
+            reference.canvas(),
+            2.0,
+            primitives(),
+            TexturesDelta::default(),
+        );
+        let screen = surface_bytes(&mut screen);
+        assert!(screen == surface_bytes(&mut replayed));
+        assert!(screen == surface_bytes(&mut reference));
+    }
+
+    #[test]
+    fn vertex_colors_with_little_or_no_alpha_stay_valid() {
+        let colors = [
+            Color32::from_rgba_premultiplied(0, 0, 0, 0),
+            // Additive colors have color without alpha.
+            Color32::from_rgba_premultiplied(255, 128, 1, 0),
+            Color32::from_rgba_premultiplied(1, 1, 1, 1),
+            Color32::from_rgba_premultiplied(2, 1, 0, 2),
+            Color32::from_rgba_premultiplied(3, 0, 3, 3),
+        ];
+        let primitives = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let x = i as f32 * 10.0;
+                rect_primitive(rect((x, 0.0), (x + 10.0, 10.0)), color)
+            })
+            .collect::<Vec<_>>();
+        let mut surface = surface(50, 10);
+        painter().paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        for x in 0..20 {
+            assert_eq!(pixel(&mut surface, x, 5), Color::TRANSPARENT, "x {}", x);
+        }
+        let pixels = surface_bytes(&mut surface);
+        for (i, premultiplied) in pixels.chunks(4).enumerate() {
+            let alpha = premultiplied[3];
+            let valid = premultiplied[..3].iter().all(|&c| c <= alpha);
+            assert!(valid, "{:?} at {}", premultiplied, i);
+        }
+        assert!(pixel(&mut surface, 45, 5).a() > 0);
+    }
+
+    #[test]
+    fn drawn_textures_are_marked_after_the_frame_and_demoted_ones_come_back() {
+        let mut painter = painter().with_low_memory(1);
+        let id = TextureId::User(1);
+        let image = ImageData::Color(ColorImage::new([2, 2], Color32::RED));
+        painter
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (10.0, 10.0)), uv, Color32::WHITE);
+        let textured = [mesh_primitive(egui::Rect::EVERYTHING, mesh)];
+        let demoted = |painter: &Painter| painter.texture_store().read().unwrap().stats().demoted;
+
+        // Drawn every frame, the texture is never demoted.
+        for _ in 0..5 {
+            painter.paint_primitives(surface(10, 10).canvas(), 1.0, &textured);
+            painter.free_textures(&TexturesDelta::default());
+        }
+        assert_eq!(demoted(&painter), 0);
+
+        for _ in 0..3 {
+            painter.paint_primitives(surface(10, 10).canvas(), 1.0, &[]);
+            painter.free_textures(&TexturesDelta::default());
+        }
+        assert_eq!(demoted(&painter), 1);
+
+        let mut surface = surface(10, 10);
+        painter.paint_primitives(surface.canvas(), 1.0, &textured);
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        assert_eq!(demoted(&painter), 0);
+    }
+
+    #[test]
+    fn draw_mesh_draws_atlas_packed_textures() {
+        let mut painter = painter().with_texture_atlas(true);
+        let id = TextureId::User(1);
+        let image = ImageData::Color(ColorImage::new([2, 2], Color32::RED));
+        painter
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (10.0, 10.0)), uv, Color32::WHITE);
+
+        let mut surface = surface(10, 10);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+    }
+
+    #[test]
+    fn draw_mesh_marks_its_texture_used_and_brings_it_back_when_demoted() {
+        let mut painter = painter().with_low_memory(1);
+        let id = TextureId::User(1);
+        let image = ImageData::Color(ColorImage::new([2, 2], Color32::RED));
+        painter
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (10.0, 10.0)), uv, Color32::WHITE);
+        let demoted = |painter: &Painter| painter.texture_store().read().unwrap().stats().demoted;
+
+        for _ in 0..5 {
+            painter.draw_mesh(surface(10, 10).canvas(), 1.0, None, &mesh);
+            painter.free_textures(&TexturesDelta::default());
+        }
+        assert_eq!(demoted(&painter), 0);
+
+        for _ in 0..3 {
+            painter.free_textures(&TexturesDelta::default());
+        }
+        assert_eq!(demoted(&painter), 1);
+
+        let mut surface = surface(10, 10);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        assert_eq!(demoted(&painter), 0);
+    }
+
+    #[test]
+    fn large_user_images_drawn_as_one_quad_are_not_split_into_tiles() {
+        let mut painter = painter().with_max_atlas_size(16);
+        let id = TextureId::User(1);
+        // Red on the left and blue on the right half.
+        let pixels = (0..64 * 64)
+            .map(|i| [Color32::RED, Color32::BLUE][i % 64 / 32])
+            .collect();
+        let image = ImageData::Color(ColorImage {
+            size: [64, 64],
+            pixels,
+        });
+        painter
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((0.0, 0.0), (64.0, 64.0)), uv, Color32::WHITE);
+
+        let mut surface = surface(64, 64);
+        let primitive = mesh_primitive(egui::Rect::EVERYTHING, mesh);
+        painter.paint_primitives(surface.canvas(), 1.0, &[primitive]);
+        let textures = painter.texture_store().read().unwrap();
+        assert!(textures.get(&id).unwrap().tiles.is_empty());
+        assert_color_near(pixel(&mut surface, 4, 4), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 4, 60), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 60, 60), Color::BLUE, 0);
+    }
+
+    #[test]
+    fn options_set_on_a_shared_store_keep_its_textures() {
+        let store = Arc::new(RwLock::new(TextureStore::new().with_texture_atlas(true)));
+        let mut first = Painter::with_texture_store(store.clone());
+        crate::test_util::upload_white(&mut first, TextureId::User(1));
+
+        let second = Painter::with_texture_store(store.clone())
+            .with_quality(QualityTier::High)
+            .with_texture_atlas(false)
+            .with_async_atlas_upload(true)
+            .with_max_atlas_size(256)
+            .with_low_memory(10);
+        // The texture packed before stays in the atlas, and both painters still draw it.
+        let options = store.read().unwrap().texture_options(TextureId::User(1));
+        assert!(options.is_some());
+        let mut mesh = rect_mesh(rect((0.0, 0.0), (4.0, 4.0)), Color32::RED);
+        mesh.texture_id = TextureId::User(1);
+        for painter in [&first, &second] {
+            let mut surface = surface(4, 4);
+            let primitive = mesh_primitive(egui::Rect::EVERYTHING, mesh.clone());
+            painter.paint_primitives(surface.canvas(), 1.0, &[primitive]);
+            assert_color_near(pixel(&mut surface, 2, 2), Color::RED, 0);
+        }
+    }
+
+    #[test]
+    fn dithering_breaks_up_the_bands_of_subtle_gradients() {
+        // 200 pixels from gray 100 to gray 108, bands of 25 pixels without dithering.
+        let (left, right) = (Color32::from_gray(100), Color32::from_gray(108));
+        let vertex = |x, y, color| Vertex {
+            pos: pos2(x, y),
+            uv: Pos2::ZERO,
+            color,
+        };
+        let mesh = Mesh {
+            indices: vec![0, 1, 2, 1, 2, 3],
+            vertices: vec![
+                vertex(0.0, 0.0, left),
+                vertex(200.0, 0.0, right),
+                vertex(0.0, 16.0, left),
+                vertex(200.0, 16.0, right),
+            ],
+            texture_id: TextureId::default(),
+        };
+        let render = |dithering: bool| {
+            let painter = painter().with_dithering(dithering);
+            let mut surface = surface(200, 16);
+            let primitive = mesh_primitive(egui::Rect::EVERYTHING, mesh.clone());
+            painter.paint_primitives(surface.canvas(), 1.0, &[primitive]);
+            let mut row = |y| {
+                (0..200)
+                    .map(|x| pixel(&mut surface, x, y).r())
+                    .collect::<Vec<_>>()
+            };
+            let reds = (0..16).map(&mut row).collect::<Vec<_>>();
+            // Steps between neighbouring pixels, the edges of flat bands, and the mean.
+            let steps = reds
+                .iter()
+                .flat_map(|row| row.windows(2).filter(|pair| pair[0] != pair[1]))
+                .count();
+            let sum = reds.iter().flatten().map(|&red| red as f32).sum::<f32>();
+            (steps, sum / (200.0 * 16.0))
+        };
+
+        let (plain_steps, plain_mean) = render(false);
+        let (dithered_steps, dithered_mean) = render(true);
+        assert!(plain_steps <= 16 * 10, "{}", plain_steps);
+        assert!(dithered_steps > plain_steps * 4, "{}", dithered_steps);
+        // Dithering moves the error around without changing the overall brightness.
+        let difference = (plain_mean - dithered_mean).abs();
+        assert!(difference < 0.5, "{} {}", plain_mean, dithered_mean);
+    }
+
+    #[test]
+    fn linear_textures_are_encoded_to_srgb_and_srgb_textures_pass_through() {
+        let encode = |value: u8| {
+            let linear = value as f32 / 255.0;
+            let srgb = if linear <= 0.003_130_8 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            (srgb * 255.0).round() as u8
+        };
+        let gradient = [32, 64, 128, 255];
+        let draw = |usage: TextureColorUsage| {
+            let mut painter = painter();
+            let id = TextureId::User(1);
+            painter.set_texture_color_usage(id, usage).unwrap();
+            let black = ImageData::Color(ColorImage::new([4, 1], Color32::BLACK));
+            painter
+                .upload_texture(id, &black, TextureOptions::NEAREST)
+                .unwrap();
+            // The usage survives partial updates.
+            let pixels = gradient.map(Color32::from_gray).to_vec();
+            let size = [4, 1];
+            let image = ImageData::Color(ColorImage { size, pixels });
+            painter.update_texture_region(id, [0, 0], &image).unwrap();
+
+            let mut mesh = Mesh::with_texture(id);
+            let uv = rect((0.0, 0.0), (1.0, 1.0));
+            mesh.add_rect_with_uv(rect((0.0, 0.0), (4.0, 1.0)), uv, Color32::WHITE);
+            let info = ImageInfo::new_n32_premul((4, 1), Some(ColorSpace::new_srgb()));
+            let mut surface = Surface::new_raster(&info, None, None).unwrap();
+            surface.canvas().clear(Color::TRANSPARENT);
+            let primitives = [mesh_primitive(Rect::EVERYTHING, mesh)];
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            let row = (0..4).map(|x| pixel(&mut surface, x, 0));
+            row.collect::<Vec<_>>()
+        };
+
+        let srgb = draw(TextureColorUsage::Srgb);
+        let linear = draw(TextureColorUsage::Linear);
+        for (i, value) in gradient.into_iter().enumerate() {
+            let gray = |v: u8| Color::from_rgb(v, v, v);
+            assert_color_near(srgb[i], gray(value), 1);
+            assert_color_near(linear[i], gray(encode(value)), 2);
+        }
+        // Only full white is left as it is, every other value is brightened.
+        let mut brightened = linear[..3].iter().zip(&srgb);
+        assert!(brightened.all(|(l, s)| l.r() > s.r() + 20));
+    }
+
+    #[test]
+    fn callbacks_and_meshes_keep_their_positions_whatever_was_drawn_before() {
+        let painter = painter();
+        // Leaves a save with a translation behind, which must not leak into the next primitive.
+        let callback = || {
+            EguiSkiaPaintCallback::new(|canvas| {
+                let mut paint = Paint::default();
+                paint.set_color(Color::RED);
+                canvas.draw_rect(Rect::from_wh(4.0, 4.0), &paint);
+                canvas.save();
+                canvas.translate((10.0, 10.0));
+            })
+        };
+        let primitives = [
+            rect_primitive(rect((0.0, 10.0), (4.0, 14.0)), Color32::BLUE),
+            callback_primitive(rect((10.0, 2.0), (20.0, 12.0)), callback()),
+            rect_primitive(rect((0.0, 16.0), (4.0, 20.0)), Color32::GREEN),
+            callback_primitive(rect((30.0, 2.0), (40.0, 12.0)), callback()),
+        ];
+        let mut surface = surface(80, 40);
+        painter.paint_primitives(surface.canvas(), 2.0, &primitives);
+
+        // The callbacks are translated to their rect in pixels, after a mesh and a callback alike.
+        for x in [20, 60] {
+            assert_color_near(pixel(&mut surface, x + 1, 5), Color::RED, 0);
+            assert_color_near(pixel(&mut surface, x + 7, 11), Color::RED, 0);
+            assert_color_near(pixel(&mut surface, x - 1, 5), Color::TRANSPARENT, 0);
+            assert_color_near(pixel(&mut surface, x + 9, 13), Color::TRANSPARENT, 0);
+        }
+        // The mesh after the first callback is still scaled by the dpi only.
+        assert_color_near(pixel(&mut surface, 1, 21), Color::BLUE, 0);
+        assert_color_near(pixel(&mut surface, 1, 33), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 7, 39), Color::GREEN, 0);
+        assert_color_near(pixel(&mut surface, 21, 39), Color::TRANSPARENT, 0);
+    }
+
+    #[test]
+    fn identical_frames_hash_equal_and_one_changed_color_differs() {
+        let frame = |color| {
+            vec![
+                rect_primitive(rect((0.0, 0.0), (20.0, 20.0)), Color32::from_gray(40)),
+                rect_primitive(rect((5.0, 5.0), (10.0, 10.0)), color),
+            ]
+        };
+        let hash = Painter::frame_hash(&frame(Color32::RED));
+        assert_eq!(hash, Painter::frame_hash(&frame(Color32::RED)));
+
+        let mut changed = frame(Color32::RED);
+        if let Primitive::Mesh(mesh) = &mut changed[1].primitive {
+            mesh.vertices[2].color = Color32::from_rgb(254, 0, 0);
+        }
+        assert_ne!(hash, Painter::frame_hash(&changed));
+        let bluish = frame(Color32::from_rgb(255, 0, 1));
+        assert_ne!(hash, Painter::frame_hash(&bluish));
+    }
+
+    #[test]
+    fn vertex_colors_keep_their_srgb_values_and_callbacks_draw_outside_of_srgb() {
+        let painter = painter();
+        // Saturated Display P3 red, outside of sRGB, in extended linear sRGB.
+        let p3_red = [1.2249, -0.0421, -0.0196];
+        let callback = EguiSkiaPaintCallback::new(move |canvas| {
+            let [r, g, b] = p3_red;
+            let linear = ColorSpace::new_srgb_linear();
+            canvas.draw_paint(&Paint::new(skia_safe::Color4f::new(r, g, b, 1.0), &linear));
+        });
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED),
+            callback_primitive(rect((10.0, 0.0), (20.0, 10.0)), callback),
+        ];
+        let info = OutputOptions::hdr().image_info((20, 10));
+        let mut surface = Surface::new_raster(&info, None, None).unwrap();
+        surface.canvas().clear(Color::TRANSPARENT);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        let pixels = surface.peek_pixels().unwrap();
+        let rgb = |x: i32| {
+            let color = pixels.get_color_4f((x, 5));
+            [color.r, color.g, color.b]
+        };
+        let assert_near = |actual: [f32; 3], expected: [f32; 3]| {
+            let mut pairs = actual.iter().zip(expected);
+            let near = pairs.all(|(a, e)| (a - e).abs() < 0.005);
+            assert!(near, "expected {:?}, got {:?}", expected, actual);
+        };
+        // The most saturated vertex color egui has is sRGB red, which arrives exactly.
+        assert_near(rgb(5), [1.0, 0.0, 0.0]);
+        assert_near(rgb(15), p3_red);
+    }
+
+    #[test]
+    fn drawing_primitives_one_at_a_time_matches_the_batch() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        let image = ColorImage::from_rgba_unmultiplied([2, 1], &[255, 0, 0, 255, 0, 0, 255, 128]);
+        painter
+            .upload_texture(id, &ImageData::Color(image), TextureOptions::LINEAR)
+            .unwrap();
+        let mut mesh = Mesh::with_texture(id);
+        let uv = rect((0.0, 0.0), (1.0, 1.0));
+        mesh.add_rect_with_uv(rect((2.0, 2.0), (18.0, 10.0)), uv, Color32::WHITE);
+        let callback = EguiSkiaPaintCallback::with_bounds(|canvas, bounds| {
+            let mut paint = Paint::default();
+            paint.set_anti_alias(true);
+            paint.set_color(Color::GREEN);
+            canvas.draw_oval(bounds, &paint);
+        });
+        let translucent = Color32::from_rgba_unmultiplied(255, 255, 0, 100);
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (20.0, 20.0)), Color32::from_gray(40)),
+            mesh_primitive(rect((0.0, 0.0), (15.0, 20.0)), mesh),
+            callback_primitive(rect((4.0, 11.0), (16.0, 19.0)), callback),
+            rect_primitive(rect((6.0, 6.0), (14.0, 14.0)), translucent),
+        ];
+
+        let mut batch = surface(40, 40);
+        painter.paint_primitives(batch.canvas(), 2.0, &primitives);
+        let mut one_by_one = surface(40, 40);
+        for primitive in &primitives {
+            painter.draw_primitive(one_by_one.canvas(), 2.0, primitive);
+        }
+
+        assert!(surface_bytes(&mut one_by_one) == surface_bytes(&mut batch));
+    }
+
+    #[test]
+    fn clips_under_a_rotation_follow_the_rotated_quad_with_smooth_edges() {
+        let painter = painter();
+        let clip = rect((30.0, 30.0), (70.0, 70.0));
+        let everything = rect((0.0, 0.0), (100.0, 100.0));
+        let callback = EguiSkiaPaintCallback::new(|canvas| canvas.draw_color(Color::RED, None));
+        let clipped = |primitive: ClippedPrimitive| ClippedPrimitive {
+            clip_rect: clip,
+            ..primitive
+        };
+        let frames = [
+            clipped(rect_primitive(everything, Color32::RED)),
+            clipped(callback_primitive(everything, callback)),
+        ];
+
+        for primitive in frames {
+            let mut surface = surface(100, 100);
+            let canvas = surface.canvas();
+            canvas.translate((50.0, 50.0));
+            canvas.rotate(30.0, None);
+            canvas.translate((-50.0, -50.0));
+            painter.paint_primitives(canvas, 1.0, &[primitive]);
+
+            let (sin, cos) = (-30f32).to_radians().sin_cos();
+            let mut smooth = 0;
+            for y in 0..100 {
+                for x in 0..100 {
+                    // The pixel center in the coordinates of the ui, rotated back.
+                    let (dx, dy) = (x as f32 + 0.5 - 50.0, y as f32 + 0.5 - 50.0);
+                    let local = pos2(50.0 + dx * cos - dy * sin, 50.0 + dx * sin + dy * cos);
+                    let alpha = pixel(&mut surface, x, y).a();
+                    if clip.shrink(1.0).contains(local) {
+                        assert_eq!(alpha, 255, "{}, {}", x, y);
+                    } else if !clip.expand(1.0).contains(local) {
+                        assert_eq!(alpha, 0, "{}, {}", x, y);
+                    } else if alpha != 0 && alpha != 255 {
+                        smooth += 1;
+                    }
+                }
+            }
+            // Most of the pixels along the four edges are partly covered.
+            assert!(smooth > 80, "{}", smooth);
+        }
+    }
+
+    #[test]
+    fn pdf_exports_embed_callbacks_as_images_at_twice_the_resolution() {
+        let primitives = || {
+            let callback =
+                EguiSkiaPaintCallback::new(|canvas| canvas.draw_color(Color::GREEN, None));
+            vec![
+                rect_primitive(rect((0.0, 0.0), (100.0, 50.0)), Color32::from_gray(40)),
+                callback_primitive(rect((10.0, 10.0), (33.0, 21.0)), callback),
+            ]
+        };
+        let mut painter = painter();
+        let pdf = painter
+            .render_to_pdf((100.0, 50.0), 1.0, primitives(), TexturesDelta::default())
+            .unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        // The 23 by 11 point callback, rasterized at 2 pixels per point.
+        assert!(pdf.contains("/Subtype /Image"));
+        assert!(pdf.contains("/Width 46"));
+        assert!(pdf.contains("/Height 22"));
+
+        let mut strict = Painter::new().with_callback_export(CallbackExport::Error);
+        let error = strict
+            .render_to_pdf((100.0, 50.0), 1.0, primitives(), TexturesDelta::default())
+            .unwrap_err();
+        assert!(matches!(error, RenderError::VectorCallback), "{}", error);
+    }
+
+    #[test]
+    fn primitives_far_outside_the_canvas_are_culled() {
+        let callback = EguiSkiaPaintCallback::new(|canvas| canvas.draw_color(Color::GREEN, None));
+        let far = rect((5000.0, 5000.0), (5010.0, 5010.0));
+        let left = rect((-900.0, 0.0), (-800.0, 20.0));
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED),
+            mesh_primitive(far, rect_mesh(far, Color32::BLUE)),
+            mesh_primitive(left, rect_mesh(left, Color32::BLUE)),
+            ClippedPrimitive {
+                clip_rect: far,
+                ..callback_primitive(far, callback)
+            },
+        ];
+
+        for culling in [false, true] {
+            let painter = painter().with_viewport_culling(culling);
+            let mut surface = surface(20, 20);
+            painter.paint_primitives(surface.canvas(), 2.0, &primitives);
+            let stats = painter.frame_stats();
+            assert_eq!(stats.culled_primitives, if culling { 3 } else { 0 });
+            assert_eq!(stats.callbacks, if culling { 0 } else { 1 });
+            assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        }
+    }
+
+    #[test]
+    fn red_and_green_collapse_under_deuteranopia() {
+        // A red and a green on the same deuteranopic confusion line.
+        let (red, green) = (Color32::from_rgb(255, 0, 49), Color32::from_rgb(0, 175, 0));
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), red),
+            rect_primitive(rect((10.0, 0.0), (20.0, 10.0)), green),
+        ];
+        let draw = |painter: Painter| {
+            let mut surface = surface(20, 10);
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            (pixel(&mut surface, 5, 5), pixel(&mut surface, 15, 5))
+        };
+
+        let (seen_red, seen_green) = draw(painter());
+        assert!(seen_red.r() - seen_green.r() > 200);
+        let (simulated_red, simulated_green) =
+            draw(painter().with_cvd_simulation(CvdType::Deuteranopia));
+        assert_color_near(simulated_red, simulated_green, 6);
+        // Not just darkened to black, both turn into the same olive.
+        assert!(simulated_red.r() > 100);
+        assert!(simulated_red.g() > 100);
+    }
+
+    #[test]
+    fn edge_feathers_fade_the_alpha_out_smoothly_towards_the_region_edges() {
+        let primitives = [rect_primitive(rect((0.0, 0.0), (60.0, 60.0)), Color32::RED)];
+        let mut surface = surface(60, 60);
+        let region = rect((10.0, 10.0), (50.0, 50.0));
+        painter().paint_with_edge_feather(surface.canvas(), 1.0, region, 10.0, &primitives);
+
+        let alphas = (0..60)
+            .map(|x| pixel(&mut surface, x, 30).a())
+            .collect::<Vec<_>>();
+        assert!(alphas[..10].iter().chain(&alphas[50..]).all(|a| *a == 0));
+        assert!(alphas[20..40].iter().all(|a| *a == 255));
+        for x in 10..20 {
+            // Rising by about a tenth per pixel, and mirrored on the other side.
+            assert!(alphas[x] < alphas[x + 1], "{:?}", alphas);
+            assert!(alphas[x + 1] - alphas[x] <= 40, "{:?}", alphas);
+            assert!(alphas[x].abs_diff(alphas[59 - x]) <= 16, "{:?}", alphas);
+        }
+        // Both fades apply in the corners.
+        assert!(pixel(&mut surface, 12, 12).a() < pixel(&mut surface, 12, 30).a());
+    }
+
+    #[test]
+    fn callbacks_of_foreign_types_are_skipped_and_the_rest_is_painted() {
+        // Stands in for the callback type of another backend, e.g. `egui_wgpu::Callback`.
+        struct ForeignCallback;
+        let primitives = [
+            rect_primitive(rect((0.0, 0.0), (10.0, 10.0)), Color32::RED),
+            callback_primitive(rect((10.0, 0.0), (20.0, 10.0)), ForeignCallback),
+            rect_primitive(rect((20.0, 0.0), (30.0, 10.0)), Color32::BLUE),
+        ];
+
+        let painter = painter();
+        let mut surface = surface(30, 10);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+        let stats = painter.frame_stats();
+        assert_eq!((stats.foreign_callbacks, stats.callbacks), (1, 0));
+        assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        assert_color_near(pixel(&mut surface, 25, 5), Color::BLUE, 0);
+        // Debug builds mark the missing content, release builds leave it out.
+        let placeholder = pixel(&mut surface, 15, 3);
+        if cfg!(debug_assertions) {
+            assert!(placeholder.a() > 0 && placeholder.r() == placeholder.b());
+        } else {
+            assert_eq!(placeholder, Color::TRANSPARENT);
+        }
+    }
+
+    #[test]
+    fn registered_shadows_are_drawn_natively_and_look_like_egui_shadows() {
+        let panel = rect((20.0, 20.0), (60.0, 60.0));
+        let clip = rect((0.0, 0.0), (80.0, 80.0));
+        let shadow = egui::epaint::Shadow {
+            extrusion: 16.0,
+            color: Color32::from_black_alpha(128),
+        };
+        let shadow_mesh = shadow.tessellate(panel, 0.0);
+        let primitives = [
+            mesh_primitive(clip, shadow_mesh.clone()),
+            mesh_primitive(clip, rect_mesh(panel, Color32::WHITE)),
+        ];
+        let draw = |painter: &Painter| {
+            let mut surface = surface(80, 80);
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            let alphas = (0..20).map(|x| pixel(&mut surface, x, 40).a());
+            (alphas.collect::<Vec<_>>(), painter.frame_stats())
+        };
+
+        let (egui_alphas, egui_stats) = draw(&painter());
+        let mut native = painter();
+        native.set_shadow_regions(vec![ShadowRegion {
+            rect: panel,
+            rounding: 0.0,
+            shadow,
+        }]);
+        let (native_alphas, native_stats) = draw(&native);
+
+        // egui draws the shadow as a mesh batched with the panel, skia as one blurred rect.
+        assert!(shadow_mesh.vertices.len() >= 8);
+        assert_eq!(egui_stats.native_shadows, 0);
+        assert_eq!(egui_stats.batched_meshes, 1);
+        assert_eq!(native_stats.native_shadows, 1);
+        assert_eq!(native_stats.batched_meshes, 0);
+        assert_eq!(native_stats.draw_calls, 1);
+        // The gaussian falloff stays close to egui's linear one.
+        assert!(egui_alphas[14] > 30 && native_alphas[14] > 30);
+        for (egui_alpha, native_alpha) in egui_alphas.iter().zip(&native_alphas) {
+            let difference = egui_alpha.abs_diff(*native_alpha);
+            assert!(difference <= 20, "{:?} {:?}", egui_alphas, native_alphas);
+        }
+    }
+
+    #[test]
+    fn high_quality_smooths_the_clip_edges_that_low_quality_leaves_hard() {
+        let clip = rect((2.5, 2.5), (17.5, 17.5));
+        let mesh = rect_mesh(rect((0.0, 0.0), (20.0, 20.0)), Color32::RED);
+        let primitives = [mesh_primitive(clip, mesh)];
+        let clear_color = Color::from_argb(128, 0, 0, 255);
+        let output = OutputOptions {
+            clear_color: Some(clear_color),
+            ..Default::default()
+        };
+        let mixed_pixels = |quality| {
+            let painter = painter()
+                .with_quality(quality)
+                .with_output_options(output.clone());
+            let mut surface = surface(20, 20);
+            surface.canvas().clear(Color::GREEN);
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            // The canvas itself is cleared, not just the layer of the high quality tier.
+            assert_color_near(pixel(&mut surface, 0, 0), clear_color, 1);
+            assert_color_near(pixel(&mut surface, 10, 10), Color::RED, 1);
+            let colors = (0..20).flat_map(|y| (0..20).map(move |x| (x, y)));
+            let mut mixed = |(x, y)| {
+                let color = pixel(&mut surface, x, y);
+                color != Color::RED && color != clear_color
+            };
+            colors.filter(|xy| mixed(*xy)).count()
+        };
+
+        assert_eq!(mixed_pixels(QualityTier::Low), 0);
+        // The half covered pixels along the four edges of the clip.
+        assert!(mixed_pixels(QualityTier::High) >= 4 * 15);
+    }
+
+    #[cfg(feature = "gl")]
+    #[test]
+    fn msaa_coverage_keeps_edges_crisper_than_feathering_on_msaa_targets() {
+        use egui::epaint::{Shape, Stroke, TessellationOptions, Tessellator};
+
+        let mut context = match gl_context() {
+            Some(context) => context,
+            None => return,
+        };
+        if context.max_surface_sample_count_for_color_type(ColorType::N32) < 4 {
+            return;
+        }
+        // A square turned by 45 degrees, tessellated with or without egui's feathering.
+        let points = vec![
+            pos2(16.0, 2.0),
+            pos2(30.0, 16.0),
+            pos2(16.0, 30.0),
+            pos2(2.0, 16.0),
+        ];
+        let shape = Shape::convex_polygon(points, Color32::RED, Stroke::NONE);
+        let mut partial_pixels = |painter: Painter, feathering: bool| {
+            let options = TessellationOptions {
+                feathering,
+                ..Default::default()
+            };
+            let mut mesh = Mesh::default();
+            Tessellator::new(1.0, options, [1, 1], Vec::new())
+                .tessellate_shape(shape.clone(), &mut mesh);
+            let clip = rect((0.0, 0.0), (32.0, 32.0));
+            let mut target = render_target(&mut context, 32, 32, 4);
+            painter.paint_primitives(target.canvas(), 1.0, &[mesh_primitive(clip, mesh)]);
+            let mut readback = read_back(&mut target);
+            let pixels = (0..32).flat_map(|y| (0..32).map(move |x| (x, y)));
+            let mut partial = |(x, y)| (1..255).contains(&pixel(&mut readback, x, y).a());
+            pixels.filter(|xy| partial(*xy)).count()
+        };
+
+        let double = partial_pixels(painter(), true);
+        let msaa_only = partial_pixels(painter().with_msaa_coverage(true), false);
+        // MSAA still smooths the edges, just over fewer pixels than with the feather on top.
+        assert!(msaa_only > 0);
+        assert!(msaa_only < double, "{} {}", msaa_only, double);
+    }
+
+    #[test]
+    fn textures_updated_once_paint_several_canvases_until_they_are_freed() {
+        let id = TextureId::User(1);
+        let image = ImageData::Color(ColorImage::new([1, 1], Color32::RED));
+        // Set and freed in the same delta, like egui does for a texture used for one frame.
+        let delta = TexturesDelta {
+            set: vec![(id, ImageDelta::full(image, TextureOptions::NEAREST))],
+            free: vec![id],
+        };
+        let area = rect((0.0, 0.0), (10.0, 10.0));
+        let mut mesh = Mesh::with_texture(id);
+        mesh.add_rect_with_uv(area, rect((0.0, 0.0), (1.0, 1.0)), Color32::WHITE);
+        let primitives = [mesh_primitive(area, mesh)];
+
+        let mut painter = Painter::new();
+        painter.update_textures(&delta);
+        // The same primitives painted into two canvases without uploading the texture again.
+        for mut surface in [surface(10, 10), surface(10, 10)] {
+            painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+            assert_color_near(pixel(&mut surface, 5, 5), Color::RED, 0);
+        }
+        assert_eq!(painter.texture_store().read().unwrap().stats().textures, 1);
+
+        painter.free_textures(&delta);
+        let textures = painter.texture_store().read().unwrap();
+        assert!(textures.texture_options(id).is_none());
+    }
+}
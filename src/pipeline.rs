@@ -0,0 +1,183 @@
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+use egui::{ClippedPrimitive, TexturesDelta};
+use skia_safe::{Color, Image, Surface};
+
+use crate::painter::Painter;
+
+/// Everything needed to paint one frame on the render thread of a [`PipelinedRenderer`].
+pub struct FrameData {
+    /// Size of the frame in pixels.
+    pub size: (i32, i32),
+    pub dpi: f32,
+    pub clear_color: Color,
+    pub primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+}
+
+/// Paints frames on a separate thread onto a raster surface, so the ui of the next frame can be
+/// run and tessellated while the previous frame is rasterized.
+///
+/// [`Self::submit`] blocks until the render thread has finished the previous frame, so at most
+/// one frame is in flight. Texture deltas are applied on the render thread in submission order.
+pub struct PipelinedRenderer {
+    frames: Option<SyncSender<FrameData>>,
+    finished: Receiver<Image>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PipelinedRenderer {
+    pub fn new() -> Self {
+        let (frames, frame_receiver) = sync_channel::<FrameData>(0);
+        let (finished_sender, finished) = channel();
+
+        let thread = std::thread::spawn(move || {
+            let mut painter = Painter::new();
+            let mut surface: Option<Surface> = None;
+
+            for frame in frame_receiver {
+                if surface.as_ref().map(|s| (s.width(), s.height())) != Some(frame.size) {
+                    surface = Some(
+                        Surface::new_raster_n32_premul(frame.size)
+                            .expect("Failed to create surface"),
+                    );
+                }
+                let surface = surface.as_mut().unwrap();
+
+                let canvas = surface.canvas();
+                canvas.clear(frame.clear_color);
+                painter.paint_and_update_textures(
+                    canvas,
+                    frame.dpi,
+                    frame.primitives,
+                    frame.textures_delta,
+                );
+
+                if finished_sender.send(surface.image_snapshot()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            frames: Some(frames),
+            finished,
+            thread: Some(thread),
+        }
+    }
+
+    /// Hands `frame` to the render thread, waiting for it to finish the previous frame first.
+    pub fn submit(&mut self, frame: FrameData) {
+        if let Some(frames) = &self.frames {
+            frames.send(frame).expect("render thread panicked");
+        }
+    }
+
+    /// Returns the most recently finished frame, if a frame finished since the last call.
+    ///
+    /// Returns an [`Image`] rather than the surface itself, as surfaces can't be sent between
+    /// threads.
+    pub fn take_finished_image(&mut self) -> Option<Image> {
+        self.finished.try_iter().last()
+    }
+}
+
+impl Default for PipelinedRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PipelinedRenderer {
+    fn drop(&mut self) {
+        self.frames.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::epaint::{ImageDelta, Mesh};
+    use egui::{pos2, Color32, ColorImage, Rect, TextureId, TextureOptions};
+
+    use super::*;
+    use crate::test_util::{assert_color_near, image_pixel, mesh_primitive};
+
+    fn frame(clear_color: Color, textures_delta: TexturesDelta) -> FrameData {
+        let id = TextureId::User(1);
+        let mut mesh = Mesh::with_texture(id);
+        let rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(2.0, 2.0));
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        FrameData {
+            size: (4, 4),
+            dpi: 1.0,
+            clear_color,
+            primitives: vec![mesh_primitive(rect, mesh)],
+            textures_delta,
+        }
+    }
+
+    fn set(image: ColorImage, pos: Option<[usize; 2]>) -> TexturesDelta {
+        let options = TextureOptions::NEAREST;
+        let delta = match pos {
+            None => ImageDelta::full(image, options),
+            Some(pos) => ImageDelta::partial(pos, image, options),
+        };
+        TexturesDelta {
+            set: vec![(TextureId::User(1), delta)],
+            free: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn frames_finish_in_submission_order_with_their_texture_updates() {
+        let mut renderer = PipelinedRenderer::new();
+        let red = ColorImage::new([1, 1], Color32::RED);
+        let blue = ColorImage::new([1, 1], Color32::BLUE);
+        // The clear color tells the frames apart, the texture color is what the texture
+        // updates up to the frame give.
+        let frames = [
+            (Color::BLACK, set(red, None), Color::RED),
+            (Color::WHITE, set(blue, Some([0, 0])), Color::BLUE),
+            (Color::GRAY, TexturesDelta::default(), Color::BLUE),
+        ];
+        let mut finished = Vec::new();
+        for (clear_color, textures_delta, _) in frames.clone() {
+            renderer.submit(frame(clear_color, textures_delta));
+            finished.extend(renderer.take_finished_image());
+        }
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while finished.last().map(|image| image_pixel(image, 3, 3)) != Some(Color::GRAY) {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "the last frame never finished"
+            );
+            match renderer.take_finished_image() {
+                Some(image) => finished.push(image),
+                None => std::thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        }
+
+        let indices = finished
+            .iter()
+            .map(|image| {
+                let clear_color = image_pixel(image, 3, 3);
+                let index = frames
+                    .iter()
+                    .position(|frame| frame.0 == clear_color)
+                    .unwrap();
+                assert_color_near(image_pixel(image, 0, 0), frames[index].2, 0);
+                index
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            indices.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            indices
+        );
+    }
+}
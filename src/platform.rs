@@ -0,0 +1,186 @@
+use skia_safe::image::CachingHint;
+use skia_safe::{AlphaType, Color, ColorSpace, ColorType, ISize, Image, ImageInfo};
+
+/// Platforms with presets for the output of the [`Painter`](crate::Painter), see
+/// [`Painter::for_platform`](crate::Painter::for_platform).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// An opaque window on a desktop compositor. Same as [`OutputOptions::default`].
+    Desktop,
+    /// A translucent Android `SurfaceView` (`PixelFormat.TRANSLUCENT`) composited over other
+    /// views. It expects premultiplied RGBA in sRGB and a transparent background.
+    AndroidSurfaceView,
+    /// A `CAMetalLayer` with `isOpaque = false` on iOS. It expects premultiplied BGRA in sRGB
+    /// and a transparent background.
+    IosMetalLayer,
+    /// A transparent desktop window (e.g. winit's `with_transparent(true)`) showing the desktop
+    /// wherever egui draws nothing. It expects premultiplied alpha and a transparent background,
+    /// present it with [`argb_pixels`].
+    TransparentWindow,
+}
+
+/// What the surfaces the painter draws into look like and how frames start.
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    /// The color type surfaces for the painter should be created with.
+    pub color_type: ColorType,
+    /// The alpha type surfaces for the painter should be created with. Overlays need
+    /// [`AlphaType::Premul`], opaque windows can use [`AlphaType::Opaque`].
+    pub alpha_type: AlphaType,
+    /// The color space surfaces for the painter should be created with. `None` draws without
+    /// color management.
+    ///
+    /// egui's colors are 8-bit sRGB and skia's vertices only take 8-bit colors, so vertex colors
+    /// always lie within sRGB and are converted into wide gamut spaces like Display P3 without
+    /// clipping. There is no float vertex color path for wide gamut output, colors outside of
+    /// sRGB can only come from paint callbacks.
+    pub color_space: Option<ColorSpace>,
+    /// The color the canvas is cleared to before each frame, `None` paints over the existing
+    /// content.
+    pub clear_color: Option<Color>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            color_type: ColorType::N32,
+            alpha_type: AlphaType::Premul,
+            color_space: None,
+            clear_color: None,
+        }
+    }
+}
+
+impl OutputOptions {
+    /// The preset for `platform`.
+    pub fn for_platform(platform: Platform) -> Self {
+        match platform {
+            Platform::Desktop => Self::default(),
+            Platform::AndroidSurfaceView => Self {
+                color_type: ColorType::RGBA8888,
+                alpha_type: AlphaType::Premul,
+                color_space: Some(ColorSpace::new_srgb()),
+                clear_color: Some(Color::TRANSPARENT),
+            },
+            Platform::IosMetalLayer => Self {
+                color_type: ColorType::BGRA8888,
+                alpha_type: AlphaType::Premul,
+                color_space: Some(ColorSpace::new_srgb()),
+                clear_color: Some(Color::TRANSPARENT),
+            },
+            Platform::TransparentWindow => Self {
+                clear_color: Some(Color::TRANSPARENT),
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Half float RGBA in extended linear sRGB, for compositing egui onto HDR content. Colors
+    /// above 1.0 aren't clamped, so an HDR background stays intact wherever egui is transparent
+    /// and is blended in float precision under translucent widgets.
+    pub fn hdr() -> Self {
+        Self {
+            color_type: ColorType::RGBAF16,
+            alpha_type: AlphaType::Premul,
+            color_space: Some(ColorSpace::new_srgb_linear()),
+            clear_color: None,
+        }
+    }
+
+    /// The image info for a surface of `size` pixels matching these options.
+    pub fn image_info(&self, size: impl Into<ISize>) -> ImageInfo {
+        ImageInfo::new(
+            size,
+            self.color_type,
+            self.alpha_type,
+            self.color_space.clone(),
+        )
+    }
+}
+
+/// Reads the pixels of `image` as premultiplied `0xAARRGGBB` values, the format of transparent
+/// windows presented through softbuffer and most native window buffers.
+///
+/// The alpha channel is kept, so areas egui didn't draw to stay fully transparent. Returns `None`
+/// if the pixels can't be read, e.g. for a GPU image without a context.
+pub fn argb_pixels(image: &Image) -> Option<Vec<u32>> {
+    let info = ImageInfo::new(image.dimensions(), ColorType::BGRA8888, AlphaType::Premul, None);
+    let row_bytes = info.min_row_bytes();
+    let mut bytes = vec![0; row_bytes * image.height() as usize];
+    if !image.read_pixels(&info, &mut bytes, row_bytes, (0, 0), CachingHint::Allow) {
+        return None;
+    }
+    // BGRA bytes are ARGB in a little endian u32.
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|pixel| u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, Color32, Rect, TextureId, TexturesDelta};
+
+    use super::*;
+    use crate::test_util::{rect_primitive, upload_white};
+    use crate::Painter;
+
+    #[test]
+    fn platform_presets_set_the_documented_options() {
+        let clear = Some(Color::TRANSPARENT);
+        let cases = [
+            (Platform::Desktop, ColorType::N32, None, None),
+            (Platform::AndroidSurfaceView, ColorType::RGBA8888, Some(true), clear),
+            (Platform::IosMetalLayer, ColorType::BGRA8888, Some(true), clear),
+            (Platform::TransparentWindow, ColorType::N32, None, clear),
+        ];
+        for (platform, color_type, srgb, clear_color) in cases {
+            let painter = Painter::for_platform(platform);
+            let options = painter.output_options();
+            assert_eq!(options.color_type, color_type, "{:?}", platform);
+            assert_eq!(options.alpha_type, AlphaType::Premul, "{:?}", platform);
+            let is_srgb = options.color_space.as_ref().map(ColorSpace::is_srgb);
+            assert_eq!(is_srgb, srgb, "{:?}", platform);
+            assert_eq!(options.clear_color, clear_color, "{:?}", platform);
+
+            let info = options.image_info((4, 4));
+            assert_eq!(info.color_type(), color_type);
+            assert_eq!(info.alpha_type(), AlphaType::Premul);
+        }
+    }
+
+    #[test]
+    fn transparent_windows_present_alpha_0_where_nothing_was_drawn() {
+        let mut painter = Painter::for_platform(Platform::TransparentWindow);
+        upload_white(&mut painter, TextureId::default());
+        // The last frame of the window buffer, which the preset clears.
+        let mut surface = skia_safe::Surface::new_raster_n32_premul((20, 10)).unwrap();
+        surface.canvas().clear(Color::WHITE);
+
+        let opaque = Rect::from_min_max(pos2(0.0, 0.0), pos2(5.0, 10.0));
+        let translucent = Rect::from_min_max(pos2(5.0, 0.0), pos2(10.0, 10.0));
+        let half_blue = Color32::from_rgba_premultiplied(0, 0, 128, 128);
+        let primitives = vec![
+            rect_primitive(opaque, Color32::RED),
+            rect_primitive(translucent, half_blue),
+        ];
+        let delta = TexturesDelta::default();
+        painter.paint_and_update_textures(surface.canvas(), 1.0, primitives, delta);
+
+        let pixels = argb_pixels(&surface.image_snapshot()).unwrap();
+        assert_eq!(pixels.len(), 20 * 10);
+        let at = |x: usize, y: usize| pixels[y * 20 + x];
+        assert_eq!(at(2, 5), 0xffff_0000);
+        // Premultiplied, so the blue channel never exceeds the alpha.
+        assert_eq!(at(7, 5) >> 24, 0x80);
+        assert_eq!(at(7, 5) & 0x00ff_ff00, 0);
+        assert!((at(7, 5) & 0xff).abs_diff(0x80) <= 1);
+        for y in 0..10 {
+            for x in 10..20 {
+                assert_eq!(at(x, y), 0, "({}, {})", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Recording the [`egui::RawInput`] of a session to a file and replaying it later, e.g. to
+//! reproduce a reported glitch deterministically in a headless run.
+//!
+//! A recording starts with a magic number and the format version, followed by one bincode
+//! encoded `RawInput` per frame, each prefixed with its length. Recordings of another format
+//! version are rejected by [`replay`], as are frames longer than [`MAX_FRAME_LEN`].
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use egui::{Context, RawInput};
+use skia_safe::{Image, Surface};
+
+use crate::EguiSkia;
+
+const MAGIC: &[u8; 8] = b"EGSKREC\0";
+
+/// The version of the recording format written by [`Recorder`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The longest encoded frame accepted, in bytes. This bounds the memory a corrupt or malicious
+/// recording can make [`replay`] allocate, while leaving room for frames with dropped files.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Errors returned while recording or replaying.
+#[derive(Debug)]
+pub enum RecordingError {
+    /// Reading or writing the file failed.
+    Io(std::io::Error),
+    /// A frame could not be encoded or decoded.
+    Encoding(bincode::Error),
+    /// The file is not a recording.
+    NotARecording,
+    /// The recording was written with another version of the format.
+    UnsupportedVersion(u32),
+    /// An encoded frame is longer than [`MAX_FRAME_LEN`].
+    FrameTooLarge(usize),
+}
+
+impl std::fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::Io(err) => write!(f, "io error: {}", err),
+            RecordingError::Encoding(err) => write!(f, "failed to encode input: {}", err),
+            RecordingError::NotARecording => write!(f, "not an input recording"),
+            RecordingError::UnsupportedVersion(version) => write!(
+                f,
+                "recording has format version {}, only version {} is supported",
+                version, FORMAT_VERSION
+            ),
+            RecordingError::FrameTooLarge(len) => write!(
+                f,
+                "frame of {} bytes is longer than the maximum of {} bytes",
+                len, MAX_FRAME_LEN
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(err: std::io::Error) -> Self {
+        RecordingError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for RecordingError {
+    fn from(err: bincode::Error) -> Self {
+        RecordingError::Encoding(err)
+    }
+}
+
+/// Writes the input of every frame to a recording file.
+///
+/// Pass the input of every frame to [`Self::record`] before handing it to egui.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    /// Creates the recording file at `path`, replacing an existing file.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// Appends the input of a frame to the recording.
+    pub fn record(&mut self, input: &RawInput) -> Result<(), RecordingError> {
+        let bytes = bincode::serialize(input)?;
+        if bytes.len() > MAX_FRAME_LEN {
+            return Err(RecordingError::FrameTooLarge(bytes.len()));
+        }
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Writes the buffered frames to the file.
+    pub fn flush(&mut self) -> Result<(), RecordingError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Options for [`replay`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayOptions {
+    /// Rasterize every `n`th frame (and always the last one), `None` only runs the frames.
+    pub rasterize_every: Option<usize>,
+    /// Pixels per point of frames whose input doesn't set them.
+    pub pixels_per_point: f32,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            rasterize_every: None,
+            pixels_per_point: 1.0,
+        }
+    }
+}
+
+/// Replays the recording at `path` with `ui`, and returns the rasterized frames.
+///
+/// Frames are rasterized at the size of the screen rect of their input. Frames without a screen
+/// rect or with an empty one are run, but not rasterized.
+pub fn replay(
+    path: impl AsRef<Path>,
+    mut ui: impl FnMut(&Context),
+    options: ReplayOptions,
+) -> Result<Vec<Image>, RecordingError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 8];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| RecordingError::NotARecording)?;
+    if &magic != MAGIC {
+        return Err(RecordingError::NotARecording);
+    }
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(RecordingError::UnsupportedVersion(version));
+    }
+
+    let mut backend = EguiSkia::new();
+    let mut images = Vec::new();
+    let mut frame = 0;
+    let mut next = read_frame(&mut reader)?;
+    while let Some(mut input) = next.take() {
+        next = read_frame(&mut reader)?;
+
+        let pixels_per_point = *input
+            .pixels_per_point
+            .get_or_insert(options.pixels_per_point);
+        let screen_rect = input.screen_rect;
+        backend.run(input, &mut ui);
+
+        let rasterize = match options.rasterize_every {
+            Some(n) => frame % n.max(1) == 0 || next.is_none(),
+            None => false,
+        };
+        let size = screen_rect.map(|rect| (rect.size() * pixels_per_point).round());
+        let surface = size
+            .filter(|_| rasterize)
+            .and_then(|size| Surface::new_raster_n32_premul((size.x as i32, size.y as i32)));
+        if let Some(mut surface) = surface {
+            backend.paint(surface.canvas());
+            images.push(surface.image_snapshot());
+        }
+        frame += 1;
+    }
+    Ok(images)
+}
+
+/// Reads the next frame, `None` at the end of the recording.
+fn read_frame(reader: &mut impl Read) -> Result<Option<RawInput>, RecordingError> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(RecordingError::FrameTooLarge(len));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(bincode::deserialize(&bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::path::PathBuf;
+
+    use egui::{pos2, vec2, Event, PointerButton, Pos2, Rect, Vec2};
+
+    use super::*;
+
+    /// A path in the temp directory unique to the test `name`.
+    fn temp_path(name: &str) -> PathBuf {
+        let file = format!("egui_skia_{}_{}.rec", name, std::process::id());
+        std::env::temp_dir().join(file)
+    }
+
+    fn input(time: f64, events: Vec<Event>) -> RawInput {
+        RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(200.0, 100.0))),
+            time: Some(time),
+            events,
+            ..Default::default()
+        }
+    }
+
+    /// Hovers and clicks the button of [`counter_ui`].
+    fn session() -> Vec<RawInput> {
+        let pos = pos2(15.0, 15.0);
+        let button = |pressed| Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: Default::default(),
+        };
+        vec![
+            input(0.0, vec![]),
+            input(0.1, vec![Event::PointerMoved(pos)]),
+            input(0.2, vec![button(true)]),
+            input(0.3, vec![button(false)]),
+            input(0.4, vec![]),
+        ]
+    }
+
+    fn counter_ui(clicks: &mut u32) -> impl FnMut(&Context) + '_ {
+        move |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ui.button(format!("Clicked {} times", clicks)).clicked() {
+                    *clicks += 1;
+                }
+            });
+        }
+    }
+
+    fn hash(image: &Image) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let pixels = image.peek_pixels().unwrap();
+        pixels.bytes().unwrap().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn replayed_sessions_end_in_the_recorded_frame() {
+        let path = temp_path("replay");
+        let mut recorder = Recorder::new(&path).unwrap();
+        let mut backend = EguiSkia::new();
+        let mut clicks = 0;
+        let mut last = None;
+        for input in session() {
+            recorder.record(&input).unwrap();
+            backend.run(input, counter_ui(&mut clicks));
+            let mut surface = Surface::new_raster_n32_premul((200, 100)).unwrap();
+            backend.paint(surface.canvas());
+            last = Some(surface.image_snapshot());
+        }
+        recorder.flush().unwrap();
+        drop(recorder);
+        assert_eq!(clicks, 1);
+
+        let mut replayed_clicks = 0;
+        let options = ReplayOptions {
+            rasterize_every: Some(2),
+            ..Default::default()
+        };
+        let images = replay(&path, counter_ui(&mut replayed_clicks), options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replayed_clicks, 1);
+        // Frames 0, 2 and the last one.
+        assert_eq!(images.len(), 3);
+        assert_eq!(hash(images.last().unwrap()), hash(&last.unwrap()));
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected_before_allocating() {
+        let path = temp_path("oversized");
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        bytes.extend(u32::MAX.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let result = replay(&path, |_| {}, ReplayOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        let too_large = u32::MAX as usize;
+        assert!(matches!(result, Err(RecordingError::FrameTooLarge(len)) if len == too_large));
+    }
+
+    #[test]
+    fn other_format_versions_are_rejected() {
+        let path = temp_path("version");
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((FORMAT_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let result = replay(&path, |_| {}, ReplayOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RecordingError::UnsupportedVersion(_))));
+    }
+
+    #[test]
+    fn frames_with_an_empty_screen_are_not_rasterized() {
+        let path = temp_path("empty_screen");
+        let mut recorder = Recorder::new(&path).unwrap();
+        let mut empty = input(0.0, vec![]);
+        empty.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, Vec2::ZERO));
+        recorder.record(&empty).unwrap();
+        recorder.record(&input(0.1, vec![])).unwrap();
+        recorder.flush().unwrap();
+        drop(recorder);
+
+        let options = ReplayOptions {
+            rasterize_every: Some(1),
+            ..Default::default()
+        };
+        let images = replay(&path, |_| {}, options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width(), images[0].height()), (200, 100));
+    }
+}
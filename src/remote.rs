@@ -0,0 +1,298 @@
+//! Serializable mirrors of the data the [`crate::Painter`] consumes, for running the ui on one
+//! machine and painting it on another.
+//!
+//! Paint callbacks can't be serialized and are dropped by [`to_wire`].
+
+use egui::epaint::{ImageDelta, Mesh, Primitive, Vertex};
+use egui::{
+    ClippedPrimitive, Color32, ColorImage, FontImage, ImageData, Pos2, Rect, TextureFilter,
+    TextureId, TextureOptions, TexturesDelta,
+};
+use serde::{Deserialize, Serialize};
+
+/// A frame of primitives together with the texture updates it needs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WireFrame {
+    pub primitives: Vec<WirePrimitive>,
+    pub textures_delta: WireTexturesDelta,
+}
+
+/// A mesh primitive, see [`ClippedPrimitive`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WirePrimitive {
+    /// `[min x, min y, max x, max y]` in points.
+    pub clip_rect: [f32; 4],
+    pub mesh: WireMesh,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WireMesh {
+    pub indices: Vec<u32>,
+    pub vertices: Vec<WireVertex>,
+    pub texture_id: WireTextureId,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WireVertex {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    /// Premultiplied sRGBA.
+    pub color: [u8; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireTextureId {
+    Managed(u64),
+    User(u64),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WireTexturesDelta {
+    pub set: Vec<(WireTextureId, WireImageDelta)>,
+    pub free: Vec<WireTextureId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WireImageDelta {
+    pub image: WireImage,
+    /// `true` for linear filtering, `false` for nearest.
+    pub magnification_linear: bool,
+    pub minification_linear: bool,
+    pub pos: Option<[usize; 2]>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum WireImage {
+    /// Premultiplied sRGBA pixels.
+    Color { size: [usize; 2], pixels: Vec<u8> },
+    /// Coverage values of the font atlas.
+    Font { size: [usize; 2], pixels: Vec<f32> },
+}
+
+/// Converts a frame to its serializable form. Paint callbacks are dropped with a warning.
+pub fn to_wire(primitives: &[ClippedPrimitive], textures_delta: &TexturesDelta) -> WireFrame {
+    let primitives = primitives
+        .iter()
+        .filter_map(|primitive| match &primitive.primitive {
+            Primitive::Mesh(mesh) => Some(WirePrimitive {
+                clip_rect: [
+                    primitive.clip_rect.min.x,
+                    primitive.clip_rect.min.y,
+                    primitive.clip_rect.max.x,
+                    primitive.clip_rect.max.y,
+                ],
+                mesh: mesh_to_wire(mesh),
+            }),
+            Primitive::Callback(callback) => {
+                log::warn!(
+                    "dropping paint callback at {:?}, callbacks can't be sent over the wire",
+                    callback.rect
+                );
+                None
+            }
+        })
+        .collect();
+
+    WireFrame {
+        primitives,
+        textures_delta: WireTexturesDelta {
+            set: textures_delta
+                .set
+                .iter()
+                .map(|(id, delta)| (id.into(), image_delta_to_wire(delta)))
+                .collect(),
+            free: textures_delta.free.iter().map(Into::into).collect(),
+        },
+    }
+}
+
+/// Converts a frame received over the wire back into the arguments of
+/// [`crate::Painter::paint_and_update_textures`].
+pub fn from_wire(frame: WireFrame) -> (Vec<ClippedPrimitive>, TexturesDelta) {
+    let primitives = frame
+        .primitives
+        .into_iter()
+        .map(|primitive| {
+            let [min_x, min_y, max_x, max_y] = primitive.clip_rect;
+            ClippedPrimitive {
+                clip_rect: Rect::from_min_max(Pos2::new(min_x, min_y), Pos2::new(max_x, max_y)),
+                primitive: Primitive::Mesh(mesh_from_wire(primitive.mesh)),
+            }
+        })
+        .collect();
+
+    let textures_delta = TexturesDelta {
+        set: frame
+            .textures_delta
+            .set
+            .into_iter()
+            .map(|(id, delta)| (id.into(), image_delta_from_wire(delta)))
+            .collect(),
+        free: frame
+            .textures_delta
+            .free
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    };
+
+    (primitives, textures_delta)
+}
+
+fn mesh_to_wire(mesh: &Mesh) -> WireMesh {
+    WireMesh {
+        indices: mesh.indices.clone(),
+        vertices: mesh
+            .vertices
+            .iter()
+            .map(|v| WireVertex {
+                pos: [v.pos.x, v.pos.y],
+                uv: [v.uv.x, v.uv.y],
+                color: v.color.to_array(),
+            })
+            .collect(),
+        texture_id: (&mesh.texture_id).into(),
+    }
+}
+
+fn mesh_from_wire(mesh: WireMesh) -> Mesh {
+    Mesh {
+        indices: mesh.indices,
+        vertices: mesh
+            .vertices
+            .into_iter()
+            .map(|v| {
+                let [r, g, b, a] = v.color;
+                Vertex {
+                    pos: Pos2::new(v.pos[0], v.pos[1]),
+                    uv: Pos2::new(v.uv[0], v.uv[1]),
+                    color: Color32::from_rgba_premultiplied(r, g, b, a),
+                }
+            })
+            .collect(),
+        texture_id: mesh.texture_id.into(),
+    }
+}
+
+fn image_delta_to_wire(delta: &ImageDelta) -> WireImageDelta {
+    let image = match &delta.image {
+        ImageData::Color(image) => WireImage::Color {
+            size: image.size,
+            pixels: image.pixels.iter().flat_map(|p| p.to_array()).collect(),
+        },
+        ImageData::Font(image) => WireImage::Font {
+            size: image.size,
+            pixels: image.pixels.clone(),
+        },
+    };
+    WireImageDelta {
+        image,
+        magnification_linear: delta.options.magnification == TextureFilter::Linear,
+        minification_linear: delta.options.minification == TextureFilter::Linear,
+        pos: delta.pos,
+    }
+}
+
+fn image_delta_from_wire(delta: WireImageDelta) -> ImageDelta {
+    let image = match delta.image {
+        WireImage::Color { size, pixels } => ImageData::Color(ColorImage {
+            size,
+            pixels: pixels
+                .chunks_exact(4)
+                .map(|p| Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3]))
+                .collect(),
+        }),
+        WireImage::Font { size, pixels } => ImageData::Font(FontImage { size, pixels }),
+    };
+    let filter = |linear| {
+        if linear {
+            TextureFilter::Linear
+        } else {
+            TextureFilter::Nearest
+        }
+    };
+    ImageDelta {
+        image,
+        options: TextureOptions {
+            magnification: filter(delta.magnification_linear),
+            minification: filter(delta.minification_linear),
+        },
+        pos: delta.pos,
+    }
+}
+
+impl From<&TextureId> for WireTextureId {
+    fn from(id: &TextureId) -> Self {
+        match *id {
+            TextureId::Managed(id) => WireTextureId::Managed(id),
+            TextureId::User(id) => WireTextureId::User(id),
+        }
+    }
+}
+
+impl From<WireTextureId> for TextureId {
+    fn from(id: WireTextureId) -> Self {
+        match id {
+            WireTextureId::Managed(id) => TextureId::Managed(id),
+            WireTextureId::User(id) => TextureId::User(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+    use crate::test_util::{callback_primitive, mesh_primitive, surface};
+    use crate::{EguiSkiaPaintCallback, Painter};
+
+    /// Sends `frame` through bincode when the `recording` feature brings it in.
+    fn loopback(frame: WireFrame) -> WireFrame {
+        #[cfg(feature = "recording")]
+        let frame = bincode::deserialize(&bincode::serialize(&frame).unwrap()).unwrap();
+        frame
+    }
+
+    fn paint(primitives: &[ClippedPrimitive], textures_delta: &TexturesDelta) -> Vec<u8> {
+        let mut painter = Painter::new();
+        let mut surface = surface(20, 10);
+        painter.paint_and_update_textures(
+            surface.canvas(),
+            1.0,
+            primitives.to_vec(),
+            textures_delta.clone(),
+        );
+        let pixels = surface.peek_pixels().unwrap();
+        pixels.bytes().unwrap().to_vec()
+    }
+
+    #[test]
+    fn frames_paint_the_same_after_a_round_trip() {
+        let id = TextureId::User(1);
+        let image = ColorImage::from_rgba_unmultiplied([2, 1], &[255, 0, 0, 255, 0, 0, 255, 128]);
+        let textures_delta = TexturesDelta {
+            set: vec![(id, ImageDelta::full(image, TextureOptions::NEAREST))],
+            free: Vec::new(),
+        };
+        let mut mesh = Mesh::with_texture(id);
+        let rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(20.0, 10.0));
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::from_rgb(255, 255, 128));
+        let clip = Rect::from_min_max(pos2(1.0, 1.0), pos2(19.0, 9.0));
+        let primitives = vec![mesh_primitive(clip, mesh)];
+
+        let with_callback = [
+            primitives.clone(),
+            vec![callback_primitive(rect, EguiSkiaPaintCallback::new(|_| {}))],
+        ]
+        .concat();
+        let frame = loopback(to_wire(&with_callback, &textures_delta));
+        assert_eq!(frame.primitives.len(), 1);
+        let (received, received_delta) = from_wire(frame);
+
+        let expected = paint(&primitives, &textures_delta);
+        assert!(expected.iter().any(|&byte| byte != 0));
+        assert_eq!(paint(&received, &received_delta), expected);
+    }
+}
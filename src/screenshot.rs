@@ -0,0 +1,58 @@
+use egui::{CursorIcon, Pos2};
+use skia_safe::{Canvas, Color, Paint, PaintStyle, Path, RRect, Rect};
+
+/// Options for [`EguiSkia::screenshot`](crate::EguiSkia::screenshot).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScreenshotOptions {
+    /// Draw a mouse cursor matching egui's cursor icon at the pointer position, e.g. for
+    /// tutorials. The OS cursor is never part of the surface, so it is drawn from a small
+    /// built-in set of cursor shapes (arrow, text, pointing hand and crosshair).
+    pub draw_cursor: bool,
+}
+
+/// Draws a cursor for `icon` with its hotspot at `pos` in points.
+pub(crate) fn draw_cursor(canvas: &mut Canvas, pos: Pos2, icon: CursorIcon, dpi: f32) {
+    let mut fill = Paint::default();
+    fill.set_anti_alias(true);
+    fill.set_color(Color::BLACK);
+    let mut outline = fill.clone();
+    outline.set_style(PaintStyle::Stroke);
+    outline.set_stroke_width(1.0);
+    outline.set_color(Color::WHITE);
+
+    let mut path = Path::new();
+    match icon {
+        CursorIcon::None => return,
+        CursorIcon::Text | CursorIcon::VerticalText => {
+            path.add_rect(Rect::from_ltrb(-0.5, -8.0, 0.5, 8.0), None);
+            path.add_rect(Rect::from_ltrb(-3.0, -8.5, 3.0, -7.5), None);
+            path.add_rect(Rect::from_ltrb(-3.0, 7.5, 3.0, 8.5), None);
+        }
+        CursorIcon::Crosshair | CursorIcon::Cell => {
+            path.add_rect(Rect::from_ltrb(-0.5, -9.0, 0.5, 9.0), None);
+            path.add_rect(Rect::from_ltrb(-9.0, -0.5, 9.0, 0.5), None);
+        }
+        CursorIcon::PointingHand => {
+            let finger = Rect::from_ltrb(-2.0, 0.0, 2.0, 10.0);
+            let palm = Rect::from_ltrb(-5.0, 7.0, 8.0, 18.0);
+            path.add_rrect(RRect::new_rect_xy(finger, 2.0, 2.0), None);
+            path.add_rrect(RRect::new_rect_xy(palm, 3.0, 3.0), None);
+        }
+        _ => {
+            path.move_to((0.0, 0.0));
+            path.line_to((0.0, 17.0));
+            path.line_to((4.0, 13.0));
+            path.line_to((7.0, 20.0));
+            path.line_to((10.0, 19.0));
+            path.line_to((7.0, 12.0));
+            path.line_to((12.0, 12.0));
+            path.close();
+        }
+    }
+
+    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+    arc.scale((dpi, dpi));
+    arc.translate((pos.x, pos.y));
+    arc.draw_path(&path, &outline);
+    arc.draw_path(&path, &fill);
+}
@@ -0,0 +1,226 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use egui::{Align2, FontId, Response, Sense, Ui, Vec2, Widget};
+use skia_safe::{Data, Paint, RuntimeEffect};
+
+use crate::callback::EguiSkiaPaintCallback;
+
+thread_local! {
+    /// Compiled effects (or their compile errors) by the hash of their source.
+    static EFFECTS: RefCell<HashMap<u64, Result<RuntimeEffect, String>>> = Default::default();
+}
+
+/// The value of a uniform of a [`ShaderWidget`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShaderUniform {
+    Float(f32),
+    Float2([f32; 2]),
+    Float3([f32; 3]),
+    Float4([f32; 4]),
+}
+
+impl ShaderUniform {
+    fn floats(&self) -> &[f32] {
+        match self {
+            ShaderUniform::Float(value) => std::slice::from_ref(value),
+            ShaderUniform::Float2(value) => value,
+            ShaderUniform::Float3(value) => value,
+            ShaderUniform::Float4(value) => value,
+        }
+    }
+}
+
+/// Fills its rect with an SKSL shader, e.g. as a live shader playground next to a `TextEdit`.
+///
+/// The shader is compiled once per source and gets the user uniforms plus these built-ins, if it
+/// declares them:
+/// - `uniform float iTime`: the egui time in seconds. Declaring it keeps repainting the ui.
+/// - `uniform float2 iResolution`: the size of the widget in points.
+/// - `uniform float2 iMouse`: the pointer position relative to the widget while it is hovered,
+///   `(-1, -1)` otherwise.
+///
+/// Coordinates passed to `main` are in points relative to the top left corner of the widget.
+/// If the source doesn't compile, the widget shows the compile error instead.
+pub struct ShaderWidget<'a> {
+    size: Vec2,
+    sksl: &'a str,
+    uniforms: &'a [(&'a str, ShaderUniform)],
+}
+
+impl<'a> ShaderWidget<'a> {
+    pub fn new(size: Vec2, sksl: &'a str, uniforms: &'a [(&'a str, ShaderUniform)]) -> Self {
+        Self {
+            size,
+            sksl,
+            uniforms,
+        }
+    }
+}
+
+impl Widget for ShaderWidget<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, Sense::hover());
+
+        let mut hasher = DefaultHasher::new();
+        self.sksl.hash(&mut hasher);
+        let effect = EFFECTS.with(|effects| {
+            effects
+                .borrow_mut()
+                .entry(hasher.finish())
+                .or_insert_with(|| RuntimeEffect::make_for_shader(self.sksl, None))
+                .clone()
+        });
+
+        let effect = match effect {
+            Ok(effect) => effect,
+            Err(error) => {
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+                painter.text(
+                    rect.left_top(),
+                    Align2::LEFT_TOP,
+                    error,
+                    FontId::monospace(12.0),
+                    ui.visuals().error_fg_color,
+                );
+                return response;
+            }
+        };
+
+        let time = ui.input().time as f32;
+        let mouse = response.hover_pos().map(|pos| pos - rect.min);
+        let mut data = vec![0u8; effect.uniform_size()];
+        for uniform in effect.uniforms() {
+            let value = match uniform.name() {
+                "iTime" => {
+                    ui.ctx().request_repaint();
+                    ShaderUniform::Float(time)
+                }
+                "iResolution" => ShaderUniform::Float2([rect.width(), rect.height()]),
+                "iMouse" => match mouse {
+                    Some(mouse) => ShaderUniform::Float2([mouse.x, mouse.y]),
+                    None => ShaderUniform::Float2([-1.0, -1.0]),
+                },
+                name => match self.uniforms.iter().find(|(n, _)| *n == name) {
+                    Some((_, value)) => *value,
+                    None => continue,
+                },
+            };
+            let bytes = value
+                .floats()
+                .iter()
+                .flat_map(|float| float.to_ne_bytes())
+                .take(uniform.size_in_bytes())
+                .collect::<Vec<_>>();
+            data[uniform.offset()..uniform.offset() + bytes.len()].copy_from_slice(&bytes);
+        }
+
+        let mut paint = Paint::default();
+        if let Some(shader) = effect.make_shader(Data::new_copy(&data), &[], None) {
+            paint.set_shader(shader);
+        }
+        ui.painter().add(egui::PaintCallback {
+            rect,
+            callback: Arc::new(EguiSkiaPaintCallback::with_bounds(move |canvas, bounds| {
+                canvas.draw_rect(bounds, &paint);
+            })),
+        });
+
+        response
+    }
+}
+
+/// Adds [`ShaderWidget`]s to a [`Ui`].
+pub trait SkiaShaderExt {
+    /// Adds a [`ShaderWidget`] of `size` running `sksl` with `uniforms`.
+    fn skia_shader(
+        &mut self,
+        size: Vec2,
+        sksl: &str,
+        uniforms: &[(&str, ShaderUniform)],
+    ) -> Response;
+}
+
+impl SkiaShaderExt for Ui {
+    fn skia_shader(
+        &mut self,
+        size: Vec2,
+        sksl: &str,
+        uniforms: &[(&str, ShaderUniform)],
+    ) -> Response {
+        self.add(ShaderWidget::new(size, sksl, uniforms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::epaint::ClippedShape;
+    use egui::{CentralPanel, Context, Frame, Shape};
+    use skia_safe::Color;
+
+    use super::*;
+    use crate::test_util::{assert_color_near, pixel};
+
+    const GRADIENT: &str = "
+        uniform float2 iResolution;
+        uniform float blue;
+
+        half4 main(float2 pos) {
+            return half4(pos.x / iResolution.x, 0, blue, 1);
+        }
+    ";
+
+    fn show(ctx: &Context, sksl: &str, uniforms: &[(&str, ShaderUniform)]) {
+        CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                ui.skia_shader(egui::vec2(100.0, 10.0), sksl, uniforms)
+            });
+    }
+
+    #[test]
+    fn gradient_shader_fills_the_widget() {
+        let uniforms = [("blue", ShaderUniform::Float(1.0))];
+        let mut surface = crate::rasterize((100, 10), |ctx| show(ctx, GRADIENT, &uniforms), None);
+
+        assert_color_near(pixel(&mut surface, 0, 5), Color::from_rgb(1, 0, 255), 1);
+        assert_color_near(pixel(&mut surface, 50, 5), Color::from_rgb(129, 0, 255), 1);
+        assert_color_near(pixel(&mut surface, 99, 5), Color::from_rgb(254, 0, 255), 1);
+    }
+
+    #[test]
+    fn uniform_updates_reuse_the_compiled_shader() {
+        let ctx = Context::default();
+        for blue in [0.0, 0.5, 1.0] {
+            let uniforms = [("blue", ShaderUniform::Float(blue))];
+            let _ = ctx.run(Default::default(), |ctx| show(ctx, GRADIENT, &uniforms));
+        }
+        assert_eq!(EFFECTS.with(|effects| effects.borrow().len()), 1);
+    }
+
+    #[test]
+    fn syntax_errors_are_shown_in_the_widget() {
+        let ctx = Context::default();
+        let sksl = "half4 main(float2 pos) { return oops; }";
+        let output = ctx.run(Default::default(), |ctx| show(ctx, sksl, &[]));
+
+        let shapes = output.shapes.iter().map(|ClippedShape(_, shape)| shape);
+        let mut texts = Vec::new();
+        for shape in shapes {
+            match shape {
+                Shape::Text(text) => texts.push(text.galley.text().to_owned()),
+                Shape::Callback(_) => panic!("the broken shader was drawn"),
+                _ => {}
+            }
+        }
+        assert!(
+            texts.iter().any(|text| text.contains("oops")),
+            "{:?}",
+            texts
+        );
+    }
+}
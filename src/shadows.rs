@@ -0,0 +1,63 @@
+use egui::epaint::{Mesh, Shadow};
+use skia_safe::{BlurStyle, Canvas, MaskFilter, Paint, RRect, Rect};
+
+use crate::color::color32_to_skia_unmultiplied;
+
+/// How far the bounds of a mesh may be off the expected shadow bounds, in points.
+const MATCH_TOLERANCE: f32 = 1.0;
+
+/// A shadow egui draws behind a window or panel, registered with
+/// [`crate::Painter::set_shadow_regions`] to have skia draw it instead.
+///
+/// egui tessellates shadows into meshes with a wide anti aliasing feather, which are soft and
+/// cover a lot of pixels. egui doesn't tag these meshes, so the host describes the shadows it
+/// knows of, and the painter replaces the meshes matching them with a blurred rounded rect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowRegion {
+    /// The rect casting the shadow in points, e.g. the outer rect of a window frame.
+    pub rect: egui::Rect,
+    /// The corner radius of `rect` in points.
+    pub rounding: f32,
+    /// The shadow as passed to the frame, e.g. `ctx.style().visuals.window_shadow`.
+    pub shadow: Shadow,
+}
+
+impl ShadowRegion {
+    /// Whether `mesh` is the mesh egui tessellates for this shadow.
+    ///
+    /// `Shadow::tessellate` fills `rect` expanded by half the extrusion and feathers it by the
+    /// extrusion, so the mesh covers `rect` expanded by the extrusion.
+    pub(crate) fn matches(&self, mesh: &Mesh) -> bool {
+        if mesh.texture_id != egui::TextureId::default() || mesh.vertices.is_empty() {
+            return false;
+        }
+        let bounds = mesh.calc_bounds();
+        let expected = self.rect.expand(self.shadow.extrusion);
+        (bounds.min - expected.min).length() <= MATCH_TOLERANCE
+            && (bounds.max - expected.max).length() <= MATCH_TOLERANCE
+    }
+
+    /// Draws the shadow onto `canvas`, whose matrix maps points.
+    ///
+    /// The blur covers the same extent as egui's linear feather: it is centered on the edge of
+    /// the filled rect, with a sigma of a quarter of the extrusion.
+    pub(crate) fn draw(&self, canvas: &mut Canvas) {
+        let half_extrusion = 0.5 * self.shadow.extrusion;
+        let rect = self.rect.expand(half_extrusion);
+        let radius = self.rounding + half_extrusion;
+        let rrect = RRect::new_rect_xy(
+            Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y),
+            radius,
+            radius,
+        );
+
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_color(color32_to_skia_unmultiplied(self.shadow.color));
+        let sigma = 0.25 * self.shadow.extrusion;
+        if sigma > 0.0 {
+            paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, sigma, false));
+        }
+        canvas.draw_rrect(rrect, &paint);
+    }
+}
@@ -0,0 +1,35 @@
+/// Statistics about the last frame painted by a [`crate::Painter`], see
+/// [`crate::Painter::frame_stats`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Number of `draw_vertices` calls.
+    pub draw_calls: usize,
+    /// Number of meshes merged into the mesh before them, saving their draw calls. Consecutive
+    /// meshes with the same texture and clip rect are always merged, see also
+    /// [`crate::Painter::with_max_draw_calls`].
+    pub batched_meshes: usize,
+    /// Number of paint callbacks drawn.
+    pub callbacks: usize,
+    /// Number of paint callbacks skipped because they don't hold an
+    /// [`crate::EguiSkiaPaintCallback`], e.g. callbacks of another egui backend.
+    pub foreign_callbacks: usize,
+    /// Number of times a clip rect was applied to the canvas.
+    pub clip_operations: usize,
+    /// Number of distinct clip rects. Many distinct clip rects usually mean the ui fragments its
+    /// draws, e.g. through many nested scroll areas.
+    pub unique_clip_rects: usize,
+    /// Area of the largest clip rect in points².
+    pub largest_clip_area: f32,
+    /// Number of primitives skipped because their clip rect is outside the canvas, see
+    /// [`crate::Painter::with_viewport_culling`].
+    pub culled_primitives: usize,
+    /// Number of egui shadow meshes drawn as skia shadows, see
+    /// [`crate::Painter::set_shadow_regions`].
+    pub native_shadows: usize,
+    /// Number of times the GPU context was flushed while painting, see
+    /// [`crate::Painter::with_flush_interval`].
+    pub gpu_flushes: usize,
+    /// Number of times the GPU work was submitted since the frame was painted, by
+    /// [`crate::Painter::flush`].
+    pub gpu_submissions: usize,
+}
@@ -0,0 +1,153 @@
+//! Helpers shared by the unit tests.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use egui::epaint::{Mesh, Primitive};
+use egui::{
+    ClippedPrimitive, Color32, ColorImage, ImageData, PaintCallback, Rect, TextureId,
+    TextureOptions,
+};
+use skia_safe::{Color, Image, Surface};
+
+use crate::Painter;
+
+/// A transparent raster surface of `width` by `height` pixels.
+pub(crate) fn surface(width: i32, height: i32) -> Surface {
+    let mut surface = Surface::new_raster_n32_premul((width, height)).unwrap();
+    surface.canvas().clear(Color::TRANSPARENT);
+    surface
+}
+
+/// An image of `width` by `height` pixels filled with `color`.
+pub(crate) fn solid_image(width: i32, height: i32, color: Color) -> Image {
+    let mut surface = surface(width, height);
+    surface.canvas().clear(color);
+    surface.image_snapshot()
+}
+
+/// The unpremultiplied color of the pixel at `x`, `y` of `surface`.
+pub(crate) fn pixel(surface: &mut Surface, x: i32, y: i32) -> Color {
+    surface.peek_pixels().unwrap().get_color((x, y))
+}
+
+/// The raw pixels of the raster `surface`, to compare two renderings exactly.
+pub(crate) fn surface_bytes(surface: &mut Surface) -> Vec<u8> {
+    surface.peek_pixels().unwrap().bytes().unwrap().to_vec()
+}
+
+/// The unpremultiplied color of the pixel at `x`, `y` of the raster `image`.
+pub(crate) fn image_pixel(image: &Image, x: i32, y: i32) -> Color {
+    image.peek_pixels().unwrap().get_color((x, y))
+}
+
+/// Asserts that every channel of `actual` is within `tolerance` of `expected`.
+#[track_caller]
+pub(crate) fn assert_color_near(actual: Color, expected: Color, tolerance: u8) {
+    let channels = |c: Color| [c.a(), c.r(), c.g(), c.b()];
+    let close = channels(actual)
+        .iter()
+        .zip(channels(expected))
+        .all(|(a, e)| a.abs_diff(e) <= tolerance);
+    assert!(close, "expected {:?}, got {:?}", expected, actual);
+}
+
+/// A painter with a font texture of one white texel, which the untextured shapes of egui
+/// sample like the white texel at the origin of the real font atlas.
+pub(crate) fn painter() -> Painter {
+    let mut painter = Painter::new();
+    upload_white(&mut painter, TextureId::default());
+    painter
+}
+
+/// Uploads a texture of one white texel as `id`.
+pub(crate) fn upload_white(painter: &mut Painter, id: TextureId) {
+    let image = ImageData::Color(ColorImage::new([1, 1], Color32::WHITE));
+    painter
+        .upload_texture(id, &image, TextureOptions::NEAREST)
+        .unwrap();
+}
+
+/// A mesh filling `rect` with `color`.
+pub(crate) fn rect_mesh(rect: Rect, color: Color32) -> Mesh {
+    let mut mesh = Mesh::default();
+    mesh.add_colored_rect(rect, color);
+    mesh
+}
+
+/// `mesh` clipped to `clip_rect`.
+pub(crate) fn mesh_primitive(clip_rect: Rect, mesh: Mesh) -> ClippedPrimitive {
+    ClippedPrimitive {
+        clip_rect,
+        primitive: Primitive::Mesh(mesh),
+    }
+}
+
+/// A rect of `color` clipped to the whole surface, as egui tessellates a filled rect.
+pub(crate) fn rect_primitive(rect: Rect, color: Color32) -> ClippedPrimitive {
+    mesh_primitive(Rect::EVERYTHING, rect_mesh(rect, color))
+}
+
+/// A paint `callback` of any type in `rect`, clipped to the whole surface.
+pub(crate) fn callback_primitive(rect: Rect, callback: impl Any + Send + Sync) -> ClippedPrimitive {
+    ClippedPrimitive {
+        clip_rect: Rect::EVERYTHING,
+        primitive: Primitive::Callback(PaintCallback {
+            rect,
+            callback: Arc::new(callback),
+        }),
+    }
+}
+
+/// A GL context, or `None` without a current GL context, which test runners without a display
+/// don't have.
+#[cfg(feature = "gl")]
+pub(crate) fn gl_context() -> Option<skia_safe::gpu::DirectContext> {
+    skia_safe::gpu::DirectContext::new_gl(None, None)
+}
+
+/// A Metal context on the default device, or `None` on machines without one.
+#[cfg(all(feature = "metal", target_os = "macos"))]
+pub(crate) fn metal_context() -> Option<skia_safe::gpu::DirectContext> {
+    use foreign_types_shared::ForeignType;
+    use skia_safe::gpu::mtl;
+
+    let device = metal::Device::system_default()?;
+    let queue = device.new_command_queue();
+    // skia retains the device and the queue.
+    let backend = unsafe {
+        mtl::BackendContext::new(
+            device.as_ptr() as mtl::Handle,
+            queue.as_ptr() as mtl::Handle,
+            std::ptr::null(),
+        )
+    };
+    skia_safe::gpu::DirectContext::new_metal(&backend, None)
+}
+
+/// A transparent GPU render target of `width` by `height` pixels with `sample_count` samples
+/// per pixel.
+#[cfg(feature = "gpu")]
+pub(crate) fn render_target(
+    context: &mut skia_safe::gpu::DirectContext,
+    width: i32,
+    height: i32,
+    sample_count: usize,
+) -> Surface {
+    let info = skia_safe::ImageInfo::new_n32_premul((width, height), None);
+    let budgeted = skia_safe::Budgeted::Yes;
+    let mut target =
+        Surface::new_render_target(context, budgeted, &info, sample_count, None, None, None)
+            .unwrap();
+    target.canvas().clear(Color::TRANSPARENT);
+    target
+}
+
+/// A raster copy of the pixels of the GPU render `target`.
+#[cfg(feature = "gpu")]
+pub(crate) fn read_back(target: &mut Surface) -> Surface {
+    let mut readback = surface(target.width(), target.height());
+    let read = target.read_pixels_to_pixmap(&readback.peek_pixels().unwrap(), (0, 0));
+    assert!(read);
+    readback
+}
@@ -0,0 +1,194 @@
+//! Test doubles for code driving a [`Painter`](crate::Painter).
+
+use egui::epaint::Primitive;
+use egui::{ClippedPrimitive, Rect, TextureId, TexturesDelta};
+
+use crate::egui_painter::{EguiPainter, PaintTarget};
+
+/// A primitive passed to [`RecordingPainter::paint`], without its vertex data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedPrimitive {
+    Mesh {
+        clip_rect: Rect,
+        texture_id: TextureId,
+        vertices: usize,
+        indices: usize,
+    },
+    Callback {
+        clip_rect: Rect,
+        rect: Rect,
+    },
+}
+
+/// A frame painted by [`RecordingPainter::paint`], with the texture updates since the previous
+/// frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordedFrame {
+    pub dpi: f32,
+    pub primitives: Vec<RecordedPrimitive>,
+    /// The textures set before the frame, in the order they were set.
+    pub textures_set: Vec<TextureId>,
+    /// The textures freed after the frame.
+    pub textures_freed: Vec<TextureId>,
+}
+
+impl RecordedFrame {
+    /// The number of meshes drawn in the frame.
+    pub fn meshes(&self) -> usize {
+        self.primitives
+            .iter()
+            .filter(|primitive| matches!(primitive, RecordedPrimitive::Mesh { .. }))
+            .count()
+    }
+
+    /// The number of paint callbacks drawn in the frame.
+    pub fn callbacks(&self) -> usize {
+        self.primitives.len() - self.meshes()
+    }
+}
+
+/// An [`EguiPainter`] that records the calls made to it instead of drawing anything, e.g. to
+/// assert that a frame uploaded 2 textures and drew 5 meshes.
+///
+/// Paint into `()` if there is no canvas.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingPainter {
+    pub frames: Vec<RecordedFrame>,
+    textures_set: Vec<TextureId>,
+}
+
+impl RecordingPainter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last frame painted, if any.
+    pub fn last_frame(&self) -> Option<&RecordedFrame> {
+        self.frames.last()
+    }
+}
+
+impl EguiPainter for RecordingPainter {
+    fn begin_frame(&mut self, textures_delta: &TexturesDelta) {
+        self.textures_set
+            .extend(textures_delta.set.iter().map(|(id, _)| *id));
+    }
+
+    fn end_frame(&mut self, textures_delta: &TexturesDelta) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.textures_freed.extend(&textures_delta.free);
+        }
+    }
+
+    fn paint(&mut self, _target: &mut dyn PaintTarget, dpi: f32, primitives: &[ClippedPrimitive]) {
+        let primitives = primitives
+            .iter()
+            .map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => RecordedPrimitive::Mesh {
+                    clip_rect: primitive.clip_rect,
+                    texture_id: mesh.texture_id,
+                    vertices: mesh.vertices.len(),
+                    indices: mesh.indices.len(),
+                },
+                Primitive::Callback(callback) => RecordedPrimitive::Callback {
+                    clip_rect: primitive.clip_rect,
+                    rect: callback.rect,
+                },
+            })
+            .collect();
+        self.frames.push(RecordedFrame {
+            dpi,
+            primitives,
+            textures_set: std::mem::take(&mut self.textures_set),
+            textures_freed: Vec::new(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use egui::epaint::ImageDelta;
+    use egui::{pos2, Color32, ColorImage, PaintCallback, TextureOptions};
+
+    use super::*;
+    use crate::test_util::{mesh_primitive, rect_mesh, surface, surface_bytes};
+    use crate::Painter;
+
+    fn rect(x: f32) -> Rect {
+        Rect::from_min_max(pos2(x, 0.0), pos2(x + 4.0, 4.0))
+    }
+
+    /// Five meshes side by side and a callback after them.
+    fn primitives() -> Vec<ClippedPrimitive> {
+        let mut primitives = (0..5)
+            .map(|i| rect(i as f32 * 4.0))
+            .map(|rect| mesh_primitive(rect, rect_mesh(rect, Color32::RED)))
+            .collect::<Vec<_>>();
+        primitives.push(ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Callback(PaintCallback {
+                rect: rect(20.0),
+                callback: Arc::new(()),
+            }),
+        });
+        primitives
+    }
+
+    /// Two textures set, one freed.
+    fn delta() -> TexturesDelta {
+        let image = |color| {
+            let image = ColorImage::new([1, 1], color);
+            ImageDelta::full(image, TextureOptions::NEAREST)
+        };
+        TexturesDelta {
+            set: vec![
+                (TextureId::default(), image(Color32::WHITE)),
+                (TextureId::User(1), image(Color32::BLUE)),
+            ],
+            free: vec![TextureId::User(0)],
+        }
+    }
+
+    /// App code that only knows about the trait.
+    fn draw_app(painter: &mut impl EguiPainter, target: &mut dyn PaintTarget) {
+        painter.paint_frame_with_textures(target, 2.0, &primitives(), &delta());
+    }
+
+    #[test]
+    fn recording_painter_records_the_frame_without_a_canvas() {
+        let mut painter = RecordingPainter::new();
+        draw_app(&mut painter, &mut ());
+
+        let frame = painter.last_frame().unwrap();
+        assert_eq!(frame.dpi, 2.0);
+        let set = vec![TextureId::default(), TextureId::User(1)];
+        assert_eq!(frame.textures_set, set);
+        assert_eq!(frame.meshes(), 5);
+        assert_eq!(frame.callbacks(), 1);
+        assert_eq!(frame.textures_freed, vec![TextureId::User(0)]);
+        assert_eq!(
+            frame.primitives[0],
+            RecordedPrimitive::Mesh {
+                clip_rect: rect(0.0),
+                texture_id: TextureId::default(),
+                vertices: 4,
+                indices: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn painter_draws_through_the_trait_like_through_its_own_methods() {
+        let mut through_trait = surface(48, 8);
+        draw_app(&mut Painter::new(), &mut through_trait);
+
+        // With the trait in scope, the inherent method is still the one called.
+        let mut painter = Painter::new();
+        let mut direct = surface(48, 8);
+        painter.paint_and_update_textures(direct.canvas(), 2.0, primitives(), delta());
+
+        assert!(surface_bytes(&mut through_trait) == surface_bytes(&mut direct));
+    }
+}
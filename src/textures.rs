@@ -0,0 +1,2020 @@
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use egui::epaint::ahash::{AHashMap, AHasher};
+use egui::epaint::ImageDelta;
+use egui::{ImageData, TextureFilter, TextureId, TextureOptions, TexturesDelta};
+use skia_safe::image::CachingHint;
+use skia_safe::runtime_effect::{ChildPtr, ChildType};
+use skia_safe::{
+    AlphaType, BlendMode, ClipOp, Codec, CodecResult, Color, ColorSpace, ColorType,
+    ConditionallySend, Data, EncodedImageFormat, IRect, ISize, Image, ImageInfo, Paint, Pixmap,
+    Point, RuntimeEffect, Sendable, Surface,
+};
+
+use crate::atlas::TextureAtlas;
+use crate::color::{color32_bytes_premul, font_coverage_bytes_premul, FONT_GAMMA};
+#[cfg(feature = "gpu")]
+use crate::gpu_backend::GpuBackend;
+use crate::painter::QualityTier;
+
+pub(crate) struct PaintHandle {
+    pub(crate) paint: Paint,
+    /// The raster image of the texture, also kept for GPU textures for partial updates.
+    pub(crate) image: Image,
+    pub(crate) options: TextureOptions,
+    /// Whether `paint` samples a GPU texture instead of `image`.
+    pub(crate) on_gpu: bool,
+    /// The pieces of a font texture larger than [`TextureStore::with_max_atlas_size`], drawn
+    /// instead of `paint`. Empty for all other textures.
+    pub(crate) tiles: Vec<TextureTile>,
+    /// The hash of the egui image of the last full upload, see [`image_data_hash`]. `None` for
+    /// images not uploaded from egui, and after partial updates and other changes of the pixels.
+    source_hash: Option<u64>,
+}
+
+/// A piece of a texture split by [`TextureStore::with_max_atlas_size`].
+pub(crate) struct TextureTile {
+    /// The pixels of the texture the tile covers.
+    pub(crate) rect: IRect,
+    /// The raster image of the tile, also kept for GPU textures.
+    image: Image,
+    /// Samples the tile with the uv coordinates of the whole texture.
+    pub(crate) paint: Paint,
+}
+
+/// A texture dropped from memory except for its encoded pixels, see
+/// [`TextureStore::with_low_memory`].
+struct DemotedTexture {
+    pixels: DemotedPixels,
+    options: TextureOptions,
+}
+
+/// The pixels of a [`DemotedTexture`].
+enum DemotedPixels {
+    /// The bytes the texture was uploaded from.
+    Encoded(Data),
+    /// The pixels read back on demotion, while the [`Compressor`] compresses them into
+    /// `compressed`. They stay raw if the compression fails.
+    Raw {
+        pixels: RawPixels,
+        compressed: Option<Mutex<Receiver<Option<Sendable<Data>>>>>,
+    },
+    /// The premultiplied RGBA bytes stored as the channels of an unpremultiplied PNG, which
+    /// keeps them exactly, unlike a PNG of the colors.
+    Compressed { data: Data, alpha_type: AlphaType },
+}
+
+/// Premultiplied RGBA pixels without padding.
+#[derive(Clone)]
+struct RawPixels {
+    size: ISize,
+    alpha_type: AlphaType,
+    bytes: Arc<Vec<u8>>,
+}
+
+impl RawPixels {
+    /// Reads the pixels of the raster `image`.
+    fn read(image: &Image) -> Option<Self> {
+        let alpha_type = match image.alpha_type() {
+            AlphaType::Opaque => AlphaType::Opaque,
+            _ => AlphaType::Premul,
+        };
+        let info = ImageInfo::new(image.dimensions(), ColorType::RGBA8888, alpha_type, None);
+        let mut bytes = vec![0; info.compute_min_byte_size()];
+        let row_bytes = info.min_row_bytes();
+        let read = image.read_pixels(&info, &mut bytes, row_bytes, (0, 0), CachingHint::Disallow);
+        read.then(|| RawPixels {
+            size: image.dimensions(),
+            alpha_type,
+            bytes: Arc::new(bytes),
+        })
+    }
+
+    fn image(&self) -> Option<Image> {
+        let info = ImageInfo::new(self.size, ColorType::RGBA8888, self.alpha_type, None);
+        let row_bytes = info.min_row_bytes();
+        Image::from_raster_data(&info, Data::new_copy(&self.bytes), row_bytes)
+    }
+
+    /// Compresses the bytes into a PNG, see [`DemotedPixels::Compressed`].
+    fn compress(&self) -> Option<Sendable<Data>> {
+        let info = ImageInfo::new(self.size, ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let pixmap = Pixmap::new(&info, &self.bytes, info.min_row_bytes());
+        // A new data has no other references, so it can be sent back.
+        let png = pixmap.encode(EncodedImageFormat::PNG, 100)?;
+        png.wrap_send().ok()
+    }
+}
+
+/// The thread compressing the pixels of the demoted textures of a [`TextureStore`], one after
+/// another. It stops once the store is dropped.
+///
+/// The channel ends are behind mutexes to keep the store `Sync`.
+struct Compressor {
+    jobs: Mutex<Sender<(RawPixels, Sender<Option<Sendable<Data>>>)>>,
+}
+
+impl Compressor {
+    fn spawn() -> Self {
+        let (jobs, queue) = mpsc::channel::<(RawPixels, Sender<_>)>();
+        std::thread::spawn(move || {
+            for (pixels, compressed) in queue {
+                // Nobody waits for the result anymore if the texture came back meanwhile.
+                let _ = compressed.send(pixels.compress());
+            }
+        });
+        Self {
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    /// Queues `pixels` for compression, the returned receiver gets the result.
+    fn compress(&self, pixels: &RawPixels) -> Mutex<Receiver<Option<Sendable<Data>>>> {
+        let (compressed, receiver) = mpsc::channel();
+        // Without the thread the receiver is disconnected, and the pixels stay raw.
+        let _ = self.jobs.lock().unwrap().send((pixels.clone(), compressed));
+        Mutex::new(receiver)
+    }
+}
+
+impl DemotedPixels {
+    /// Keeps the pixels of `image`: the bytes it was decoded from, or else its pixels, which
+    /// `compressor` compresses to keep the work off the frame.
+    fn new(image: &Image, compressor: &Compressor) -> Option<Self> {
+        if let Some(data) = image.encoded_data() {
+            return Some(DemotedPixels::Encoded(data));
+        }
+        let pixels = RawPixels::read(image)?;
+        let compressed = Some(compressor.compress(&pixels));
+        Some(DemotedPixels::Raw { pixels, compressed })
+    }
+
+    /// Swaps the raw pixels for the compressed ones once they are ready.
+    fn finish_compression(&mut self) {
+        if let DemotedPixels::Raw { pixels, compressed } = self {
+            let result = match compressed {
+                Some(receiver) => receiver.get_mut().unwrap().try_recv(),
+                None => return,
+            };
+            if matches!(result, Err(TryRecvError::Empty)) {
+                return;
+            }
+            *compressed = None;
+            if let Ok(Some(data)) = result {
+                let alpha_type = pixels.alpha_type;
+                *self = DemotedPixels::Compressed {
+                    data: data.unwrap(),
+                    alpha_type,
+                };
+            }
+        }
+    }
+
+    fn decode(&self) -> Option<Image> {
+        match self {
+            DemotedPixels::Encoded(data) => Image::from_encoded(data.clone()),
+            DemotedPixels::Raw { pixels, .. } => pixels.image(),
+            DemotedPixels::Compressed { data, alpha_type } => {
+                let mut codec = Codec::from_data(data.clone())?;
+                let size = codec.dimensions();
+                let info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Unpremul, None);
+                let mut bytes = vec![0; info.compute_min_byte_size()];
+                let row_bytes = info.min_row_bytes();
+                let decoded = codec.get_pixels_with_options(&info, &mut bytes, row_bytes, None);
+                if decoded != CodecResult::Success {
+                    return None;
+                }
+                let pixels = RawPixels {
+                    size,
+                    alpha_type: *alpha_type,
+                    bytes: Arc::new(bytes),
+                };
+                pixels.image()
+            }
+        }
+    }
+
+    /// The memory the pixels take up.
+    fn size(&self) -> usize {
+        match self {
+            DemotedPixels::Encoded(data) | DemotedPixels::Compressed { data, .. } => data.size(),
+            DemotedPixels::Raw { pixels, .. } => pixels.bytes.len(),
+        }
+    }
+}
+
+/// The font texture converted on a background thread, see
+/// [`TextureStore::with_async_atlas_upload`].
+struct PendingAtlas {
+    thread: JoinHandle<Result<Sendable<Image>, TextureError>>,
+    options: TextureOptions,
+    /// Partial updates that arrived during the conversion, applied once it is done.
+    updates: Vec<ImageDelta>,
+}
+
+impl PendingAtlas {
+    fn spawn(delta: &ImageDelta) -> Self {
+        let image = delta.image.clone();
+        Self {
+            thread: std::thread::spawn(move || {
+                // A new image has no other references, so it can be sent back.
+                image_from_data(&image)?
+                    .wrap_send()
+                    .map_err(|_| TextureError::ImageCreation)
+            }),
+            options: delta.options,
+            updates: Vec::new(),
+        }
+    }
+}
+
+/// Errors returned by the texture functions of the [`crate::Painter`] and [`TextureStore`].
+#[derive(Debug)]
+pub enum TextureError {
+    /// skia could not create an image from the given pixel data.
+    ImageCreation,
+    /// The shader for sampling the texture could not be created.
+    ShaderCreation(String),
+    /// The texture has not been uploaded.
+    UnknownTexture(TextureId),
+    /// The pixel data passed in is malformed.
+    InvalidData(String),
+    /// The encoded image could not be decoded.
+    Decode,
+    /// Reading a file failed.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::ImageCreation => write!(f, "failed to create image from pixel data"),
+            TextureError::ShaderCreation(err) => write!(f, "failed to create shader: {}", err),
+            TextureError::UnknownTexture(id) => write!(f, "unknown texture {:?}", id),
+            TextureError::InvalidData(err) => write!(f, "invalid pixel data: {}", err),
+            TextureError::Decode => write!(f, "failed to decode image"),
+            TextureError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(err: std::io::Error) -> Self {
+        TextureError::Io(err)
+    }
+}
+
+/// How the pixels of a texture are interpreted when they are drawn, see
+/// [`TextureStore::set_texture_color_usage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextureColorUsage {
+    /// sRGB encoded colors, converted to the color space of the target. The default, and what
+    /// egui's own textures are.
+    #[default]
+    Srgb,
+    /// Linear data, tagged with the linear sRGB color space, e.g. heightmaps or lookup tables
+    /// sampled by a texture shader. On a target with an sRGB transfer function the values are
+    /// encoded on the way out instead of being passed through as colors.
+    Linear,
+}
+
+impl TextureColorUsage {
+    fn color_space(self) -> ColorSpace {
+        match self {
+            TextureColorUsage::Srgb => ColorSpace::new_srgb(),
+            TextureColorUsage::Linear => ColorSpace::new_srgb_linear(),
+        }
+    }
+}
+
+/// Number of textures held by a [`TextureStore`] and the memory their pixels take up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureStats {
+    /// Textures with decoded pixels.
+    pub textures: usize,
+    /// The memory of the decoded pixels.
+    pub bytes: usize,
+    /// Textures only kept encoded, see [`TextureStore::with_low_memory`].
+    pub demoted: usize,
+    /// The memory of the encoded pixels of the demoted textures.
+    pub demoted_bytes: usize,
+    /// Textures sampled on the opaque fast path, see [`TextureStore::set_texture_opaque`].
+    pub opaque: usize,
+}
+
+/// How often full uploads of a texture reused the existing image and paint because the pixels,
+/// size and options were unchanged, see [`TextureStore::cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureCacheStats {
+    /// Full uploads that reused the image and paint of the texture.
+    pub hits: u64,
+    /// Full uploads that created a new image and paint.
+    pub misses: u64,
+}
+
+/// The textures used by one or more [`crate::Painter`]s.
+///
+/// Every painter owns a store by default. To share textures between several painters (e.g. one
+/// painter per swapchain image), create the store once, wrap it in an `Arc<RwLock<_>>` and pass it
+/// to [`crate::Painter::with_texture_store`]. Apply the frame's [`TexturesDelta`] once to the
+/// store, paint with every painter and then finish the frame:
+///
+/// ```ignore
+/// store.write().unwrap().set_textures(&textures_delta)?;
+/// painter_a.paint_primitives(canvas_a, dpi, &primitives);
+/// painter_b.paint_primitives(canvas_b, dpi, &primitives);
+/// let mut store = store.write().unwrap();
+/// store.free_textures(&textures_delta.free);
+/// store.end_frame();
+/// ```
+///
+/// Freed textures stay readable until [`Self::end_frame`] was called `frames_in_flight + 1` times,
+/// so frames that are still being drawn by another painter never lose their textures.
+#[derive(Default)]
+pub struct TextureStore {
+    paints: AHashMap<TextureId, PaintHandle>,
+    retired: Vec<(u64, TextureId, PaintHandle)>,
+    generation: u64,
+    frames_in_flight: u64,
+    effect: Option<RuntimeEffect>,
+    texture_effects: AHashMap<TextureId, RuntimeEffect>,
+    /// The textures not using [`TextureColorUsage::Srgb`].
+    color_usages: AHashMap<TextureId, TextureColorUsage>,
+    /// The textures not combined with the vertex colors by [`BlendMode::Modulate`].
+    blend_modes: AHashMap<TextureId, BlendMode>,
+    /// The opacity set by the host, overriding the alpha type of the image.
+    opaque: AHashMap<TextureId, bool>,
+    raw_image_shaders: bool,
+    atlas: Option<TextureAtlas>,
+    /// Whether uploads are packed into `atlas`, which keeps the textures packed before.
+    pack_textures: bool,
+    /// The frames a texture stays decoded without being drawn, see [`Self::with_low_memory`].
+    idle_frames: Option<u64>,
+    /// The generation every texture was last drawn or uploaded in.
+    last_used: AHashMap<TextureId, u64>,
+    demoted: AHashMap<TextureId, DemotedTexture>,
+    /// Started with the first demoted texture.
+    compressor: Option<Compressor>,
+    quality: QualityTier,
+    async_atlas_upload: bool,
+    max_atlas_size: Option<u32>,
+    pending_atlas: Option<PendingAtlas>,
+    /// Whether the font texture arrived since the last [`Self::take_arrived_atlas`].
+    arrived_atlas: bool,
+    cache_stats: TextureCacheStats,
+}
+
+/// The tile mode of the texture shaders.
+pub(crate) const TILE_MODE: skia_safe::TileMode = skia_safe::TileMode::Clamp;
+
+const SKSL_SHADER: &'static str = r#"
+uniform shader image;
+vec4 main(float2 coord) {
+    return image.eval(coord);
+}
+"#;
+
+impl TextureStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps freed textures alive for `frames` additional frames.
+    pub fn with_frames_in_flight(mut self, frames: u64) -> Self {
+        self.frames_in_flight = frames;
+        self
+    }
+
+    /// Samples the textures through the SKSL shader `sksl` instead of the built-in passthrough
+    /// shader, e.g. to color grade every texture.
+    ///
+    /// The shader must declare exactly one child, `uniform shader image;`, which is the texture,
+    /// and no other uniforms. `main` gets the coordinate to pass on to `image.eval`.
+    pub fn with_texture_shader(mut self, sksl: &str) -> Result<Self, TextureError> {
+        self.effect = Some(texture_effect(sksl)?);
+        Ok(self)
+    }
+
+    /// Samples the textures with plain image shaders instead of wrapping them in the runtime
+    /// effect, which saves compiling the effect and a shader indirection per draw.
+    ///
+    /// The built-in effect only passes the image through, so standard textures look the same
+    /// either way; it is kept as the default because it has been used since the first release
+    /// and some backends may rely on it. A shader set with [`Self::with_texture_shader`] is not
+    /// used in this mode. Only affects textures uploaded afterwards.
+    pub fn with_raw_image_shaders(mut self, raw: bool) -> Self {
+        self.raw_image_shaders = raw;
+        self
+    }
+
+    /// Packs textures of at most 64 by 64 pixels into one shared image, so apps with thousands
+    /// of small textures like icons don't need one skia image per texture. Only affects textures
+    /// uploaded afterwards.
+    ///
+    /// Packed textures are sampled without mipmaps and stay raster images on the GPU, where skia
+    /// uploads the whole atlas once per change. Textures that don't fit into the atlas any more
+    /// stay standalone images.
+    pub fn with_texture_atlas(mut self, enabled: bool) -> Self {
+        self.set_texture_atlas(enabled);
+        self
+    }
+
+    /// Turns the atlas of [`Self::with_texture_atlas`] on or off for the textures uploaded
+    /// afterwards. Textures packed before stay in the atlas until they are uploaded again.
+    pub fn set_texture_atlas(&mut self, enabled: bool) {
+        if enabled && self.atlas.is_none() {
+            self.atlas = TextureAtlas::new();
+        }
+        self.pack_textures = enabled;
+    }
+
+    /// Drops the decoded pixels of textures that weren't drawn for `idle_frames` frames and
+    /// keeps them encoded instead, for devices with little memory. Demoted textures are decoded
+    /// again when a frame draws them, see [`Self::mark_used`].
+    ///
+    /// Textures uploaded from encoded images keep their original bytes. The pixels of all others
+    /// are compressed losslessly, one texture after another on a single thread of the store,
+    /// and kept raw until that is done, so they come back exactly. The font texture and
+    /// textures in the atlas are never demoted.
+    /// Demoted GPU textures come back as raster images until they are uploaded again.
+    pub fn with_low_memory(mut self, idle_frames: u64) -> Self {
+        self.set_low_memory(idle_frames);
+        self
+    }
+
+    /// Sets the idle frames of [`Self::with_low_memory`] on an existing store.
+    pub fn set_low_memory(&mut self, idle_frames: u64) {
+        self.idle_frames = Some(idle_frames);
+    }
+
+    /// Samples the textures uploaded afterwards at `quality`: [`QualityTier::Low`] samples every
+    /// texture with the nearest neighbour and without mipmaps, [`QualityTier::High`] samples
+    /// linearly filtered textures with a cubic resampler instead, which has no mipmaps either.
+    /// [`QualityTier::Medium`] (the default) honors the options of each texture.
+    pub fn with_quality(mut self, quality: QualityTier) -> Self {
+        self.set_quality(quality);
+        self
+    }
+
+    /// Sets the quality of [`Self::with_quality`] on an existing store.
+    pub fn set_quality(&mut self, quality: QualityTier) {
+        self.quality = quality;
+    }
+
+    /// Converts the first full upload of the font texture on a background thread instead of
+    /// during [`Self::set_textures`], so a large font atlas doesn't stall the first frame.
+    ///
+    /// Text is left out of the frames painted until the conversion is done, the texture is then
+    /// swapped in by the next [`Self::set_textures`] together with the partial updates egui made
+    /// in the meantime. Poll [`Self::has_pending_uploads`] to keep painting until then. Later
+    /// uploads of the font texture, e.g. after the pixels per point changed, happen in place.
+    /// Off by default.
+    pub fn with_async_atlas_upload(mut self, enabled: bool) -> Self {
+        self.set_async_atlas_upload(enabled);
+        self
+    }
+
+    /// Turns [`Self::with_async_atlas_upload`] on or off for an existing store.
+    pub fn set_async_atlas_upload(&mut self, enabled: bool) {
+        self.async_atlas_upload = enabled;
+    }
+
+    /// Splits the font texture into several images of at most `size` pixels per side if it is
+    /// wider or taller, for GPUs that can't hold the whole font atlas in one texture. Only
+    /// affects font textures uploaded afterwards.
+    ///
+    /// Neighbouring tiles overlap by a quarter of `size`, and every triangle is drawn from a
+    /// tile that holds its whole uv rect, so glyphs are never cut at a tile edge. Other textures
+    /// are never split, as their triangles, e.g. an image drawn as one quad, may span more than
+    /// the overlap.
+    pub fn with_max_atlas_size(mut self, size: u32) -> Self {
+        self.set_max_atlas_size(size);
+        self
+    }
+
+    /// Sets the tile size of [`Self::with_max_atlas_size`] on an existing store.
+    pub fn set_max_atlas_size(&mut self, size: u32) {
+        self.max_atlas_size = Some(size.max(4));
+    }
+
+    /// Whether a texture is still converted in the background, see
+    /// [`Self::with_async_atlas_upload`].
+    pub fn has_pending_uploads(&self) -> bool {
+        self.pending_atlas.is_some()
+    }
+
+    /// Whether texture `id` is still converted in the background.
+    pub(crate) fn is_pending(&self, id: TextureId) -> bool {
+        id == TextureId::default() && self.pending_atlas.is_some()
+    }
+
+    /// Whether texture `id` is drawn from a GPU texture, which stays valid as long as the
+    /// texture isn't changed.
+    pub(crate) fn is_on_gpu(&self, id: TextureId) -> bool {
+        self.paints.get(&id).map_or(false, |handle| handle.on_gpu)
+    }
+
+    /// Whether the font texture converted in the background was swapped in since the last call,
+    /// so the painter uploads it to the GPU.
+    pub(crate) fn take_arrived_atlas(&mut self) -> bool {
+        std::mem::take(&mut self.arrived_atlas)
+    }
+
+    /// Swaps in the font texture converted in the background if it is done.
+    fn finish_atlas_upload(&mut self) -> Result<(), TextureError> {
+        let finished = self.pending_atlas.as_ref();
+        if !finished.map_or(false, |pending| pending.thread.is_finished()) {
+            return Ok(());
+        }
+        let pending = self.pending_atlas.take().unwrap();
+        let id = TextureId::default();
+        // A failed conversion leaves the font texture out, egui uploads it again when the fonts
+        // change.
+        let image = pending
+            .thread
+            .join()
+            .map_err(|_| TextureError::ImageCreation)??;
+        self.insert_image(id, image.unwrap(), pending.options)?;
+        for delta in &pending.updates {
+            match delta.pos {
+                None => image_from_data(&delta.image)
+                    .and_then(|image| self.insert_image(id, image, delta.options)),
+                Some(pos) => self.insert_region(id, pos, &delta.image),
+            }?;
+        }
+        self.arrived_atlas = true;
+        Ok(())
+    }
+
+    /// Whether idle textures are demoted, see [`Self::with_low_memory`].
+    pub(crate) fn low_memory(&self) -> bool {
+        self.idle_frames.is_some()
+    }
+
+    /// Marks the textures `ids` as drawn in the current frame and decodes the demoted ones among
+    /// them again, see [`Self::with_low_memory`].
+    ///
+    /// The [`crate::Painter`] calls this for the textures of every frame it paints.
+    pub fn mark_used(
+        &mut self,
+        ids: impl IntoIterator<Item = TextureId>,
+    ) -> Result<(), TextureError> {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        self.record_used(ids.iter().copied());
+        self.promote(ids)
+    }
+
+    /// Marks the textures `ids` as drawn in the current frame without decoding them.
+    pub(crate) fn record_used(&mut self, ids: impl IntoIterator<Item = TextureId>) {
+        for id in ids {
+            self.last_used.insert(id, self.generation);
+        }
+    }
+
+    /// Decodes the demoted textures among `ids` again and returns the first error. Textures that
+    /// can't be decoded stay demoted, the others are decoded all the same.
+    pub(crate) fn promote(
+        &mut self,
+        ids: impl IntoIterator<Item = TextureId>,
+    ) -> Result<(), TextureError> {
+        let mut result = Ok(());
+        for id in ids {
+            let demoted = match self.demoted.get(&id) {
+                Some(demoted) => demoted,
+                None => continue,
+            };
+            let options = demoted.options;
+            let promoted = demoted
+                .pixels
+                .decode()
+                .ok_or(TextureError::Decode)
+                .and_then(|image| self.insert_image(id, image, options));
+            result = result.and(promoted);
+        }
+        result
+    }
+
+    /// Whether texture `id` is only kept encoded, see [`Self::with_low_memory`].
+    pub(crate) fn is_demoted(&self, id: TextureId) -> bool {
+        self.demoted.contains_key(&id)
+    }
+
+    /// Demotes the textures idle for longer than [`Self::with_low_memory`] allows.
+    fn demote_idle(&mut self, idle_frames: u64) {
+        let generation = self.generation;
+        let atlas = self.atlas.as_ref();
+        let idle = self
+            .paints
+            .keys()
+            .filter(|id| **id != TextureId::default())
+            .filter(|id| atlas.map_or(true, |atlas| !atlas.regions.contains_key(*id)))
+            .filter(|id| {
+                let last_used = self.last_used.get(*id).copied().unwrap_or(0);
+                generation.saturating_sub(last_used) > idle_frames
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for id in idle {
+            let compressor = self.compressor.get_or_insert_with(Compressor::spawn);
+            let handle = &self.paints[&id];
+            if let Some(pixels) = DemotedPixels::new(&handle.image, compressor) {
+                let options = handle.options;
+                self.paints.remove(&id);
+                self.demoted.insert(id, DemotedTexture { pixels, options });
+            }
+        }
+    }
+
+    /// Samples texture `id` through the SKSL shader `sksl` instead of the shader of the store,
+    /// until the texture is freed. See [`Self::with_texture_shader`] for the requirements on the
+    /// shader. Also applies with [`Self::with_raw_image_shaders`].
+    ///
+    /// Can be called before the texture is uploaded. An uploaded GPU texture is sampled from its
+    /// raster image until it is uploaded to the GPU again.
+    pub fn set_texture_shader(&mut self, id: TextureId, sksl: &str) -> Result<(), TextureError> {
+        self.texture_effects.insert(id, texture_effect(sksl)?);
+        self.remake_paint(id)
+    }
+
+    /// Applies `gamma` to the coverage of the font texture, making text heavier for values above
+    /// `1.0` and lighter below, e.g. `1.8` to `2.2` to match the weight of ClearType or FreeType
+    /// text. `1.0` (the default) draws the coverage egui rasterized.
+    ///
+    /// The curve is applied by a shader of the font texture, see [`Self::set_texture_shader`],
+    /// replacing a shader set for [`TextureId::default`] before.
+    pub fn set_font_gamma(&mut self, gamma: f32) -> Result<(), TextureError> {
+        let id = TextureId::default();
+        if gamma == 1.0 {
+            self.texture_effects.remove(&id);
+        } else {
+            self.texture_effects.insert(id, texture_effect(&font_gamma_sksl(gamma))?);
+        }
+        self.remake_paint(id)
+    }
+
+    /// Builds the paints of texture `id` again after its shader changed.
+    fn remake_paint(&mut self, id: TextureId) -> Result<(), TextureError> {
+        if let Some(handle) = self.paints.get(&id) {
+            let (image, options) = (handle.image.clone(), handle.options);
+            self.insert_image(id, image, options)?;
+        }
+        Ok(())
+    }
+
+    /// Sets how the pixels of texture `id` are interpreted, until the texture is freed. Partial
+    /// updates keep the usage.
+    ///
+    /// Can be called before the texture is uploaded, including for the textures egui uploads
+    /// through [`Self::set_textures`]. Linear textures are never packed into the atlas. An
+    /// uploaded GPU texture is sampled from its raster image until it is uploaded to the GPU
+    /// again.
+    pub fn set_texture_color_usage(
+        &mut self,
+        id: TextureId,
+        usage: TextureColorUsage,
+    ) -> Result<(), TextureError> {
+        match usage {
+            TextureColorUsage::Srgb => self.color_usages.remove(&id),
+            _ => self.color_usages.insert(id, usage),
+        };
+        if let Some(handle) = self.paints.get(&id) {
+            let (image, options) = (handle.image.clone(), handle.options);
+            self.insert_image(id, image, options)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the blend mode combining texture `id` (src) with the vertex colors (dst) of the meshes
+    /// drawing it, until the texture is freed. The result is composited onto the canvas with
+    /// SrcOver either way.
+    ///
+    /// The default, [`BlendMode::Modulate`], is the `texture * vertex color` egui expects for
+    /// font coverage and images alike. [`BlendMode::Src`] ignores the vertex colors, e.g. for a
+    /// video frame that must never be tinted. Can be called before the texture is uploaded.
+    pub fn set_texture_blend_mode(&mut self, id: TextureId, mode: BlendMode) {
+        match mode {
+            BlendMode::Modulate => self.blend_modes.remove(&id),
+            _ => self.blend_modes.insert(id, mode),
+        };
+    }
+
+    /// The blend mode of texture `id`, see [`Self::set_texture_blend_mode`].
+    pub fn texture_blend_mode(&self, id: TextureId) -> BlendMode {
+        self.blend_modes
+            .get(&id)
+            .copied()
+            .unwrap_or(BlendMode::Modulate)
+    }
+
+    /// Marks texture `id` as opaque or not, until the texture is freed. By default a texture is
+    /// opaque if its image has the opaque alpha type, which egui images get when all their
+    /// pixels are opaque and decoded files get from formats without alpha, like JPEG.
+    ///
+    /// Opaque textures are sampled with a plain image shader instead of the runtime effect,
+    /// unless they have a shader of their own from [`Self::set_texture_shader`]. Only mark
+    /// textures whose pixels are all opaque. Can be called before the texture is uploaded.
+    pub fn set_texture_opaque(&mut self, id: TextureId, opaque: bool) -> Result<(), TextureError> {
+        self.opaque.insert(id, opaque);
+        if let Some(handle) = self.paints.get(&id) {
+            let (image, options) = (handle.image.clone(), handle.options);
+            self.insert_image(id, image, options)?;
+        }
+        Ok(())
+    }
+
+    /// Whether texture `id` with `image` is sampled on the opaque fast path, see
+    /// [`Self::set_texture_opaque`].
+    fn is_opaque(&self, id: TextureId, image: &Image) -> bool {
+        let packed = self.atlas.as_ref().map_or(false, |atlas| atlas.regions.contains_key(&id));
+        !packed
+            && !self.texture_effects.contains_key(&id)
+            && self.opaque.get(&id).copied().unwrap_or_else(|| image.is_opaque())
+    }
+
+    /// Whether textures are sampled with plain image shaders, see
+    /// [`Self::with_raw_image_shaders`].
+    pub(crate) fn raw_image_shaders(&self) -> bool {
+        self.raw_image_shaders
+    }
+
+    /// Number of times [`Self::end_frame`] has been called.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn stats(&self) -> TextureStats {
+        let handles = self
+            .paints
+            .values()
+            .chain(self.retired.iter().map(|(_, _, handle)| handle));
+        let mut stats = TextureStats::default();
+        for handle in handles {
+            stats.textures += 1;
+            stats.bytes += handle.image.width() as usize * handle.image.height() as usize * 4;
+        }
+        stats.opaque = self
+            .paints
+            .iter()
+            .filter(|(id, handle)| self.is_opaque(**id, &handle.image))
+            .count();
+        for demoted in self.demoted.values() {
+            stats.demoted += 1;
+            stats.demoted_bytes += demoted.pixels.size();
+        }
+        stats
+    }
+
+    /// The hits and misses of full uploads on the textures already in the store, counted since
+    /// the store was created.
+    ///
+    /// egui re-sends whole textures, e.g. the font texture after it grew, with the same pixels
+    /// more often than one would expect. Such uploads keep the image, paint and GPU texture of
+    /// the texture instead of converting the pixels and creating the shader again.
+    ///
+    /// Every full upload is hashed to be recognized, which costs a pass over its pixels. An
+    /// upload changing only the options counts as a miss, but keeps the image and only creates
+    /// the paint again.
+    pub fn cache_stats(&self) -> TextureCacheStats {
+        self.cache_stats
+    }
+
+    /// Applies the `set` part of `textures_delta`. Call this before [`Self::free_textures`] with
+    /// the `free` part of the same delta, so a texture set and freed in one delta is still drawn
+    /// by its frame.
+    ///
+    /// The updates of each texture are applied in the order of `TexturesDelta::set`: a partial
+    /// update always lands on the full upload (or the earlier partial updates) of the same
+    /// texture that precede it. Updates of different textures are independent and applied
+    /// sorted by id, so the atlas is packed the same way however they were interleaved. Don't
+    /// dedup the list before passing it in, and use [`TexturesDelta::append`] to merge deltas of
+    /// skipped frames.
+    ///
+    /// Returns the first error, the other updates are applied all the same.
+    pub fn set_textures(&mut self, textures_delta: &TexturesDelta) -> Result<(), TextureError> {
+        let mut result = self.finish_atlas_upload();
+        // The sort is stable, which keeps the order of the updates of each texture.
+        let mut updates = textures_delta.set.iter().collect::<Vec<_>>();
+        updates.sort_by_key(|(id, _)| *id);
+        for (id, image_delta) in updates {
+            if *id == TextureId::default() {
+                if let Some(pending) = &mut self.pending_atlas {
+                    pending.updates.push(image_delta.clone());
+                    continue;
+                }
+                if self.async_atlas_upload
+                    && image_delta.pos.is_none()
+                    && !self.paints.contains_key(id)
+                {
+                    self.pending_atlas = Some(PendingAtlas::spawn(image_delta));
+                    continue;
+                }
+            }
+            let update = match image_delta.pos {
+                None => self.insert_data(*id, &image_delta.image, image_delta.options),
+                Some(pos) => self.insert_region(*id, pos, &image_delta.image),
+            };
+            result = result.and(update);
+        }
+        // The atlas is updated once for all textures packed into it.
+        self.refresh_atlas().and(result)
+    }
+
+    /// Frees the textures `ids`. They are only dropped by a later [`Self::end_frame`].
+    pub fn free_textures(&mut self, ids: &[TextureId]) {
+        for id in ids {
+            if let Some(handle) = self.paints.remove(id) {
+                self.retired.push((self.generation, *id, handle));
+            }
+            self.texture_effects.remove(id);
+            self.color_usages.remove(id);
+            self.blend_modes.remove(id);
+            self.opaque.remove(id);
+            self.last_used.remove(id);
+            self.demoted.remove(id);
+            if let Some(atlas) = &mut self.atlas {
+                atlas.regions.remove(id);
+            }
+            if *id == TextureId::default() {
+                self.pending_atlas = None;
+            }
+        }
+    }
+
+    /// Marks the end of a frame and drops the freed textures no frame in flight can reference.
+    pub fn end_frame(&mut self) {
+        self.generation += 1;
+        let generation = self.generation;
+        let frames_in_flight = self.frames_in_flight;
+        self.retired
+            .retain(|(freed, _, _)| freed + frames_in_flight >= generation);
+        if let Some(idle_frames) = self.idle_frames {
+            for demoted in self.demoted.values_mut() {
+                demoted.pixels.finish_compression();
+            }
+            self.demote_idle(idle_frames);
+        }
+    }
+
+    /// Uploads `image` as the texture `id`, replacing any previous texture with that id.
+    ///
+    /// This is what [`crate::Painter::paint_and_update_textures`] does for full texture updates,
+    /// and can be used to upload textures outside of egui's [`TexturesDelta`] mechanism, e.g. to
+    /// pre-upload assets during a loading screen.
+    pub fn upload_texture(
+        &mut self,
+        id: TextureId,
+        image: &ImageData,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        let image = image_from_data(image)?;
+        self.upload_image(id, image, options)
+    }
+
+    /// Uploads an existing skia [`Image`] as the texture `id`, replacing any previous texture
+    /// with that id.
+    pub fn upload_image(
+        &mut self,
+        id: TextureId,
+        image: Image,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        self.insert_image(id, image, options)
+    }
+
+    /// Replaces the region of texture `id` starting at `pos` with `image`.
+    ///
+    /// The texture must have been uploaded before with [`Self::upload_texture`].
+    pub fn update_texture_region(
+        &mut self,
+        id: TextureId,
+        pos: [usize; 2],
+        image: &ImageData,
+    ) -> Result<(), TextureError> {
+        self.insert_region(id, pos, image)
+    }
+
+    /// Clears `rect` of texture `id` to transparent, for hosts managing their own atlas in a
+    /// user texture. Parts of `rect` outside the texture are ignored.
+    pub fn free_texture_region(&mut self, id: TextureId, rect: IRect) -> Result<(), TextureError> {
+        self.promote([id])?;
+        let handle = self
+            .paints
+            .get(&id)
+            .ok_or(TextureError::UnknownTexture(id))?;
+        let old_image = &handle.image;
+        let mut surface = Surface::new_raster_n32_premul(old_image.dimensions())
+            .ok_or(TextureError::ImageCreation)?;
+
+        let canvas = surface.canvas();
+        let mut copy = Paint::default();
+        copy.set_blend_mode(BlendMode::Src);
+        canvas.draw_image(old_image, Point::new(0.0, 0.0), Some(&copy));
+        canvas.clip_irect(rect, ClipOp::Intersect);
+        canvas.clear(Color::TRANSPARENT);
+
+        let options = handle.options;
+        self.insert_image(id, surface.image_snapshot(), options)
+    }
+
+    /// Uploads the egui image `data` as the texture `id`, unless the texture already holds the
+    /// same pixels with the same options.
+    fn insert_data(
+        &mut self,
+        id: TextureId,
+        data: &ImageData,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        let hash = image_data_hash(data);
+        if let Some(handle) = self.paints.get(&id) {
+            let same_pixels = handle.source_hash == Some(hash);
+            let same_options = handle.options == options;
+            if same_pixels && same_options {
+                self.cache_stats.hits += 1;
+            } else {
+                self.cache_stats.misses += 1;
+            }
+            if same_pixels {
+                if self.idle_frames.is_some() {
+                    self.last_used.insert(id, self.generation);
+                }
+                if !same_options {
+                    self.set_options(id, options)?;
+                }
+                return Ok(());
+            }
+        }
+        self.insert_image(id, image_from_data(data)?, options)?;
+        self.paints.get_mut(&id).unwrap().source_hash = Some(hash);
+        Ok(())
+    }
+
+    /// Samples the image texture `id` already holds with `options` instead, dropping its GPU
+    /// texture until it is uploaded again.
+    fn set_options(&mut self, id: TextureId, options: TextureOptions) -> Result<(), TextureError> {
+        let image = self.paints[&id].image.clone();
+        let tiles = self.make_tiles(id, &image, options)?;
+        let paint = if tiles.is_empty() {
+            self.make_paint(id, &image, options)?
+        } else {
+            Paint::default()
+        };
+        let handle = self.paints.get_mut(&id).unwrap();
+        handle.paint = paint;
+        handle.options = options;
+        handle.tiles = tiles;
+        handle.on_gpu = false;
+        Ok(())
+    }
+
+    /// Uploads `image` as the texture `id`, packing it into the atlas if possible. The paints of
+    /// packed textures are only usable after the next [`Self::refresh_atlas`].
+    fn insert_image(
+        &mut self,
+        id: TextureId,
+        image: Image,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        self.demoted.remove(&id);
+        if self.idle_frames.is_some() {
+            self.last_used.insert(id, self.generation);
+        }
+        let usage = self.color_usages.get(&id).copied().unwrap_or_default();
+        let image = image
+            .reinterpret_color_space(usage.color_space())
+            .ok_or(TextureError::ImageCreation)?;
+        // The font texture is updated in place every few frames, and usually too large anyway.
+        // The atlas is untagged, which skia treats as sRGB.
+        let packable = self.pack_textures && id != TextureId::default();
+        let region = match &mut self.atlas {
+            Some(atlas) if packable && usage == TextureColorUsage::Srgb => atlas.insert(id, &image),
+            _ => None,
+        };
+        let (paint, tiles) = match region {
+            Some(_) => (Paint::default(), Vec::new()),
+            None => {
+                if let Some(atlas) = &mut self.atlas {
+                    atlas.regions.remove(&id);
+                }
+                match self.make_tiles(id, &image, options)? {
+                    tiles if tiles.is_empty() => (self.make_paint(id, &image, options)?, tiles),
+                    tiles => (Paint::default(), tiles),
+                }
+            }
+        };
+        self.paints.insert(
+            id,
+            PaintHandle {
+                paint,
+                image,
+                options,
+                on_gpu: false,
+                tiles,
+                source_hash: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether textures were packed into the atlas since the last [`Self::refresh_atlas`].
+    pub(crate) fn atlas_dirty(&self) -> bool {
+        self.atlas.as_ref().map_or(false, |atlas| atlas.dirty)
+    }
+
+    /// Takes a new snapshot of the atlas if textures were packed into it, and points the paints
+    /// of all packed textures to it.
+    ///
+    /// Uploads only mark the atlas dirty, so a batch of them copies the atlas once: at the end of
+    /// [`Self::set_textures`], or before the next frame the painter draws.
+    pub(crate) fn refresh_atlas(&mut self) -> Result<(), TextureError> {
+        let atlas = match &mut self.atlas {
+            Some(atlas) if atlas.dirty => atlas,
+            _ => return Ok(()),
+        };
+        atlas.snapshot().ok_or(TextureError::ImageCreation)?;
+        let ids = atlas.regions.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let (image, options) = match self.paints.get(&id) {
+                Some(handle) => (handle.image.clone(), handle.options),
+                None => continue,
+            };
+            let paint = self.make_paint(id, &image, options)?;
+            self.paints.get_mut(&id).unwrap().paint = paint;
+        }
+        Ok(())
+    }
+
+    /// Splits `image` into tiles if it is the font texture and exceeds
+    /// [`Self::with_max_atlas_size`], returns no tiles otherwise.
+    fn make_tiles(
+        &mut self,
+        id: TextureId,
+        image: &Image,
+        options: TextureOptions,
+    ) -> Result<Vec<TextureTile>, TextureError> {
+        let max = match self.max_atlas_size {
+            Some(_) if id != TextureId::default() => return Ok(Vec::new()),
+            Some(max) if image.width() as u32 > max || image.height() as u32 > max => max as i32,
+            _ => return Ok(Vec::new()),
+        };
+        let mut tiles = Vec::new();
+        for rect in tile_rects(image.width(), image.height(), max) {
+            let mut surface = Surface::new_raster_n32_premul(rect.size())
+                .ok_or(TextureError::ImageCreation)?;
+            let mut copy = Paint::default();
+            copy.set_blend_mode(BlendMode::Src);
+            let offset = Point::new(-rect.left as f32, -rect.top as f32);
+            surface.canvas().draw_image(image, offset, Some(&copy));
+            let tile_image = surface.image_snapshot();
+            let paint = self.make_tile_paint(id, &tile_image, rect, image, options)?;
+            tiles.push(TextureTile {
+                rect,
+                image: tile_image,
+                paint,
+            });
+        }
+        Ok(tiles)
+    }
+
+    /// The paint sampling `tile_image`, the `rect` of `image`, with the uv coordinates of the
+    /// whole image.
+    fn make_tile_paint(
+        &mut self,
+        id: TextureId,
+        tile_image: &Image,
+        rect: IRect,
+        image: &Image,
+        options: TextureOptions,
+    ) -> Result<Paint, TextureError> {
+        let region = IRect::from_xywh(-rect.left, -rect.top, image.width(), image.height());
+        self.make_region_paint(id, tile_image, region, false, options)
+    }
+
+    /// Like [`Self::update_texture_region`], without refreshing the atlas.
+    fn insert_region(
+        &mut self,
+        id: TextureId,
+        pos: [usize; 2],
+        image: &ImageData,
+    ) -> Result<(), TextureError> {
+        self.promote([id])?;
+        let handle = self
+            .paints
+            .get(&id)
+            .ok_or(TextureError::UnknownTexture(id))?;
+        let delta_image = image_from_data(image)?;
+        let old_image = &handle.image;
+
+        // When egui grows the font atlas with a region past the old image, the texture grows
+        // with it and the rest of the new area starts out transparent.
+        let mut surface = Surface::new_raster_n32_premul(skia_safe::ISize::new(
+            old_image.width().max(pos[0] as i32 + delta_image.width()),
+            old_image.height().max(pos[1] as i32 + delta_image.height()),
+        ))
+        .ok_or(TextureError::ImageCreation)?;
+
+        let canvas = surface.canvas();
+        canvas.clear(Color::TRANSPARENT);
+
+        // Src copies the premultiplied pixels as they are, including translucent ones, instead
+        // of blending the region over the old content.
+        let mut copy = Paint::default();
+        copy.set_blend_mode(BlendMode::Src);
+        canvas.draw_image(old_image, Point::new(0.0, 0.0), Some(&copy));
+        canvas.draw_image(&delta_image, Point::new(pos[0] as f32, pos[1] as f32), Some(&copy));
+
+        let options = handle.options;
+        self.insert_image(id, surface.image_snapshot(), options)
+    }
+
+    /// Creates a GPU texture for texture `id` on `context`, which is used for drawing from then
+    /// on. The raster image is kept around for partial updates.
+    ///
+    /// The upload is only recorded, it is submitted with the next flush of `context`.
+    #[cfg(feature = "gpu")]
+    pub fn upload_to_gpu(
+        &mut self,
+        id: TextureId,
+        context: &mut skia_safe::gpu::DirectContext,
+    ) -> Result<(), TextureError> {
+        self.promote([id])?;
+        let handle = self
+            .paints
+            .get(&id)
+            .ok_or(TextureError::UnknownTexture(id))?;
+        // Packed textures stay in the raster atlas, which skia uploads once per snapshot.
+        if self.atlas.as_ref().map_or(false, |atlas| atlas.regions.contains_key(&id)) {
+            return Ok(());
+        }
+        let options = handle.options;
+        if !handle.tiles.is_empty() {
+            let image = handle.image.clone();
+            let tiles = handle
+                .tiles
+                .iter()
+                .map(|tile| (tile.rect, tile.image.clone()))
+                .collect::<Vec<_>>();
+            for (i, (rect, tile_image)) in tiles.into_iter().enumerate() {
+                let texture = context
+                    .upload_image(&tile_image)
+                    .ok_or(TextureError::ImageCreation)?;
+                let paint = self.make_tile_paint(id, &texture, rect, &image, options)?;
+                self.paints.get_mut(&id).unwrap().tiles[i].paint = paint;
+            }
+            self.paints.get_mut(&id).unwrap().on_gpu = true;
+            return Ok(());
+        }
+        let texture = context
+            .upload_image(&handle.image)
+            .ok_or(TextureError::ImageCreation)?;
+        let paint = self.make_paint(id, &texture, options)?;
+        let handle = self.paints.get_mut(&id).unwrap();
+        handle.paint = paint;
+        handle.on_gpu = true;
+        Ok(())
+    }
+
+    /// Uses the GPU image `texture` as texture `id`, without a raster image, e.g. for video
+    /// frames converted from YUV by the GPU. Such textures can't be updated partially and are
+    /// dropped by [`Self::release_gpu_resources`].
+    #[cfg(feature = "gpu")]
+    pub(crate) fn insert_gpu_image(
+        &mut self,
+        id: TextureId,
+        texture: Image,
+        options: TextureOptions,
+    ) -> Result<(), TextureError> {
+        self.demoted.remove(&id);
+        if self.idle_frames.is_some() {
+            self.last_used.insert(id, self.generation);
+        }
+        if let Some(atlas) = &mut self.atlas {
+            atlas.regions.remove(&id);
+        }
+        let paint = self.make_paint(id, &texture, options)?;
+        self.paints.insert(
+            id,
+            PaintHandle {
+                paint,
+                image: texture,
+                options,
+                on_gpu: true,
+                tiles: Vec::new(),
+                source_hash: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Drops all GPU textures, drawing from the raster images again from then on. Textures
+    /// without a raster image, like video frames converted by the GPU, are dropped entirely.
+    ///
+    /// Call this before the GPU context the textures were uploaded to is destroyed.
+    pub fn release_gpu_resources(&mut self) {
+        self.retired.retain(|(_, _, handle)| !handle.on_gpu);
+        let ids = self
+            .paints
+            .iter()
+            .filter(|(_, handle)| handle.on_gpu)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let handle = &self.paints[&id];
+            // Textures without a raster image can't be drawn without the GPU.
+            if handle.image.is_texture_backed() {
+                self.paints.remove(&id);
+                continue;
+            }
+            let (image, options) = (handle.image.clone(), handle.options);
+            let paints = self.make_tiles(id, &image, options).and_then(|tiles| {
+                let paint = if tiles.is_empty() {
+                    self.make_paint(id, &image, options)?
+                } else {
+                    Paint::default()
+                };
+                Ok((paint, tiles))
+            });
+            match paints {
+                Ok((paint, tiles)) => {
+                    let handle = self.paints.get_mut(&id).unwrap();
+                    handle.paint = paint;
+                    handle.tiles = tiles;
+                    handle.on_gpu = false;
+                }
+                Err(_) => {
+                    self.paints.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Returns the options texture `id` was uploaded with.
+    pub fn texture_options(&self, id: TextureId) -> Option<TextureOptions> {
+        let demoted = || self.demoted.get(&id).map(|demoted| demoted.options);
+        self.get(&id).map(|handle| handle.options).or_else(demoted)
+    }
+
+    fn make_paint(
+        &mut self,
+        id: TextureId,
+        image: &Image,
+        options: TextureOptions,
+    ) -> Result<Paint, TextureError> {
+        // Packed textures sample their region of the atlas instead of their own image.
+        let atlas_entry = self
+            .atlas
+            .as_ref()
+            .and_then(|atlas| Some((atlas.image.clone(), *atlas.regions.get(&id)?)));
+        match atlas_entry {
+            Some((atlas_image, region)) => {
+                self.make_region_paint(id, &atlas_image, region, true, options)
+            }
+            None => {
+                let region = IRect::from_wh(image.width(), image.height());
+                self.make_region_paint(id, image, region, false, options)
+            }
+        }
+    }
+
+    /// The paint sampling `region` of `image` with uv coordinates from 0 to 1. Textures `packed`
+    /// into an atlas are sampled without mipmaps.
+    fn make_region_paint(
+        &mut self,
+        id: TextureId,
+        image: &Image,
+        region: IRect,
+        packed: bool,
+        options: TextureOptions,
+    ) -> Result<Paint, TextureError> {
+        let opaque = self.is_opaque(id, image);
+        let mut local_matrix =
+            skia_safe::Matrix::scale((1.0 / region.width() as f32, 1.0 / region.height() as f32));
+        local_matrix.pre_translate((-region.left as f32, -region.top as f32));
+
+        let sampling_options = {
+            let filter_mode = match options.magnification {
+                TextureFilter::Nearest => skia_safe::FilterMode::Nearest,
+                TextureFilter::Linear => skia_safe::FilterMode::Linear,
+            };
+            // Smaller mipmap levels of the atlas would mix neighboring textures.
+            let mm_mode = if cfg!(feature = "cpu_fix") || packed {
+                skia_safe::MipmapMode::None
+            } else {
+                match options.minification {
+                    TextureFilter::Nearest => skia_safe::MipmapMode::Nearest,
+                    TextureFilter::Linear => skia_safe::MipmapMode::Linear,
+                }
+            };
+            match self.quality {
+                QualityTier::Low => skia_safe::SamplingOptions::new(
+                    skia_safe::FilterMode::Nearest,
+                    skia_safe::MipmapMode::None,
+                ),
+                QualityTier::High if options.magnification == TextureFilter::Linear => {
+                    skia_safe::CubicResampler::mitchell().into()
+                }
+                _ => skia_safe::SamplingOptions::new(filter_mode, mm_mode),
+            }
+        };
+        let tile_mode = TILE_MODE;
+
+        let mut paint = Paint::default();
+
+        let mut shader = image
+            .to_shader((tile_mode, tile_mode), sampling_options, &local_matrix)
+            .ok_or_else(|| TextureError::ShaderCreation("failed to create image shader".into()))?;
+
+        let effect = match self.texture_effects.get(&id) {
+            Some(effect) => effect,
+            // Opaque textures skip the effect, which would only pass them through.
+            None if self.raw_image_shaders || opaque => {
+                paint.set_shader(shader);
+                return Ok(paint);
+            }
+            None => {
+                // Compiling the effect is expensive, so it is compiled once and reused for every
+                // texture.
+                if self.effect.is_none() {
+                    self.effect = Some(texture_effect(SKSL_SHADER)?);
+                }
+                self.effect.as_ref().unwrap()
+            }
+        };
+        shader = effect
+            .make_shader(Data::new_empty(), &[ChildPtr::Shader(shader)], None)
+            .ok_or_else(|| {
+                TextureError::ShaderCreation("failed to create runtime shader".into())
+            })?;
+
+        paint.set_shader(shader);
+
+        Ok(paint)
+    }
+
+    pub(crate) fn get(&self, id: &TextureId) -> Option<&PaintHandle> {
+        self.paints.get(id).or_else(|| {
+            self.retired
+                .iter()
+                .rev()
+                .find(|(_, retired_id, _)| retired_id == id)
+                .map(|(_, _, handle)| handle)
+        })
+    }
+
+    /// An empty store sampling its textures like this one, for textures that must not enter this
+    /// store, see [`Painter::warmup`](crate::Painter::warmup).
+    #[cfg(feature = "gpu")]
+    pub(crate) fn empty_like(&self) -> TextureStore {
+        TextureStore {
+            effect: self.effect.clone(),
+            raw_image_shaders: self.raw_image_shaders,
+            quality: self.quality,
+            ..Default::default()
+        }
+    }
+}
+
+/// The rects of the tiles of at most `max` pixels per side a texture of `width` by `height`
+/// pixels is split into, overlapping by a quarter of `max`, see
+/// [`TextureStore::with_max_atlas_size`].
+fn tile_rects(width: i32, height: i32, max: i32) -> Vec<IRect> {
+    let starts = |length: i32| {
+        let stride = max - max / 4;
+        let mut starts = vec![0];
+        while starts.last().unwrap() + max < length {
+            starts.push(starts.last().unwrap() + stride);
+        }
+        starts
+    };
+    let mut rects = Vec::new();
+    for top in starts(height) {
+        for left in starts(width) {
+            let (right, bottom) = ((left + max).min(width), (top + max).min(height));
+            rects.push(IRect::new(left, top, right, bottom));
+        }
+    }
+    rects
+}
+
+/// The shader raising the coverage of the font texture, premultiplied white, to the power of
+/// `1 / gamma`, see [`TextureStore::set_font_gamma`].
+fn font_gamma_sksl(gamma: f32) -> String {
+    format!(
+        r#"
+uniform shader image;
+vec4 main(float2 coord) {{
+    vec4 color = image.eval(coord);
+    return color * (pow(color.a, {:.6}) / max(color.a, 0.0001));
+}}
+"#,
+        1.0 / gamma.max(0.01)
+    )
+}
+
+/// Compiles `sksl` and checks that it can sample a texture: one shader child and no uniforms.
+fn texture_effect(sksl: &str) -> Result<RuntimeEffect, TextureError> {
+    let effect = RuntimeEffect::make_for_shader(sksl, None).map_err(TextureError::ShaderCreation)?;
+    let children = effect.children();
+    if children.len() != 1 || children[0].ty() != ChildType::Shader {
+        let found = children
+            .iter()
+            .map(|child| format!("{} ({:?})", child.name(), child.ty()))
+            .collect::<Vec<_>>();
+        return Err(TextureError::ShaderCreation(format!(
+            "the texture shader must declare exactly one `uniform shader` child, found [{}]",
+            found.join(", ")
+        )));
+    }
+    if effect.uniform_size() != 0 {
+        return Err(TextureError::ShaderCreation(
+            "the texture shader must not declare uniforms other than its shader child".into(),
+        ));
+    }
+    Ok(effect)
+}
+
+/// A hash of the size and pixels of `image`, to recognize a texture uploaded again unchanged.
+fn image_data_hash(image: &ImageData) -> u64 {
+    let mut hasher = AHasher::default();
+    match image {
+        ImageData::Color(color_image) => {
+            color_image.size.hash(&mut hasher);
+            color_image.pixels.hash(&mut hasher);
+        }
+        ImageData::Font(font) => {
+            font.size.hash(&mut hasher);
+            for coverage in &font.pixels {
+                coverage.to_bits().hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+fn image_from_data(image: &ImageData) -> Result<Image, TextureError> {
+    let (size, pixels, alpha_type) = match image {
+        ImageData::Color(color_image) => (
+            color_image.size,
+            color_image
+                .pixels
+                .iter()
+                .flat_map(|p| color32_bytes_premul(*p))
+                .collect::<Vec<_>>(),
+            // Tagging opaque images lets skia skip blending them, see
+            // [`TextureStore::set_texture_opaque`].
+            if color_image.pixels.iter().all(|p| p.a() == 255) {
+                AlphaType::Opaque
+            } else {
+                AlphaType::Premul
+            },
+        ),
+        ImageData::Font(font) => (
+            font.size,
+            font.pixels
+                .iter()
+                .flat_map(|coverage| font_coverage_bytes_premul(*coverage, FONT_GAMMA))
+                .collect::<Vec<_>>(),
+            AlphaType::Premul,
+        ),
+    };
+
+    // The bytes are in RGBA order, N32 is BGRA on most platforms.
+    Image::from_raster_data(
+        &ImageInfo::new(
+            skia_safe::ISize::new(size[0] as i32, size[1] as i32),
+            ColorType::RGBA8888,
+            alpha_type,
+            None,
+        ),
+        Data::new_copy(pixels.as_slice()),
+        size[0] * 4,
+    )
+    .ok_or(TextureError::ImageCreation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use egui::epaint::ImageDelta;
+    use egui::{pos2, Color32, ColorImage};
+
+    use super::*;
+    use crate::test_util::{
+        assert_color_near, mesh_primitive, pixel, rect_primitive, surface, surface_bytes,
+        upload_white,
+    };
+    use crate::Painter;
+
+    /// The unpremultiplied RGBA pixels of texture `id` of `store`.
+    fn texture_pixels(store: &TextureStore, id: TextureId) -> Vec<u8> {
+        let image = &store.get(&id).unwrap().image;
+        let (color_type, alpha_type) = (ColorType::RGBA8888, AlphaType::Unpremul);
+        let info = ImageInfo::new(image.dimensions(), color_type, alpha_type, None);
+        let mut pixels = vec![0; image.width() as usize * image.height() as usize * 4];
+        let row_bytes = image.width() as usize * 4;
+        assert!(image.read_pixels(
+            &info,
+            &mut pixels,
+            row_bytes,
+            (0, 0),
+            skia_safe::image::CachingHint::Allow
+        ));
+        pixels
+    }
+
+    fn solid(size: [usize; 2], color: Color32) -> ColorImage {
+        ColorImage::new(size, color)
+    }
+
+    #[test]
+    fn painters_sharing_a_store_paint_the_same_with_one_copy_of_each_texture() {
+        let store = Arc::new(RwLock::new(TextureStore::new()));
+        let mut first = Painter::with_texture_store(store.clone());
+        let second = Painter::with_texture_store(store.clone());
+        upload_white(&mut first, TextureId::default());
+
+        let rect = egui::Rect::from_min_max(pos2(2.0, 2.0), pos2(8.0, 8.0));
+        let primitives = [rect_primitive(rect, Color32::RED)];
+        let mut surfaces = [surface(10, 10), surface(10, 10)];
+        first.paint_primitives(surfaces[0].canvas(), 1.0, &primitives);
+        second.paint_primitives(surfaces[1].canvas(), 1.0, &primitives);
+
+        let [a, b] = surfaces.map(|mut surface| {
+            let pixels = surface.peek_pixels().unwrap();
+            pixels.bytes().unwrap().to_vec()
+        });
+        assert_eq!(a, b);
+        assert!(a.iter().any(|&byte| byte != 0));
+        assert_eq!(store.read().unwrap().stats().textures, 1);
+    }
+
+    #[test]
+    fn set_textures_applies_updates_in_order_per_texture_whatever_the_interleaving() {
+        let (a, b) = (TextureId::User(1), TextureId::User(2));
+        let options = TextureOptions::NEAREST;
+        let base = |id, color| (id, ImageDelta::full(solid([2, 1], color), options));
+        let patch = |id, color| (id, ImageDelta::partial([1, 0], solid([1, 1], color), options));
+
+        let orders = [
+            vec![
+                base(a, Color32::RED),
+                patch(a, Color32::GREEN),
+                base(b, Color32::BLUE),
+                patch(b, Color32::WHITE),
+            ],
+            vec![
+                base(b, Color32::BLUE),
+                base(a, Color32::RED),
+                patch(b, Color32::WHITE),
+                patch(a, Color32::GREEN),
+            ],
+        ];
+        let stores = orders.map(|set| {
+            let mut store = TextureStore::new().with_texture_atlas(true);
+            let delta = TexturesDelta {
+                set,
+                free: Vec::new(),
+            };
+            store.set_textures(&delta).unwrap();
+            store
+        });
+
+        for store in &stores {
+            assert_eq!(texture_pixels(store, a), [255, 0, 0, 255, 0, 255, 0, 255]);
+            assert_eq!(texture_pixels(store, b), [0, 0, 255, 255, 255, 255, 255, 255]);
+        }
+        let regions = |store: &TextureStore| {
+            let atlas = store.atlas.as_ref().unwrap();
+            [atlas.regions.get(&a).copied(), atlas.regions.get(&b).copied()]
+        };
+        assert_eq!(regions(&stores[0]), regions(&stores[1]));
+    }
+
+    #[test]
+    fn textures_set_and_freed_in_one_delta_outlive_their_frame() {
+        let id = TextureId::User(1);
+        let delta = TexturesDelta {
+            set: vec![(id, ImageDelta::full(solid([1, 1], Color32::RED), TextureOptions::NEAREST))],
+            free: vec![id],
+        };
+        let mut store = TextureStore::new();
+        store.set_textures(&delta).unwrap();
+        store.free_textures(&delta.free);
+
+        // The frame of the delta still draws the texture, it is dropped at the end of the frame.
+        assert!(store.get(&id).is_some());
+        store.end_frame();
+        assert!(store.get(&id).is_none());
+    }
+
+    #[test]
+    fn texture_shaders_with_the_wrong_children_are_rejected() {
+        let error = |sksl: &str| match TextureStore::default().with_texture_shader(sksl) {
+            Ok(_) => panic!("accepted {}", sksl),
+            Err(TextureError::ShaderCreation(message)) => message,
+            Err(error) => panic!("unexpected error {}", error),
+        };
+
+        let none = error("half4 main(float2 p) { return half4(1); }");
+        assert!(none.contains("exactly one `uniform shader` child, found []"), "{}", none);
+        let two = error(
+            "uniform shader image; uniform shader mask;
+            half4 main(float2 p) { return image.eval(p) * mask.eval(p).a; }",
+        );
+        assert!(two.contains("found [image (Shader), mask (Shader)]"), "{}", two);
+        let filter = error("uniform colorFilter image; half4 main(float2 p) { return half4(1); }");
+        assert!(filter.contains("found [image (ColorFilter)]"), "{}", filter);
+
+        let valid = "uniform shader image; half4 main(float2 p) { return image.eval(p); }";
+        assert!(TextureStore::default().with_texture_shader(valid).is_ok());
+    }
+
+    #[test]
+    fn raw_image_shaders_paint_like_the_runtime_effect() {
+        let texels: Vec<u8> = (0..16u8)
+            .flat_map(|i| [i * 16, 255 - i * 16, 128, 64 + i * 12])
+            .collect();
+        let image = ImageData::Color(ColorImage::from_rgba_unmultiplied([4, 4], &texels));
+        let paint = |raw: bool| {
+            let store = TextureStore::new().with_raw_image_shaders(raw);
+            let mut painter = Painter::with_texture_store(Arc::new(RwLock::new(store)));
+            let mut primitives = Vec::new();
+            for (i, options) in [TextureOptions::NEAREST, TextureOptions::LINEAR]
+                .into_iter()
+                .enumerate()
+            {
+                let id = TextureId::User(i as u64);
+                painter.upload_texture(id, &image, options).unwrap();
+                let mut mesh = egui::epaint::Mesh::with_texture(id);
+                let x = i as f32 * 20.0;
+                let rect = egui::Rect::from_min_max(pos2(x, 0.0), pos2(x + 18.0, 18.0));
+                let uv = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+                let tint = Color32::from_rgba_premultiplied(200, 180, 160, 220);
+                mesh.add_rect_with_uv(rect, uv, tint);
+                primitives.push(mesh_primitive(egui::Rect::EVERYTHING, mesh));
+            }
+            let mut surface = surface(80, 40);
+            painter.paint_primitives(surface.canvas(), 2.0, &primitives);
+            surface_bytes(&mut surface)
+        };
+
+        let effect = paint(false);
+        assert!(effect.iter().any(|&byte| byte != 0));
+        assert!(effect == paint(true));
+    }
+
+    /// A font texture of `size` texels with the coverage `coverage(x, y)`.
+    fn font(size: [usize; 2], coverage: impl Fn(usize, usize) -> f32) -> ImageData {
+        let mut image = egui::FontImage::new(size);
+        for y in 0..size[1] {
+            for x in 0..size[0] {
+                image.pixels[y * size[0] + x] = coverage(x, y);
+            }
+        }
+        ImageData::Font(image)
+    }
+
+    #[test]
+    fn grown_font_atlases_keep_the_old_glyphs_and_start_transparent() {
+        // The old atlas ends with a half covered row, the grown one adds a glyph row below it.
+        let old = |_: usize, y: usize| if y == 3 { 0.5 } else { 0.0 };
+        let grown = |x: usize, y: usize| match y {
+            0..=3 => old(x, y),
+            4 => 1.0,
+            _ => 0.0,
+        };
+        let id = TextureId::default();
+        let options = TextureOptions::NEAREST;
+        let mut painter = Painter::new();
+        let upload = |painter: &mut Painter, delta| {
+            painter.update_textures(&TexturesDelta {
+                set: vec![(id, delta)],
+                free: Vec::new(),
+            });
+        };
+        upload(&mut painter, ImageDelta::full(font([4, 4], old), options));
+        let glyph = font([2, 1], |_, _| 0.5);
+        upload(&mut painter, ImageDelta::partial([0, 0], glyph, options));
+        upload(&mut painter, ImageDelta::full(font([4, 8], grown), options));
+        // A glyph added to the new region afterwards.
+        let glyph = font([2, 1], |_, _| 1.0);
+        upload(&mut painter, ImageDelta::partial([2, 6], glyph, options));
+
+        // The atlas drawn texel by texel.
+        let mut mesh = egui::epaint::Mesh::with_texture(id);
+        let uv = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        let rect = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(4.0, 8.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        let mut surface = surface(4, 8);
+        painter.paint_primitives(surface.canvas(), 1.0, &[mesh_primitive(rect, mesh)]);
+
+        let [.., half] = font_coverage_bytes_premul(0.5, FONT_GAMMA);
+        let half = Color::from_argb(half, 255, 255, 255);
+        for x in 0..4 {
+            // The full upload replaced the partial one of the old atlas.
+            assert_eq!(pixel(&mut surface, x, 0), Color::TRANSPARENT);
+            assert_color_near(pixel(&mut surface, x, 3), half, 0);
+            assert_color_near(pixel(&mut surface, x, 4), Color::WHITE, 0);
+            assert_eq!(pixel(&mut surface, x, 5), Color::TRANSPARENT);
+            assert_eq!(pixel(&mut surface, x, 7), Color::TRANSPARENT);
+        }
+        assert_eq!(pixel(&mut surface, 1, 6), Color::TRANSPARENT);
+        assert_color_near(pixel(&mut surface, 2, 6), Color::WHITE, 0);
+    }
+
+    #[test]
+    fn small_textures_are_packed_into_one_atlas_and_drawn_from_it() {
+        let mut painter = Painter::new().with_texture_atlas(true);
+        let colors = [Color32::RED, Color32::GREEN, Color32::BLUE, Color32::YELLOW];
+        let mut primitives = Vec::new();
+        for (i, color) in colors.into_iter().enumerate() {
+            let id = TextureId::User(i as u64);
+            let image = ImageData::Color(solid([8, 8], color));
+            painter
+                .upload_texture(id, &image, TextureOptions::NEAREST)
+                .unwrap();
+            let mut mesh = egui::Mesh::with_texture(id);
+            let rect = egui::Rect::from_min_size(pos2(i as f32 * 8.0, 0.0), egui::vec2(8.0, 8.0));
+            let uv = egui::Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+            primitives.push(mesh_primitive(egui::Rect::EVERYTHING, mesh));
+        }
+        {
+            let store = painter.texture_store().read().unwrap();
+            let atlas = store.atlas.as_ref().unwrap();
+            assert_eq!(atlas.regions.len(), colors.len());
+            // The uploads are snapshotted together by the next frame.
+            assert!(store.atlas_dirty());
+        }
+
+        let mut surface = surface(32, 8);
+        painter.paint_primitives(surface.canvas(), 1.0, &primitives);
+
+        let store = painter.texture_store().read().unwrap();
+        assert!(!store.atlas_dirty());
+        let atlas = store.atlas.as_ref().unwrap();
+        let atlas_pixels = atlas.image.peek_pixels().unwrap();
+        for (i, color) in colors.into_iter().enumerate() {
+            let region = atlas.regions[&TextureId::User(i as u64)];
+            let color = Color::from_argb(color.a(), color.r(), color.g(), color.b());
+            let center = (region.left + 4, region.top + 4);
+            assert_color_near(atlas_pixels.get_color(center), color, 0);
+            assert_color_near(pixel(&mut surface, i as i32 * 8 + 4, 4), color, 0);
+        }
+    }
+
+    #[test]
+    fn set_textures_applies_the_other_updates_when_one_fails() {
+        let mut store = TextureStore::new().with_texture_atlas(true);
+        let (unknown, known) = (TextureId::User(1), TextureId::User(2));
+        let options = TextureOptions::NEAREST;
+        let patch = ImageDelta::partial([0, 0], solid([1, 1], Color32::RED), options);
+        let full = ImageDelta::full(solid([2, 2], Color32::BLUE), options);
+        let delta = TexturesDelta {
+            set: vec![(unknown, patch), (known, full)],
+            free: Vec::new(),
+        };
+        let err = store.set_textures(&delta).unwrap_err();
+        let unknown_error = matches!(err, TextureError::UnknownTexture(id) if id == unknown);
+        assert!(unknown_error, "{}", err);
+        assert_eq!(texture_pixels(&store, known)[..4], [0, 0, 255, 255]);
+        // The atlas was snapshotted for the texture that made it in.
+        assert!(!store.atlas_dirty());
+    }
+
+    #[test]
+    fn freed_texture_regions_turn_transparent_and_the_rest_stays() {
+        let mut store = TextureStore::new();
+        let id = TextureId::User(1);
+        let image = ImageData::Color(solid([4, 4], Color32::RED));
+        store
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        store
+            .free_texture_region(id, IRect::from_xywh(1, 1, 2, 2))
+            .unwrap();
+
+        let pixels = texture_pixels(&store, id);
+        for y in 0..4 {
+            for x in 0..4 {
+                let freed = (1..3).contains(&x) && (1..3).contains(&y);
+                let (red, transparent) = ([255, 0, 0, 255], [0, 0, 0, 0]);
+                let expected = if freed { transparent } else { red };
+                let at = (y * 4 + x) * 4;
+                assert_eq!(pixels[at..at + 4], expected, "pixel {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn demoted_translucent_textures_come_back_exactly() {
+        let mut store = TextureStore::new().with_low_memory(1);
+        let id = TextureId::User(1);
+        let rgba = [
+            255, 0, 0, 1, 0, 255, 0, 77, 0, 0, 255, 128, 13, 37, 201, 200, 255, 255, 255, 0,
+            90, 180, 45, 255,
+        ];
+        let image = ImageData::Color(ColorImage::from_rgba_unmultiplied([3, 2], &rgba));
+        store
+            .upload_texture(id, &image, TextureOptions::NEAREST)
+            .unwrap();
+        let premultiplied = |store: &TextureStore| {
+            let pixels = RawPixels::read(&store.get(&id).unwrap().image).unwrap();
+            pixels.bytes.to_vec()
+        };
+        let (before, before_pixels) = (premultiplied(&store), texture_pixels(&store, id));
+
+        for _ in 0..3 {
+            store.end_frame();
+        }
+        assert!(store.is_demoted(id));
+        let stats = store.stats();
+        assert_eq!((stats.textures, stats.demoted), (0, 1));
+        assert!(stats.demoted_bytes > 0);
+
+        // The raw pixels come back exactly as well, but the compressed ones are the point.
+        let compressing = |store: &TextureStore| match &store.demoted[&id].pixels {
+            DemotedPixels::Raw { compressed, .. } => compressed.is_some(),
+            _ => false,
+        };
+        while compressing(&store) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            store.end_frame();
+        }
+        let compressed = &store.demoted[&id].pixels;
+        assert!(matches!(compressed, DemotedPixels::Compressed { .. }));
+        store.mark_used([id]).unwrap();
+        assert!(!store.is_demoted(id));
+        assert_eq!(premultiplied(&store), before);
+        assert_eq!(texture_pixels(&store, id), before_pixels);
+    }
+
+    #[test]
+    fn many_demoted_textures_are_compressed_one_after_another() {
+        let mut store = TextureStore::new().with_low_memory(1);
+        let ids = (1..=20).map(TextureId::User).collect::<Vec<_>>();
+        for (i, id) in ids.iter().enumerate() {
+            let color = Color32::from_rgba_unmultiplied(i as u8 * 10, 0, 0, 128);
+            let image = ImageData::Color(solid([4, 4], color));
+            store
+                .upload_texture(*id, &image, TextureOptions::NEAREST)
+                .unwrap();
+        }
+        let pixels = |store: &TextureStore| {
+            let ids = ids.iter();
+            ids.map(|id| texture_pixels(store, *id)).collect::<Vec<_>>()
+        };
+        let before = pixels(&store);
+
+        for _ in 0..3 {
+            store.end_frame();
+        }
+        assert_eq!(store.stats().demoted, ids.len());
+        let compressed = |store: &TextureStore| {
+            let pixels = store.demoted.values().map(|texture| &texture.pixels);
+            let is_compressed =
+                |pixels: &&DemotedPixels| matches!(pixels, DemotedPixels::Compressed { .. });
+            pixels.filter(is_compressed).count()
+        };
+        let start = std::time::Instant::now();
+        while compressed(&store) < ids.len() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            store.end_frame();
+        }
+
+        store.mark_used(ids.iter().copied()).unwrap();
+        assert_eq!(pixels(&store), before);
+    }
+
+    #[test]
+    fn async_font_atlases_spare_the_first_frame_and_arrive_within_a_few_frames() {
+        use std::time::{Duration, Instant};
+
+        let id = TextureId::default();
+        let options = TextureOptions::NEAREST;
+        let atlas = font([2048, 2048], |x, y| if x < 2 && y == 0 { 1.0 } else { 0.0 });
+        let delta = |image_delta| TexturesDelta {
+            set: vec![(id, image_delta)],
+            free: Vec::new(),
+        };
+        let full = delta(ImageDelta::full(atlas, options));
+        let start = Instant::now();
+        TextureStore::new().set_textures(&full).unwrap();
+        let sync = start.elapsed();
+
+        let mut store = TextureStore::new().with_async_atlas_upload(true);
+        let start = Instant::now();
+        store.set_textures(&full).unwrap();
+        let first = start.elapsed();
+        assert!(first < sync, "{:?} {:?}", first, sync);
+        assert!(store.has_pending_uploads());
+        assert!(store.get(&id).is_none());
+
+        // A glyph egui adds in the second frame, during the conversion or after it.
+        let glyph = ImageDelta::partial([4, 0], font([2, 1], |_, _| 1.0), options);
+        let mut glyph = Some(delta(glyph));
+        for _ in 0..100 {
+            if !store.has_pending_uploads() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(16));
+            let frame = glyph.take().unwrap_or_default();
+            store.set_textures(&frame).unwrap();
+        }
+        assert!(!store.has_pending_uploads());
+
+        let pixels = texture_pixels(&store, id);
+        let alpha = |x: usize| pixels[x * 4 + 3];
+        assert_eq!([alpha(0), alpha(1), alpha(4), alpha(5)], [255; 4]);
+        assert_eq!([alpha(2), alpha(3), alpha(6)], [0; 3]);
+    }
+
+    #[test]
+    fn opaque_textures_take_the_fast_path_and_translucent_ones_the_effect() {
+        let mut store = TextureStore::new();
+        let (opaque, translucent) = (TextureId::User(1), TextureId::User(2));
+        let upload = |store: &mut TextureStore, id, color| {
+            let image = ImageData::Color(solid([2, 2], color));
+            store.upload_texture(id, &image, TextureOptions::NEAREST)
+        };
+        upload(&mut store, opaque, Color32::RED).unwrap();
+        let half_red = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        upload(&mut store, translucent, half_red).unwrap();
+        // The fast path samples the image itself, the full path goes through the runtime effect.
+        let fast = |store: &TextureStore, id| {
+            let shader = store.get(&id).unwrap().paint.shader().unwrap();
+            shader.is_a_image()
+        };
+
+        assert!(fast(&store, opaque));
+        assert!(!fast(&store, translucent));
+        assert_eq!(store.stats().opaque, 1);
+        // The host knows better, e.g. for an image it keeps translucent pixels out of.
+        store.set_texture_opaque(opaque, false).unwrap();
+        assert!(!fast(&store, opaque));
+        assert_eq!(store.stats().opaque, 0);
+        assert_eq!(texture_pixels(&store, opaque)[..4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn unchanged_full_uploads_hit_the_cache() {
+        let id = TextureId::User(1);
+        let mut painter = Painter::new();
+        let mut upload = |size, color, options| {
+            let image = ImageData::Color(solid(size, color));
+            painter.update_textures(&TexturesDelta {
+                set: vec![(id, ImageDelta::full(image, options))],
+                free: Vec::new(),
+            });
+            let stats = painter.texture_cache_stats();
+            (stats.hits, stats.misses)
+        };
+        let nearest = TextureOptions::NEAREST;
+
+        // The first upload of a texture is neither, the first repeat already hits.
+        assert_eq!(upload([4, 4], Color32::RED, nearest), (0, 0));
+        assert_eq!(upload([4, 4], Color32::RED, nearest), (1, 0));
+        assert_eq!(upload([4, 4], Color32::RED, nearest), (2, 0));
+        // Other pixels, options or sizes are uploaded again.
+        assert_eq!(upload([4, 4], Color32::BLUE, nearest), (2, 1));
+        let linear = TextureOptions::LINEAR;
+        assert_eq!(upload([4, 4], Color32::BLUE, linear), (2, 2));
+        assert_eq!(upload([8, 4], Color32::BLUE, nearest), (2, 3));
+        assert_eq!(upload([8, 4], Color32::BLUE, nearest), (3, 3));
+    }
+
+    #[test]
+    fn uploads_changing_only_the_options_keep_the_image() {
+        let mut store = TextureStore::new();
+        let id = TextureId::User(1);
+        let image = ImageData::Color(solid([4, 4], Color32::RED));
+        let upload = |store: &mut TextureStore, options| {
+            let delta = TexturesDelta {
+                set: vec![(id, ImageDelta::full(image.clone(), options))],
+                free: Vec::new(),
+            };
+            store.set_textures(&delta).unwrap();
+            store.get(&id).unwrap().image.unique_id()
+        };
+        let first = upload(&mut store, TextureOptions::NEAREST);
+
+        assert_eq!(upload(&mut store, TextureOptions::LINEAR), first);
+        assert_eq!(store.texture_options(id), Some(TextureOptions::LINEAR));
+        assert_eq!(texture_pixels(&store, id)[..4], [255, 0, 0, 255]);
+        assert_eq!(store.cache_stats().misses, 1);
+        // Other pixels still create a new image.
+        let image = ImageData::Color(solid([4, 4], Color32::BLUE));
+        let delta = TexturesDelta {
+            set: vec![(id, ImageDelta::full(image, TextureOptions::LINEAR))],
+            free: Vec::new(),
+        };
+        store.set_textures(&delta).unwrap();
+        assert_ne!(store.get(&id).unwrap().image.unique_id(), first);
+    }
+}
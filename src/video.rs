@@ -0,0 +1,222 @@
+use skia_safe::{AlphaType, ColorType, Data, Image, ImageInfo};
+
+use crate::textures::TextureError;
+
+/// The YUV to RGB conversion of a video frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvColorSpace {
+    /// Full range BT.601, as used by JPEG.
+    Jpeg,
+    /// Limited range BT.601, used by most SD video.
+    Rec601,
+    /// Limited range BT.709, used by most HD video.
+    Rec709,
+}
+
+impl YuvColorSpace {
+    /// The matching color space of skia's YUV conversion.
+    #[cfg(feature = "gpu")]
+    fn skia(self) -> skia_safe::YUVColorSpace {
+        match self {
+            YuvColorSpace::Jpeg => skia_safe::YUVColorSpace::JPEG,
+            YuvColorSpace::Rec601 => skia_safe::YUVColorSpace::Rec601,
+            YuvColorSpace::Rec709 => skia_safe::YUVColorSpace::Rec709,
+        }
+    }
+
+    /// `(y offset, y scale, v→r, u→g, v→g, u→b)`, the factors are fixed point with 10 bits.
+    fn coefficients(self) -> (i32, i32, i32, i32, i32, i32) {
+        match self {
+            YuvColorSpace::Jpeg => (0, 1024, 1436, 352, 731, 1815),
+            YuvColorSpace::Rec601 => (16, 1192, 1634, 401, 833, 2066),
+            YuvColorSpace::Rec709 => (16, 1192, 1836, 218, 546, 2163),
+        }
+    }
+}
+
+/// The planes of an 8 bit I420 (YUV 4:2:0) video frame.
+///
+/// The `u` and `v` planes have half the width and height of the `y` plane, rounded up.
+#[derive(Clone, Copy, Debug)]
+pub struct YuvPlanes<'a> {
+    /// Width and height of the frame in pixels.
+    pub size: [usize; 2],
+    pub y: &'a [u8],
+    pub y_stride: usize,
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+    /// Stride of the `u` and `v` planes.
+    pub uv_stride: usize,
+}
+
+impl YuvPlanes<'_> {
+    fn validate(&self) -> Result<(), TextureError> {
+        let [width, height] = self.size;
+        let (uv_width, uv_height) = ((width + 1) / 2, (height + 1) / 2);
+        let plane_fits = |plane: &[u8], stride: usize, width: usize, height: usize| {
+            height == 0 || (stride >= width && plane.len() >= stride * (height - 1) + width)
+        };
+        if width == 0 || height == 0 {
+            return Err(TextureError::InvalidData("empty video frame".into()));
+        }
+        if !plane_fits(self.y, self.y_stride, width, height) {
+            return Err(TextureError::InvalidData("y plane too small".into()));
+        }
+        if !plane_fits(self.u, self.uv_stride, uv_width, uv_height)
+            || !plane_fits(self.v, self.uv_stride, uv_width, uv_height)
+        {
+            return Err(TextureError::InvalidData("u or v plane too small".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Converts `planes` to an opaque raster image.
+pub(crate) fn yuv_to_image(
+    planes: &YuvPlanes<'_>,
+    color: YuvColorSpace,
+) -> Result<Image, TextureError> {
+    planes.validate()?;
+
+    let [width, height] = planes.size;
+    let (y_offset, y_scale, rv, gu, gv, bu) = color.coefficients();
+    let clamp = |value: i32| (value >> 10).clamp(0, 255) as u8;
+
+    let mut pixels = vec![0u8; width * height * 4];
+    for (row, out) in pixels.chunks_exact_mut(width * 4).enumerate() {
+        let y_row = &planes.y[row * planes.y_stride..];
+        let u_row = &planes.u[row / 2 * planes.uv_stride..];
+        let v_row = &planes.v[row / 2 * planes.uv_stride..];
+        for (column, pixel) in out.chunks_exact_mut(4).enumerate() {
+            let y = (y_row[column] as i32 - y_offset) * y_scale;
+            let u = u_row[column / 2] as i32 - 128;
+            let v = v_row[column / 2] as i32 - 128;
+            pixel[0] = clamp(y + rv * v);
+            pixel[1] = clamp(y - gu * u - gv * v);
+            pixel[2] = clamp(y + bu * u);
+            pixel[3] = 255;
+        }
+    }
+
+    let size = skia_safe::ISize::new(width as i32, height as i32);
+    Image::from_raster_data(
+        &ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Opaque, None),
+        Data::new_copy(&pixels),
+        width * 4,
+    )
+    .ok_or(TextureError::ImageCreation)
+}
+
+/// Uploads `planes` to a GPU image on `context`, which skia converts to RGB on the GPU.
+#[cfg(feature = "gpu")]
+pub(crate) fn yuv_to_texture(
+    planes: &YuvPlanes<'_>,
+    color: YuvColorSpace,
+    context: &mut skia_safe::gpu::DirectContext,
+) -> Result<Image, TextureError> {
+    use skia_safe::yuva_info::{PlaneConfig, Subsampling};
+    use skia_safe::yuva_pixmap_info::DataType;
+    use skia_safe::{YUVAInfo, YUVAPixmapInfo, YUVAPixmaps};
+
+    planes.validate()?;
+
+    let [width, height] = planes.size;
+    let uv_height = (height + 1) / 2;
+    let info = YUVAInfo::new(
+        (width as i32, height as i32),
+        PlaneConfig::Y_U_V,
+        Subsampling::S420,
+        color.skia(),
+        None,
+        None,
+    )
+    .ok_or(TextureError::ImageCreation)?;
+    let row_bytes = [planes.y_stride, planes.uv_stride, planes.uv_stride];
+    let pixmap_info = YUVAPixmapInfo::from_data_type(&info, DataType::Unorm8, Some(&row_bytes))
+        .ok_or(TextureError::ImageCreation)?;
+
+    // skia expects the planes one after another, each a full stride times its height.
+    let plane_rows = [
+        (planes.y, planes.y_stride, height),
+        (planes.u, planes.uv_stride, uv_height),
+        (planes.v, planes.uv_stride, uv_height),
+    ];
+    let mut bytes = Vec::with_capacity(pixmap_info.compute_total_bytes(None));
+    for (plane, stride, rows) in plane_rows {
+        let end = bytes.len() + stride * rows;
+        bytes.extend_from_slice(&plane[..plane.len().min(stride * rows)]);
+        bytes.resize(end, 0);
+    }
+    let pixmaps = YUVAPixmaps::from_data(&pixmap_info, Data::new_copy(&bytes))
+        .ok_or(TextureError::ImageCreation)?;
+
+    Image::from_yuva_pixmaps(context, &pixmaps, skia_safe::gpu::Mipmapped::No, true, None)
+        .ok_or(TextureError::ImageCreation)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::epaint::Mesh;
+    use egui::{pos2, Color32, Rect, TextureId};
+    use skia_safe::Color;
+
+    use super::*;
+    use crate::test_util::{assert_color_near, painter, pixel, surface};
+
+    /// A frame of 8 by 2 pixels with white, red, green and blue bars of 2 pixels each.
+    const Y: [u8; 16] = [255, 255, 76, 76, 150, 150, 29, 29, 255, 255, 76, 76, 150, 150, 29, 29];
+    const U: [u8; 4] = [128, 85, 44, 255];
+    const V: [u8; 4] = [128, 255, 21, 107];
+
+    fn bars() -> YuvPlanes<'static> {
+        YuvPlanes {
+            size: [8, 2],
+            y: &Y,
+            y_stride: 8,
+            u: &U,
+            v: &V,
+            uv_stride: 4,
+        }
+    }
+
+    #[test]
+    fn video_texture_samples_yuv_bars_as_rgb() {
+        let mut painter = painter();
+        let id = TextureId::User(1);
+        painter
+            .update_video_texture(id, bars(), YuvColorSpace::Jpeg)
+            .unwrap();
+
+        let mut mesh = Mesh::with_texture(id);
+        let rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(8.0, 2.0));
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+        let mut surface = surface(8, 2);
+        painter.draw_mesh(surface.canvas(), 1.0, None, &mesh);
+
+        let expected = [
+            Color::WHITE,
+            Color::from_rgb(254, 0, 0),
+            Color::from_rgb(0, 255, 1),
+            Color::from_rgb(0, 0, 254),
+        ];
+        for (bar, color) in expected.into_iter().enumerate() {
+            for x in [bar as i32 * 2, bar as i32 * 2 + 1] {
+                assert_color_near(pixel(&mut surface, x, 0), color, 2);
+                assert_color_near(pixel(&mut surface, x, 1), color, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn planes_too_small_are_rejected() {
+        let planes = YuvPlanes {
+            y: &Y[..15],
+            ..bars()
+        };
+        assert!(matches!(
+            yuv_to_image(&planes, YuvColorSpace::Jpeg),
+            Err(TextureError::InvalidData(_))
+        ));
+    }
+}